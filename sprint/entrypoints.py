@@ -34,6 +34,8 @@ def main():
         help=("Threshold on the max number of times a residue can be involved in a HSP before being removed."))
     hsp_extraction.add_argument("-r", "--process_hsps", action="store_true",
             help=("Whether the HSPs should be processed to remove resides that occur frequently within HSPs."))
+    hsp_extraction.add_argument("--matrix", type=str, required=False, default="PAM120",
+        help=("Substitution matrix (PAM120, PAM250, BLOSUM45, BLOSUM62 or path to a matrix in the NCBI format)."))
     hsp_extraction.set_defaults(func=extract_hsps)
 
     # HSP processing
@@ -62,6 +64,8 @@ def main():
         help=("Path to the training pairs (one per line separated by a space or tab)."))
     all_to_all.add_argument("-k", "--kmer_size", type=int, required=False, default=20,
         help=("Minimum length of an HSP."))
+    all_to_all.add_argument("--matrix", type=str, required=False, default="PAM120",
+        help=("Substitution matrix (PAM120, PAM250, BLOSUM45, BLOSUM62 or path to a matrix in the NCBI format)."))
     all_to_all.set_defaults(func=score_all_to_all)
 
     # Peptide scoring
@@ -82,6 +86,8 @@ def main():
         help=("Threshold at which two s-mers are considered similar."))
     peptide_scoring.add_argument("-r", "--t_hsp", type=int, required=False, default=35,
         help=("Threshold at which two HSPs are considered similar."))
+    peptide_scoring.add_argument("--matrix", type=str, required=False, default="PAM120",
+        help=("Substitution matrix (PAM120, PAM250, BLOSUM45, BLOSUM62 or path to a matrix in the NCBI format)."))
     peptide_scoring.set_defaults(func=score_peptides)
 
     args = parser.parse_args()
@@ -16,12 +16,17 @@ def main(args):
     logger.info("Loading the protein sequences...")
     proteins = [(p.id, str(p.seq)) for p in Bio.SeqIO.parse(args.input, "fasta")]
 
+    if args.process_hsps is False:
+        logger.info(f"Streaming the HSPs of the {len(proteins)} provided protein sequences to {args.output}...")
+        sprint.extract_hsps_to_file(proteins, args.output, matrix=args.matrix)
+        logger.info(f"Done!")
+        return
+
     logger.info(f"Extracting the HSPs from the {len(proteins)} provided protein sequences...")
-    hsps = sprint.extract_hsps(proteins)
+    hsps = sprint.extract_hsps(proteins, matrix=args.matrix)
 
-    if args.process_hsps is True:
-        logger.info(f"Processing the HSPs to account for overrepresented residues...")
-        hsps = sprint.process_hsps(proteins, hsps, kmer_size=args.kmer_size, t_count=args.t_count)
+    logger.info(f"Processing the HSPs to account for overrepresented residues...")
+    hsps = sprint.process_hsps(proteins, hsps, kmer_size=args.kmer_size, t_count=args.t_count)
 
     logger.info(f"Writing to a file...")
     with open(args.output, "w") as output_file:
@@ -0,0 +1,91 @@
+use clap::Parser;
+
+use sprint::sprint::{
+    error::SprintError,
+    crossvalidation::{cross_validate, CrossValidationSettings},
+    fileio::{load_pairs, open_hsps, save_cross_validation},
+    options::{ScoringOptions, SequenceOptions},
+    prediction::missing_training_pairs,
+};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct CrossValidationArgs {
+    #[clap(value_parser, short = 'i', long = "sequences")]
+    pub sequences_path: String,
+
+    #[clap(value_parser, short = 's', long = "hsps")]
+    pub hsps_path: String,
+
+    #[clap(value_parser, short = 'r', long = "training_pairs")]
+    pub training_pairs_path: String,
+
+    #[clap(value_parser, short = 'o', long = "output")]
+    pub output_path: String,
+
+    #[clap(value_parser, short = 'f', long = "folds", default_value = "10")]
+    pub folds: usize,
+
+    /// Hold out every training pair on its own (overrides --folds)
+    #[clap(action, long = "leave_one_out")]
+    pub leave_one_out: bool,
+
+    /// Number of random negative pairs scored per held-out training pair
+    #[clap(value_parser, short = 'n', long = "negatives", default_value = "1")]
+    pub negatives: usize,
+
+    /// Draw the proteins of the negative pairs in proportion to their number
+    /// of training pairs
+    #[clap(action, long = "degree_preserving")]
+    pub degree_preserving: bool,
+
+    #[clap(value_parser, long = "random_seed", default_value = "0")]
+    pub random_seed: u64,
+
+    #[clap(flatten)]
+    pub scoring: ScoringOptions,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
+}
+
+fn main() -> Result<(), SprintError> {
+    let args = CrossValidationArgs::parse();
+
+    // Load the sequences
+    let (protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
+
+    // Load the processed HSPs (binary files are memory-mapped)
+    let hsps = open_hsps(&args.hsps_path, &protein_set)?;
+
+    // Load the training pairs to split into folds
+    let training_pairs = load_pairs(&args.training_pairs_path)?;
+    missing_training_pairs(&protein_set, &training_pairs).print_warnings(&args.training_pairs_path);
+    let folds = match args.leave_one_out {
+        true => training_pairs.len(),
+        false => args.folds,
+    };
+
+    // Load the substitution matrix
+    let matrix = args.scoring.matrix()?;
+
+    let results = cross_validate(
+        &protein_set,
+        hsps.as_ref(),
+        &training_pairs,
+        &CrossValidationSettings {
+            folds,
+            negatives_per_positive: args.negatives,
+            degree_preserving: args.degree_preserving,
+            random_seed: args.random_seed,
+        },
+        &args.scoring.settings(&matrix),
+        true,
+    );
+
+    // Save the scores of the held-out pairs to a file
+    save_cross_validation(&results, &protein_set, &args.output_path)?;
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+
+use clap::Parser;
+
+use sprint::sprint::{
+    error::SprintError,
+    evaluation::{evaluate, label_pairs, ordered_pair},
+    fileio::{load_pairs, load_scores, save_curve, save_evaluation},
+};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct EvaluationArgs {
+    /// Scores to evaluate (`protein1 protein2 score` lines)
+    #[clap(value_parser, short = 's', long = "scores")]
+    pub scores_path: String,
+
+    #[clap(value_parser, short = 'p', long = "positives")]
+    pub positives_path: String,
+
+    #[clap(value_parser, short = 'n', long = "negatives")]
+    pub negatives_path: String,
+
+    /// Summary of the evaluation
+    #[clap(value_parser, short = 'o', long = "output")]
+    pub output_path: String,
+
+    /// Points of the ROC and precision-recall curves
+    #[clap(value_parser, short = 'c', long = "curve")]
+    pub curve_path: Option<String>,
+
+    #[clap(value_parser, long = "top_k", default_value = "10,50,100", use_value_delimiter = true)]
+    pub top_k: Vec<usize>,
+
+    #[clap(value_parser, long = "recalls", default_value = "0.1,0.25,0.5", use_value_delimiter = true)]
+    pub recalls: Vec<f32>,
+}
+
+fn main() -> Result<(), SprintError> {
+    let args = EvaluationArgs::parse();
+
+    // Load the labeled pairs, then their scores
+    let positives = load_pairs(&args.positives_path)?;
+    let negatives = load_pairs(&args.negatives_path)?;
+    let labeled_pairs: HashSet<(String, String)> = positives
+        .iter()
+        .chain(negatives.iter())
+        .map(|pair| ordered_pair(&pair.0, &pair.1))
+        .collect();
+    let scores = load_scores(&args.scores_path, &labeled_pairs)?;
+
+    let (labeled_scores, missing) = label_pairs(&positives, &negatives, |protein1, protein2| {
+        scores.get(&ordered_pair(protein1, protein2)).cloned()
+    });
+    if missing > 0 {
+        println!("WARNING: {} of the labeled pairs have no score in {} and are ignored.", missing, args.scores_path);
+    }
+
+    let evaluation = evaluate(&labeled_scores, &args.top_k, &args.recalls);
+    println!(
+        "{} positives, {} negatives: AUROC {}, AUPR {}",
+        evaluation.positives, evaluation.negatives, evaluation.auroc, evaluation.aupr
+    );
+
+    save_evaluation(&evaluation, &args.output_path)?;
+    if let Some(curve_path) = &args.curve_path {
+        save_curve(&evaluation, curve_path)?;
+    }
+
+    Ok(())
+}
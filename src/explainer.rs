@@ -0,0 +1,92 @@
+use clap::Parser;
+
+use sprint::sprint::{
+    error::SprintError,
+    explanation::explain_pair,
+    fileio::{load_pairs, open_hsps, save_explanation_json, save_explanation_tsv},
+    options::{ScoringOptions, SequenceOptions},
+    prediction::missing_training_pairs,
+};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct ExplanationArgs {
+    #[clap(value_parser, short = 'i', long = "sequences")]
+    pub sequences_path: String,
+
+    #[clap(value_parser, short = 's', long = "hsps")]
+    pub hsps_path: String,
+
+    #[clap(value_parser, short = 'r', long = "training_pairs")]
+    pub training_pairs_path: String,
+
+    #[clap(value_parser, short = 'a', long = "protein1")]
+    pub protein1: String,
+
+    #[clap(value_parser, short = 'b', long = "protein2")]
+    pub protein2: String,
+
+    #[clap(value_parser, short = 'o', long = "output")]
+    pub output_path: String,
+
+    /// Output format
+    #[clap(value_parser = ["tsv", "json"], long = "format", default_value = "tsv")]
+    pub format: String,
+
+    /// Only write the supports with the highest contributions
+    #[clap(value_parser, long = "top")]
+    pub top: Option<usize>,
+
+    #[clap(flatten)]
+    pub scoring: ScoringOptions,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
+}
+
+fn main() -> Result<(), SprintError> {
+    let args = ExplanationArgs::parse();
+
+    // Load the sequences
+    let (protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
+
+    // Load the processed HSPs (binary files are memory-mapped)
+    let hsps = open_hsps(&args.hsps_path, &protein_set)?;
+
+    // Load the training pairs used for scoring
+    let training_pairs = load_pairs(&args.training_pairs_path)?;
+    missing_training_pairs(&protein_set, &training_pairs).print_warnings(&args.training_pairs_path);
+
+    // Load the substitution matrix
+    let matrix = args.scoring.matrix()?;
+
+    let mut explanation = explain_pair(
+        &protein_set,
+        hsps.as_ref(),
+        &training_pairs,
+        &args.protein1,
+        &args.protein2,
+        args.scoring.kmer_size,
+        &matrix,
+    )?;
+
+    println!(
+        "Score of {} and {}: {} ({} supporting HSP pairs)",
+        args.protein1,
+        args.protein2,
+        explanation.score,
+        explanation.supports.len()
+    );
+
+    if let Some(top) = args.top {
+        explanation.supports.truncate(top);
+    }
+
+    match args.format.as_str() {
+        "json" => save_explanation_json(&explanation, &protein_set, &args.output_path)?,
+        _ => save_explanation_tsv(&explanation, &protein_set, &args.output_path)?,
+    }
+
+    Ok(())
+}
@@ -1,8 +1,16 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use std::collections::HashSet;
 
+use sprint::sprint::error::SprintError;
+use sprint::sprint::options::{ExtractionOptions, SequenceOptions};
 use sprint::sprint::proteinset::ProteinSet;
-use sprint::sprint::extraction::extract_hsps;
+use sprint::sprint::checkpoint::{Checkpoint, CheckpointedSink};
+use sprint::sprint::distributed::{launch_workers, merge_partial_hsps, partial_path, remove_work_directory, Worker};
+use sprint::sprint::extraction::{extract_hsps_to_sink, extraction_manifest, ExtractionSettings};
 use sprint::sprint::fileio::save_hsps;
+use sprint::sprint::hspfile::{save_binary_hsps, HspParameters};
+use sprint::sprint::hsp::HSP;
+use sprint::sprint::sink::{HspSink, ShardedHspWriter};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -13,35 +21,161 @@ pub struct ExtractionArgs {
     #[clap(value_parser, short='o', long="output")]
     pub output_path: String,
 
-    #[clap(value_parser, short='k', long="kmer_size", default_value="20")]
-    pub kmer_size: usize,
+    #[clap(action, short='b', long="binary")]
+    pub binary: bool,
 
-    #[clap(value_parser, short='s', long="t_sim", default_value="15")]
-    pub t_sim: i16,
+    #[clap(action, long="stream")]
+    pub stream: bool,
 
-    #[clap(value_parser, short='t', long="t_hsp", default_value="35")]
-    pub t_hsp: i16,
+    #[clap(value_parser, long="batch_size", default_value="10000")]
+    pub batch_size: usize,
+
+    #[clap(value_parser, long="shards", default_value="64")]
+    pub shards: usize,
+
+    #[clap(value_parser, long="resume")]
+    pub resume: Option<String>,
+
+    #[clap(value_parser, short='w', long="workers", default_value="1")]
+    pub workers: usize,
+
+    #[clap(value_parser, long="rank")]
+    pub rank: Option<usize>,
+
+    #[clap(flatten)]
+    pub extraction: ExtractionOptions,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
 }
 
-fn main() {
-    let args = ExtractionArgs::parse();
+fn main() -> Result<(), SprintError> {
+    let args = parse_args();
 
     // Load the sequences
-    let set = ProteinSet::from_file(&args.input_path).unwrap();
+    let (set, report) = args.sequences.load(&args.input_path)?;
+    report.print_warnings(&args.input_path);
+
+    // Load the substitution matrix
+    let matrix = args.extraction.matrix()?;
+
+    // Select the spaced seeds
+    let seeds = args.extraction.seeds()?;
+    let settings = args.extraction.settings(&matrix, &seeds);
+
+    let parameters = HspParameters {
+        kmer_size: args.extraction.scoring.kmer_size,
+        t_sim: Some(args.extraction.t_sim),
+        t_hsp: Some(args.extraction.t_hsp),
+        t_count: None,
+        matrix: Some(matrix.name()),
+        seeds: seeds.iter().map(|seed| seed.as_string()).collect(),
+    };
+
+    // Run the workers, then union their HSPs
+    if args.workers > 1 && args.rank.is_none() {
+        launch_workers(&args.output_path, args.workers)?;
+
+        let mut writer = ShardedHspWriter::new(&args.output_path, set.len(), args.shards)?;
+        merge_partial_hsps(&args.output_path, args.workers, &set, &mut writer)?;
+        if args.binary {
+            writer.finish_binary(&set, &parameters)?;
+        } else {
+            writer.finish_text(&set)?;
+        }
+
+        remove_work_directory(&args.output_path)?;
+        if let Some(directory) = &args.resume {
+            std::fs::remove_dir_all(directory)?;
+        }
+        return Ok(());
+    }
+
+    // A worker saves its share of the HSPs to a partial (binary) HSP file
+    let (output_path, binary) = match args.rank {
+        Some(rank) => (partial_path(&args.output_path, rank), true),
+        None => (args.output_path.clone(), args.binary),
+    };
+
+    // Stream the HSPs to disk instead of holding them in memory
+    if args.stream {
+        let mut writer = ShardedHspWriter::new(&output_path, set.len(), args.shards)?;
+        let checkpoint = extract(&args, &set, &settings, &mut writer)?;
+
+        if binary {
+            writer.finish_binary(&set, &parameters)?;
+        } else {
+            writer.finish_text(&set)?;
+        }
+
+        remove_checkpoint(&args, checkpoint)?;
+        return Ok(());
+    }
 
     // Extract the HSPs
-    let hsps = extract_hsps(
-        &set,
-        args.kmer_size,
-        args.t_sim,
-        args.t_hsp,
-        0,
-        1,
-        false,
-        true,
-        true
-    );
+    let mut hsps: HashSet<HSP> = HashSet::new();
+    let checkpoint = extract(&args, &set, &settings, &mut hsps)?;
 
     // Save the HSPs to a file
-    save_hsps(hsps, &set, &args.output_path).unwrap();
+    if binary {
+        save_binary_hsps(&hsps, &set, &parameters, &output_path)?;
+    } else {
+        save_hsps(hsps, &set, &output_path)?;
+    }
+
+    remove_checkpoint(&args, checkpoint)?;
+
+    Ok(())
+}
+
+/// Parses the arguments, the thresholds keeping the short flags (-s and -t)
+/// they have in the extractor only
+fn parse_args() -> ExtractionArgs {
+    let command = ExtractionArgs::command()
+        .mut_arg("t-sim", |arg| arg.short('s'))
+        .mut_arg("t-hsp", |arg| arg.short('t'));
+    ExtractionArgs::from_arg_matches(&command.get_matches()).unwrap_or_else(|error| error.exit())
+}
+
+/// Extracts the HSPs to a sink, saving the progress to the checkpoint given
+/// with --resume (and resuming from it)
+fn extract(
+    args: &ExtractionArgs,
+    set: &ProteinSet,
+    settings: &ExtractionSettings,
+    sink: &mut dyn HspSink
+) -> Result<Option<Checkpoint>, SprintError> {
+    let worker = match args.rank {
+        Some(rank) => Worker { rank, world_size: args.workers, verbose: true },
+        None => Worker::single(true),
+    };
+
+    match &args.resume {
+        Some(directory) => {
+            // Every worker has its own checkpoint
+            let directory = match args.rank {
+                Some(rank) => format!("{}/rank_{}", directory, rank),
+                None => directory.clone(),
+            };
+            let manifest = extraction_manifest(set, settings, worker, false, true, args.batch_size);
+            let checkpoint = Checkpoint::open(&directory, &manifest).map_err(|error| SprintError::io(&directory, error))?;
+            let mut checkpointed =
+                CheckpointedSink::new(checkpoint, sink).map_err(|error| SprintError::io(&directory, error))?;
+            extract_hsps_to_sink(set, settings, worker, false, true, args.batch_size, &mut checkpointed)?;
+            Ok(Some(checkpointed.into_checkpoint()))
+        }
+        None => {
+            extract_hsps_to_sink(set, settings, worker, false, true, args.batch_size, sink)?;
+            Ok(None)
+        }
+    }
+}
+
+/// The checkpoint is no longer needed once the HSPs are saved (the
+/// checkpoints of the workers are kept until their HSPs are merged)
+fn remove_checkpoint(args: &ExtractionArgs, checkpoint: Option<Checkpoint>) -> Result<(), SprintError> {
+    if let (Some(checkpoint), None) = (checkpoint, args.rank) {
+        checkpoint.remove()?;
+    }
+    Ok(())
 }
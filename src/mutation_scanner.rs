@@ -0,0 +1,100 @@
+use clap::Parser;
+
+use sprint::sprint::{
+    error::SprintError,
+    fileio::{load_pairs, open_hsps, save_delta_matrices, save_variant_scores},
+    mutagenesis::{delta_matrix, double_substitutions, mutation_scan, single_substitutions, variant_name, PeptideScorer},
+    options::{ExtractionOptions, SequenceOptions},
+    prediction::missing_training_pairs,
+};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct MutationScanArgs {
+    #[clap(value_parser, short = 'i', long = "sequences")]
+    pub sequences_path: String,
+
+    /// Peptides to mutate (every peptide is scanned)
+    #[clap(value_parser, short = 'p', long = "peptides")]
+    pub peptides_path: String,
+
+    #[clap(value_parser, short = 's', long = "hsps")]
+    pub hsps_path: String,
+
+    #[clap(value_parser, short = 't', long = "target_name")]
+    pub target_name: String,
+
+    #[clap(value_parser, short = 'r', long = "training_pairs")]
+    pub training_pairs_path: String,
+
+    /// Score deltas of the single substitutions (position x amino acid)
+    #[clap(value_parser, short = 'o', long = "output")]
+    pub output_path: String,
+
+    /// Also scan the double substitutions, and save their scores to this file
+    #[clap(value_parser, long = "doubles")]
+    pub doubles_path: Option<String>,
+
+    /// Number of variants whose HSPs are extracted together
+    #[clap(value_parser, long = "batch_size", default_value = "20")]
+    pub batch_size: usize,
+
+    #[clap(flatten)]
+    pub extraction: ExtractionOptions,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
+}
+
+fn main() -> Result<(), SprintError> {
+    let args = MutationScanArgs::parse();
+
+    // Load the sequences and the peptides
+    let (protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
+    let (peptides, report) = args.sequences.load_fasta(&args.peptides_path, true)?;
+    report.print_warnings(&args.peptides_path);
+
+    // Load the processed HSPs (binary files are memory-mapped)
+    let hsps = open_hsps(&args.hsps_path, &protein_set)?;
+
+    // Load the training pairs used for scoring
+    let training_pairs = load_pairs(&args.training_pairs_path)?;
+    missing_training_pairs(&protein_set, &training_pairs).print_warnings(&args.training_pairs_path);
+
+    // Load the substitution matrix and select the spaced seeds
+    let matrix = args.extraction.matrix()?;
+    let seeds = args.extraction.seeds()?;
+
+    let settings = args.extraction.settings(&matrix, &seeds);
+    let scorer = PeptideScorer::new(&protein_set, hsps.as_ref(), &training_pairs, &[args.target_name.clone()], settings)?;
+
+    let mut matrices = vec![];
+    let mut double_scores = vec![];
+    for peptide in peptides.iter() {
+        // The wild type is scored first
+        let mut variants = vec![vec![]];
+        let singles = single_substitutions(&peptide.seq());
+        variants.extend(singles.iter().cloned());
+        if args.doubles_path.is_some() {
+            variants.extend(double_substitutions(&peptide.seq()));
+        }
+
+        let scores = mutation_scan(&scorer, peptide, &variants, args.batch_size, true)?;
+
+        let deltas: Vec<f32> = scores.iter().map(|score| score - scores[0]).collect();
+        println!("Score of {} with {}: {}", peptide.name(), args.target_name, scores[0]);
+
+        matrices.push((peptide, delta_matrix(peptide, &singles, &deltas[1..])));
+        for i in 1 + singles.len()..variants.len() {
+            double_scores.push((variant_name(peptide, &variants[i]), scores[i], deltas[i]));
+        }
+    }
+
+    save_delta_matrices(&matrices, &args.output_path)?;
+    if let Some(doubles_path) = &args.doubles_path {
+        save_variant_scores(&double_scores, doubles_path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use clap::Parser;
+
+use sprint::sprint::{
+    error::SprintError,
+    fileio::{load_pairs, save_pairs},
+    negatives::sample_negatives,
+    options::SequenceOptions,
+};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct NegativeSamplingArgs {
+    #[clap(value_parser, short = 'i', long = "sequences")]
+    pub sequences_path: String,
+
+    /// Known positive pairs (e.g. the training pairs)
+    #[clap(value_parser, short = 'r', long = "positives")]
+    pub positives_path: String,
+
+    #[clap(value_parser, short = 'o', long = "output")]
+    pub output_path: String,
+
+    /// Number of negative pairs per positive pair
+    #[clap(value_parser, long = "ratio", default_value = "1")]
+    pub ratio: f32,
+
+    /// Only use the proteins of the positive pairs
+    #[clap(action, long = "interactors_only")]
+    pub interactors_only: bool,
+
+    /// Draw the proteins in proportion to their number of positive pairs
+    #[clap(action, long = "degree_preserving")]
+    pub degree_preserving: bool,
+
+    #[clap(value_parser, long = "random_seed", default_value = "0")]
+    pub random_seed: u64,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
+}
+
+fn main() -> Result<(), SprintError> {
+    let args = NegativeSamplingArgs::parse();
+
+    // Load the sequences and the positive pairs
+    let (protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
+    let positives = load_pairs(&args.positives_path)?;
+
+    let negatives = sample_negatives(
+        &protein_set,
+        &positives,
+        args.ratio,
+        args.interactors_only,
+        args.degree_preserving,
+        args.random_seed,
+    );
+    println!("Sampled {} negative pairs.", negatives.len());
+
+    save_pairs(&negatives, &args.output_path)?;
+
+    Ok(())
+}
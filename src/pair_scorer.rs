@@ -0,0 +1,95 @@
+use clap::Parser;
+
+use sprint::sprint::{
+    error::SprintError,
+    distributed::Worker,
+    fileio::{load_pairs, open_hsps, save_pair_scores},
+    prediction::{map_queries, missing_training_pairs, one_vs_all_queries, score_pairs},
+    options::{ScoringOptions, SequenceOptions},
+};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct PairScoringArgs {
+    #[clap(value_parser, short = 'i', long = "sequences")]
+    pub sequences_path: String,
+
+    #[clap(value_parser, short = 's', long = "hsps")]
+    pub hsps_path: String,
+
+    #[clap(value_parser, short = 'r', long = "training_pairs")]
+    pub training_pairs_path: String,
+
+    /// File of the pairs to score (same format as the training pairs)
+    #[clap(value_parser, short = 'q', long = "queries", required_unless_present = "protein", conflicts_with = "protein")]
+    pub queries_path: Option<String>,
+
+    /// Score this protein against every protein instead
+    #[clap(value_parser, short = 'p', long = "protein")]
+    pub protein: Option<String>,
+
+    #[clap(value_parser, short = 'o', long = "output")]
+    pub output_path: String,
+
+    #[clap(flatten)]
+    pub scoring: ScoringOptions,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
+}
+
+fn main() -> Result<(), SprintError> {
+    let args = PairScoringArgs::parse();
+
+    // Load the sequences
+    let (protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
+
+    // Select the pairs to score (the pairs of every alias of the collapsed
+    // partners are saved with the protein)
+    let (queries, names) = match (&args.queries_path, &args.protein) {
+        (Some(queries_path), _) => {
+            let pairs = load_pairs(queries_path)?;
+            let queries = map_queries(&protein_set, &pairs)?;
+            (queries, pairs.into_iter().map(|pair| vec![pair]).collect::<Vec<Vec<(String, String)>>>())
+        }
+        (None, Some(protein)) => {
+            let queries = one_vs_all_queries(&protein_set, protein_set.get_protein_by_name(protein)?.index());
+            let names = queries
+                .iter()
+                .map(|query| protein_set.names(query.1).into_iter().map(|partner| (protein.clone(), partner)).collect())
+                .collect();
+            (queries, names)
+        }
+        (None, None) => unreachable!(),
+    };
+
+    // Load the processed HSPs (binary files are memory-mapped)
+    let hsps = open_hsps(&args.hsps_path, &protein_set)?;
+
+    // Load the training pairs used for scoring
+    let training_pairs = load_pairs(&args.training_pairs_path)?;
+    missing_training_pairs(&protein_set, &training_pairs).print_warnings(&args.training_pairs_path);
+
+    // Load the substitution matrix
+    let matrix = args.scoring.matrix()?;
+
+    let scores = score_pairs(
+        &protein_set,
+        hsps.as_ref(),
+        &training_pairs,
+        &queries,
+        &args.scoring.settings(&matrix),
+        Worker::single(true),
+    );
+
+    // Save the scores to a file
+    let (names, scores): (Vec<(String, String)>, Vec<f32>) = names
+        .into_iter()
+        .zip(scores)
+        .flat_map(|(pairs, score)| pairs.into_iter().map(move |pair| (pair, score)))
+        .unzip();
+    save_pair_scores(&names, &scores, &args.output_path)?;
+
+    Ok(())
+}
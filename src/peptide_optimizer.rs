@@ -0,0 +1,152 @@
+use clap::Parser;
+
+use sprint::sprint::{
+    error::SprintError,
+    checkpoint::Checkpoint,
+    evolution::{evolution_manifest, evolve_peptides, EvolutionSettings},
+    fileio::{load_pairs, open_hsps, save_candidates},
+    mutagenesis::PeptideScorer,
+    options::{ExtractionOptions, SequenceOptions},
+    prediction::missing_training_pairs,
+};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct OptimizationArgs {
+    #[clap(value_parser, short = 'i', long = "sequences")]
+    pub sequences_path: String,
+
+    #[clap(value_parser, short = 's', long = "hsps")]
+    pub hsps_path: String,
+
+    #[clap(value_parser, short = 't', long = "target_name")]
+    pub target_name: String,
+
+    #[clap(value_parser, short = 'r', long = "training_pairs")]
+    pub training_pairs_path: String,
+
+    /// Final population, from the fittest peptide to the least fit
+    #[clap(value_parser, short = 'o', long = "output")]
+    pub output_path: String,
+
+    /// Proteins the peptides should not bind (comma-separated names)
+    #[clap(value_parser, long = "decoys")]
+    pub decoys: Option<String>,
+
+    /// Peptides of the initial population (random peptides otherwise)
+    #[clap(value_parser, short = 'p', long = "peptides")]
+    pub peptides_path: Option<String>,
+
+    /// Length of the random peptides of the initial population
+    #[clap(value_parser, short = 'l', long = "length", default_value = "15")]
+    pub length: usize,
+
+    #[clap(value_parser, long = "population_size", default_value = "50")]
+    pub population_size: usize,
+
+    #[clap(value_parser, short = 'g', long = "generations", default_value = "20")]
+    pub generations: usize,
+
+    #[clap(value_parser, long = "mutation_rate", default_value = "0.05")]
+    pub mutation_rate: f32,
+
+    #[clap(value_parser, long = "crossover_rate", default_value = "0.7")]
+    pub crossover_rate: f32,
+
+    #[clap(value_parser, long = "elite", default_value = "2")]
+    pub elite: usize,
+
+    #[clap(value_parser, long = "tournament_size", default_value = "3")]
+    pub tournament_size: usize,
+
+    /// Weight of the highest score with a decoy in the fitness
+    #[clap(value_parser, long = "decoy_weight", default_value = "1.0")]
+    pub decoy_weight: f32,
+
+    #[clap(value_parser, long = "random_seed", default_value = "0")]
+    pub random_seed: u64,
+
+    /// Directory in which the population of every generation is saved (an
+    /// interrupted run resumes from it)
+    #[clap(value_parser, long = "checkpoint")]
+    pub checkpoint: Option<String>,
+
+    /// Number of peptides whose HSPs are extracted together
+    #[clap(value_parser, long = "batch_size", default_value = "20")]
+    pub batch_size: usize,
+
+    #[clap(flatten)]
+    pub extraction: ExtractionOptions,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
+}
+
+fn main() -> Result<(), SprintError> {
+    let args = OptimizationArgs::parse();
+
+    // Load the sequences and the initial peptides
+    let (protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
+    let initial: Vec<String> = match &args.peptides_path {
+        Some(path) => {
+            let (peptides, report) = args.sequences.load_fasta(path, true)?;
+            report.print_warnings(path);
+            peptides.iter().map(|peptide| peptide.seq()).collect()
+        }
+        None => vec![],
+    };
+
+    // Load the processed HSPs (binary files are memory-mapped)
+    let hsps = open_hsps(&args.hsps_path, &protein_set)?;
+
+    // Load the training pairs used for scoring
+    let training_pairs = load_pairs(&args.training_pairs_path)?;
+    missing_training_pairs(&protein_set, &training_pairs).print_warnings(&args.training_pairs_path);
+
+    // Load the substitution matrix and select the spaced seeds
+    let matrix = args.extraction.matrix()?;
+    let seeds = args.extraction.seeds()?;
+
+    // The target first, then the decoys
+    let mut targets = vec![args.target_name.clone()];
+    if let Some(decoys) = &args.decoys {
+        targets.extend(decoys.split(',').map(|decoy| decoy.trim().to_string()).filter(|decoy| !decoy.is_empty()));
+    }
+
+    let scorer = PeptideScorer::new(
+        &protein_set,
+        hsps.as_ref(),
+        &training_pairs,
+        &targets,
+        args.extraction.settings(&matrix, &seeds),
+    )?;
+    println!("Scoring the peptides against {} proteins", scorer.len());
+
+    let settings = EvolutionSettings {
+        population_size: args.population_size,
+        generations: args.generations,
+        mutation_rate: args.mutation_rate,
+        crossover_rate: args.crossover_rate,
+        elite: args.elite,
+        tournament_size: args.tournament_size,
+        decoy_weight: args.decoy_weight,
+        batch_size: args.batch_size,
+        random_seed: args.random_seed,
+    };
+
+    // A checkpoint can only be resumed by the same evolution
+    let mut checkpoint = match &args.checkpoint {
+        Some(directory) => {
+            let manifest = evolution_manifest(&scorer, &initial, args.length, &settings);
+            Some(Checkpoint::open(directory, &manifest).map_err(|error| SprintError::io(directory, error))?)
+        }
+        None => None,
+    };
+
+    let population = evolve_peptides(&scorer, &initial, args.length, &settings, checkpoint.as_mut(), true)?;
+
+    save_candidates(&population, &args.output_path)?;
+
+    Ok(())
+}
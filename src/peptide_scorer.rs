@@ -1,10 +1,14 @@
 use clap::Parser;
+use std::fs::create_dir_all;
 
 use sprint::sprint::{
-    proteinset::ProteinSet,
+    error::SprintError,
+    options::{ExtractionOptions, SequenceOptions},
     fileio::{load_hsps, load_pairs, save_peptide_scores},
     extraction::extract_hsps,
-    prediction::score_interactions
+    prediction::{missing_training_pairs, score_interactions},
+    hspfile::{save_binary_hsps, HspFile, HspParameters},
+    distributed::{launch_workers, merge_partial_scores, partial_path, prepared_hsps_path, remove_work_directory, save_partial_scores, work_directory, Worker}
 };
 
 #[derive(Parser)]
@@ -25,43 +29,78 @@ pub struct PeptideScoringArgs {
     #[clap(value_parser, short='o', long="output")]
     pub output_path: String,
 
-    #[clap(value_parser, long="t_sim", default_value="15")]
-    pub t_sim: i16,
+    #[clap(value_parser, short='w', long="workers", default_value="1")]
+    pub workers: usize,
 
-    #[clap(value_parser, long="t_hsp", default_value="35")]
-    pub t_hsp: i16,
+    #[clap(value_parser, long="rank")]
+    pub rank: Option<usize>,
 
-    #[clap(value_parser, short='k', long="kmer_size", default_value="20")]
-    pub kmer_size: usize,
+    #[clap(flatten)]
+    pub extraction: ExtractionOptions,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
 }
 
-pub fn main() {
+pub fn main() -> Result<(), SprintError> {
 
     let args = PeptideScoringArgs::parse();
 
     // Training proteins
-    let mut protein_set = ProteinSet::from_file(&args.sequences_path).unwrap();
+    let (mut protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
 
     // Add the peptides
-    protein_set.add_from_file(&args.peptides_path, true);
+    protein_set.add_from_file(&args.peptides_path, true, &args.sequences.policy()?, &args.sequences.header_format()?)?.print_warnings(&args.peptides_path);
 
-    // Load training HSPs
-    let mut hsps = load_hsps(&args.hsps_path, &protein_set);
+    // Load the substitution matrix
+    let matrix = args.extraction.matrix()?;
 
     // Load the training pairs
-    let training_pairs = load_pairs(&args.training_pairs_path);
+    let training_pairs = load_pairs(&args.training_pairs_path)?;
+    missing_training_pairs(&protein_set, &training_pairs).print_warnings(&args.training_pairs_path);
+
+    // A worker scores its share of the training pairs with the HSPs
+    // prepared by the coordinator
+    if let Some(rank) = args.rank {
+        let hsps = HspFile::open(&prepared_hsps_path(&args.output_path), &protein_set)?;
+        let worker = Worker { rank, world_size: args.workers, verbose: false };
+        let score_matrix = score_interactions(&protein_set, &hsps, &training_pairs, &args.extraction.scoring.settings(&matrix), worker);
+        save_partial_scores(&score_matrix, &partial_path(&args.output_path, rank))?;
+        return Ok(());
+    }
+
+    // Select the spaced seeds
+    let seeds = args.extraction.seeds()?;
+
+    // Load training HSPs
+    let mut hsps = load_hsps(&args.hsps_path, &protein_set)?;
 
     // Compute the peptide HSPs
-    let peptide_hsps = extract_hsps(&protein_set, args.kmer_size, args.t_sim,
-        args.t_hsp, 0, 1, true, true, false);
+    let settings = args.extraction.settings(&matrix, &seeds);
+    let peptide_hsps = extract_hsps(&protein_set, &settings, Worker::single(false), true, true);
 
     // Merge the HSPs
     hsps.extend(peptide_hsps);
 
     // Score the interactions
-    let score_matrix = score_interactions(&protein_set, &hsps,
-        &training_pairs, args.kmer_size, 0, 1, false);
+    let score_matrix = if args.workers > 1 {
+        // Run the workers, then sum their scores
+        create_dir_all(work_directory(&args.output_path))?;
+        let parameters = HspParameters { kmer_size: args.extraction.scoring.kmer_size, ..Default::default() };
+        save_binary_hsps(&hsps, &protein_set, &parameters, &prepared_hsps_path(&args.output_path))?;
+        drop(hsps);
+
+        launch_workers(&args.output_path, args.workers)?;
+        let score_matrix = merge_partial_scores(&args.output_path, args.workers, protein_set.len(), None)?;
+        remove_work_directory(&args.output_path)?;
+        score_matrix
+    } else {
+        score_interactions(&protein_set, &hsps, &training_pairs, &settings.scoring(), Worker::single(false))
+    };
 
     // Save the scores
-    save_peptide_scores(&score_matrix, &protein_set, &args.output_path).unwrap();
+    save_peptide_scores(&score_matrix, &protein_set, &args.output_path)?;
+
+    Ok(())
 }
@@ -1,9 +1,15 @@
 use clap::Parser;
 
 use sprint::sprint::{
-    fileio::{load_hsps, load_pairs, save_scores},
-    prediction::score_interactions,
+    error::SprintError,
+    fileio::{fingerprint_file, load_pairs, open_hsps, save_scores},
+    normalization::{postprocess_scores, Normalization},
+    checkpoint::Checkpoint,
+    distributed::{launch_workers, merge_partial_scores, partial_path, remove_work_directory, save_partial_scores, Worker},
+    prediction::{missing_training_pairs, score_interactions_into, scoring_manifest},
+    options::{ScoringOptions, SequenceOptions},
     proteinset::ProteinSet,
+    scorematrix::ScoreMatrix,
 };
 
 #[derive(Parser)]
@@ -21,33 +27,167 @@ pub struct PredictionArgs {
     #[clap(value_parser, short = 'o', long = "output")]
     pub output_path: String,
 
-    #[clap(value_parser, short = 'k', long = "kmer_size", default_value = "20")]
-    pub kmer_size: usize,
+    #[clap(value_parser, long = "resume")]
+    pub resume: Option<String>,
+
+    #[clap(value_parser, long = "pairs_per_checkpoint", default_value = "10000")]
+    pub pairs_per_checkpoint: usize,
+
+    /// Keep the scores in a memory-mapped file (deleted once the scores
+    /// are saved) instead of in memory
+    #[clap(value_parser, long = "spill")]
+    pub spill: Option<String>,
+
+    /// Normalization of the scores: none, zscore, rank or degree
+    #[clap(value_parser = ["none", "zscore", "rank", "degree"], long = "normalization", default_value = "none")]
+    pub normalization: String,
+
+    /// Replace the scores by probabilities: platt or isotonic (fitted on
+    /// --positives and --negatives)
+    #[clap(value_parser = ["platt", "isotonic"], long = "calibration", requires_all = &["positives-path", "negatives-path"])]
+    pub calibration: Option<String>,
+
+    #[clap(value_parser, long = "positives")]
+    pub positives_path: Option<String>,
+
+    #[clap(value_parser, long = "negatives")]
+    pub negatives_path: Option<String>,
+
+    #[clap(value_parser, short = 'w', long = "workers", default_value = "1")]
+    pub workers: usize,
+
+    #[clap(value_parser, long = "rank")]
+    pub rank: Option<usize>,
+
+    #[clap(flatten)]
+    pub scoring: ScoringOptions,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
 }
 
-fn main() {
+fn main() -> Result<(), SprintError> {
     let args = PredictionArgs::parse();
 
     // Load the sequences
-    let protein_set = ProteinSet::from_file(&args.sequences_path).unwrap();
+    let (protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
+
+    // Run the workers, then sum their scores
+    if args.workers > 1 && args.rank.is_none() {
+        launch_workers(&args.output_path, args.workers)?;
+        let mut scores = merge_partial_scores(&args.output_path, args.workers, protein_set.len(), args.spill.as_deref())?;
+        postprocess(&mut scores, &protein_set, &load_pairs(&args.training_pairs_path)?, &args)?;
+        save_scores(&scores, &protein_set, &args.output_path)?;
+        drop(scores);
+        if let Some(spill) = &args.spill {
+            std::fs::remove_file(spill)?;
+        }
 
-    // Load the processed HSPs
-    let hsps = load_hsps(&args.hsps_path, &protein_set);
+        remove_work_directory(&args.output_path)?;
+        if let Some(directory) = &args.resume {
+            std::fs::remove_dir_all(directory)?;
+        }
+        return Ok(());
+    }
+
+    let worker = match args.rank {
+        Some(rank) => Worker { rank, world_size: args.workers, verbose: true },
+        None => Worker::single(true),
+    };
+
+    // Load the processed HSPs (binary files are memory-mapped)
+    let hsps = open_hsps(&args.hsps_path, &protein_set)?;
 
     // Load the training pairs used for scoring
-    let training_pairs = load_pairs(&args.training_pairs_path);
+    let training_pairs = load_pairs(&args.training_pairs_path)?;
+    missing_training_pairs(&protein_set, &training_pairs).print_warnings(&args.training_pairs_path);
+
+    // Load the substitution matrix
+    let matrix = args.scoring.matrix()?;
+    let settings = args.scoring.settings(&matrix);
 
-    // Score the interactions
-    let scores = score_interactions(
+    // Score the interactions (saving the progress to the checkpoint given
+    // with --resume, if any)
+    let mut checkpoint = match &args.resume {
+        Some(directory) => {
+            // Every worker has its own checkpoint
+            let directory = match args.rank {
+                Some(rank) => format!("{}/rank_{}", directory, rank),
+                None => directory.clone(),
+            };
+            let mut manifest = scoring_manifest(
+                &protein_set,
+                &training_pairs,
+                &settings,
+                worker,
+                args.pairs_per_checkpoint,
+            );
+            let hsps_size = std::fs::metadata(&args.hsps_path).map_err(|error| SprintError::io(&args.hsps_path, error))?.len();
+            let hsps_fingerprint = fingerprint_file(&args.hsps_path)?;
+            manifest.push_str(&format!("\nhsps={} {:016x}", hsps_size, hsps_fingerprint));
+            Some(Checkpoint::open(&directory, &manifest).map_err(|error| SprintError::io(&directory, error))?)
+        }
+        None => None,
+    };
+
+    // Every worker spills its scores to its own file
+    let spill = args.spill.as_ref().map(|spill| match args.rank {
+        Some(rank) => format!("{}.rank_{}", spill, rank),
+        None => spill.clone(),
+    });
+    let scores = ScoreMatrix::allocate(protein_set.len(), spill.as_deref())?;
+
+    let mut scores = score_interactions_into(
         &protein_set,
-        &hsps,
+        hsps.as_ref(),
         &training_pairs,
-        args.kmer_size,
-        0,
-        1,
-        true,
-    );
+        &settings,
+        worker,
+        scores,
+        checkpoint.as_mut().map(|checkpoint| (checkpoint, args.pairs_per_checkpoint)),
+    )?;
+
+    // A worker saves its partial scores (and keeps its checkpoint until
+    // the scores of all the workers are summed)
+    if let Some(rank) = args.rank {
+        save_partial_scores(&scores, &partial_path(&args.output_path, rank))?;
+        drop(scores);
+        if let Some(spill) = &spill {
+            std::fs::remove_file(spill)?;
+        }
+        return Ok(());
+    }
 
     // Save the scores to a file
-    save_scores(&scores, &protein_set, &args.output_path).unwrap();
+    postprocess(&mut scores, &protein_set, &training_pairs, &args)?;
+    save_scores(&scores, &protein_set, &args.output_path)?;
+    drop(scores);
+    if let Some(spill) = &spill {
+        std::fs::remove_file(spill)?;
+    }
+
+    // The checkpoint is no longer needed once the scores are saved
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.remove()?;
+    }
+
+    Ok(())
+}
+
+/// Normalizes and calibrates the scores as requested
+fn postprocess(scores: &mut ScoreMatrix, protein_set: &ProteinSet, training_pairs: &[(String, String)], args: &PredictionArgs) -> Result<(), SprintError> {
+    let normalization = Normalization::from_name(&args.normalization)?;
+    let positives = args.positives_path.as_deref().map(load_pairs).transpose()?.unwrap_or_default();
+    let negatives = args.negatives_path.as_deref().map(load_pairs).transpose()?.unwrap_or_default();
+
+    postprocess_scores(
+        scores,
+        protein_set,
+        training_pairs,
+        normalization,
+        args.calibration.as_deref(),
+        &positives,
+        &negatives,
+    )
 }
@@ -1,7 +1,10 @@
 use clap::Parser;
 use std::path::Path;
 
-use sprint::sprint::{fileio::{load_hsps, save_hsps}, proteinset::ProteinSet, processing::{process_hsps}};
+use sprint::sprint::error::SprintError;
+use sprint::sprint::{fileio::{load_hsps, save_hsps}, options::SequenceOptions, processing::{process_hsps}};
+use sprint::sprint::compression::Compression;
+use sprint::sprint::hspfile::{is_binary_hsp_file, save_binary_hsps, HspFile, HspParameters};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -20,25 +23,51 @@ pub struct ProcessingArgs {
 
     #[clap(value_parser, short='c', long="count_threshold", default_value="40")]
     pub count_threshold: u16,
+
+    #[clap(action, short='b', long="binary")]
+    pub binary: bool,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
 }
 
-fn main() {
+fn main() -> Result<(), SprintError> {
     let args = ProcessingArgs::parse();
 
-    // Ensure that the output has a .phsp extension (processed hsp)
-    let output_path = Path::new(&args.output_path);
-    let output_filepath = output_path.with_extension("phsp");
+    // Ensure that the output has a .phsp extension (processed hsp), before
+    // the extension of the compression if any
+    let compression = Compression::from_path(&args.output_path);
+    let output_path = Path::new(&args.output_path[..args.output_path.len() - compression.extension().len()]);
+    let mut output_filepath = output_path.with_extension("phsp").to_string_lossy().to_string();
+    // Binary HSP files are memory-mapped, so they are never compressed
+    if !args.binary {
+        output_filepath.push_str(compression.extension());
+    }
 
     // Load the sequences
-    let protein_set = ProteinSet::from_file(&args.sequences_path).unwrap();
+    let (protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
 
     // Load the unprocessed HSPs
-    let hsps = load_hsps(&args.hsps_path, &protein_set);
+    let hsps = load_hsps(&args.hsps_path, &protein_set)?;
 
     // Process the HSPs
     let processed = process_hsps(
         &protein_set, hsps, args.kmer_size, args.count_threshold, true);
 
     // Save the processed HSPs to a file
-    save_hsps(processed, &protein_set, &output_filepath.to_str().unwrap()).unwrap();
+    if args.binary {
+        // Keep the parameters of the extraction if they are known
+        let mut parameters = if is_binary_hsp_file(&args.hsps_path)? {
+            HspFile::open(&args.hsps_path, &protein_set)?.header().parameters.clone()
+        } else {
+            HspParameters { kmer_size: args.kmer_size, ..Default::default() }
+        };
+        parameters.t_count = Some(args.count_threshold);
+        save_binary_hsps(&processed, &protein_set, &parameters, &output_filepath)?;
+    } else {
+        save_hsps(processed, &protein_set, &output_filepath)?;
+    }
+
+    Ok(())
 }
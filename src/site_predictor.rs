@@ -1,6 +1,11 @@
 use clap::Parser;
+use std::fs::create_dir_all;
 
-use sprint::sprint::{fileio::{load_fasta, load_hsps, load_pairs, save_contributions}, proteinset::ProteinSet, extraction::{extract_hsps}, sites::{compute_contributions}};
+use sprint::sprint::error::SprintError;
+use sprint::sprint::{fileio::{load_hsps, load_pairs, save_contributions, save_segments}, options::{ExtractionOptions, SequenceOptions}, prediction::missing_training_pairs, extraction::{extract_hsps}};
+use sprint::sprint::sites::{binding_segments, compute_contributions, smooth_profile, ResidueWeighting};
+use sprint::sprint::distributed::{launch_workers, merge_partial_contributions, partial_path, prepared_hsps_path, remove_work_directory, save_partial_contributions, work_directory, Worker};
+use sprint::sprint::hspfile::{save_binary_hsps, HspFile, HspParameters};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -20,56 +25,121 @@ pub struct SitePredictionArgs {
     #[clap(value_parser, short='r', long="training_pairs")]
     pub training_pairs_path: String,
 
-    #[clap(value_parser, long="t_sim", default_value="15")]
-    pub t_sim: i16,
-
-    #[clap(value_parser, long="t_hsp", default_value="35")]
-    pub t_hsp: i16,
-
     #[clap(value_parser, short='o', long="output")]
     pub output_path: String,
 
-    #[clap(value_parser, long="kmer_size", default_value="20")]
-    pub kmer_size: usize,
+    /// Distribution of the contributions over the similarity regions
+    #[clap(value_parser = ["uniform", "substitution"], long="weighting", default_value="uniform")]
+    pub weighting: String,
+
+    /// Size of the window of the moving average smoothing the contributions
+    #[clap(value_parser, long="window", default_value="1")]
+    pub window: usize,
+
+    /// File of the predicted binding segments of the target
+    #[clap(value_parser, long="segments")]
+    pub segments_path: Option<String>,
+
+    /// Number of binding segments per peptide
+    #[clap(value_parser, long="num_segments", default_value="5")]
+    pub num_segments: usize,
+
+    #[clap(value_parser, short='w', long="workers", default_value="1")]
+    pub workers: usize,
+
+    #[clap(value_parser, long="rank")]
+    pub rank: Option<usize>,
+
+    #[clap(flatten)]
+    pub extraction: ExtractionOptions,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
 }
 
-fn main() {
+fn main() -> Result<(), SprintError> {
 
     let args = SitePredictionArgs::parse();
 
     // Load the sequences
-    let mut protein_set = ProteinSet::from_file(&args.sequences_path).unwrap();
+    let (mut protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
 
     // Load the peptides and add to the protein set
-    let peptides = load_fasta(&args.peptides_path, true).unwrap();
-    protein_set.add_new(peptides, true);
+    let (peptides, report) = args.sequences.load_fasta(&args.peptides_path, true)?;
+    report.print_warnings(&args.peptides_path);
+    protein_set.add_new(peptides, true)?;
+
+    // Load the substitution matrix
+    let matrix = args.extraction.matrix()?;
+
+    // Load the training pairs used for scoring
+    let training_pairs = load_pairs(&args.training_pairs_path)?;
+    missing_training_pairs(&protein_set, &training_pairs).print_warnings(&args.training_pairs_path);
+
+    let weighting = ResidueWeighting::from_name(&args.weighting)?;
+
+    // A worker uses its share of the training pairs with the HSPs prepared
+    // by the coordinator
+    if let Some(rank) = args.rank {
+        let hsps = HspFile::open(&prepared_hsps_path(&args.output_path), &protein_set)?;
+        let contributions = compute_contributions(
+            &args.target_name, &protein_set, &hsps, &training_pairs, &args.extraction.scoring.settings(&matrix), weighting,
+            Worker { rank, world_size: args.workers, verbose: true })?;
+        save_partial_contributions(&contributions, &partial_path(&args.output_path, rank))?;
+        return Ok(());
+    }
+
+    // Select the spaced seeds
+    let seeds = args.extraction.seeds()?;
 
     // Load the processed HSPs
-    let mut hsps = load_hsps(&args.hsps_path, &protein_set);
+    let mut hsps = load_hsps(&args.hsps_path, &protein_set)?;
 
     // Add the HSPs from the peptide sequences to these
-    let new_hsps = extract_hsps(
-        &protein_set,
-        args.kmer_size,
-        args.t_sim,
-        args.t_hsp,
-        0,
-        1,
-        true, // Only new HSPs (those involving peptides)
-        true,
-        true
-    );
+    let settings = args.extraction.settings(&matrix, &seeds);
+    // Only new HSPs (those involving peptides)
+    let new_hsps = extract_hsps(&protein_set, &settings, Worker::single(true), true, true);
 
     hsps.extend(new_hsps);
 
-    // Load the training pairs used for scoring
-    let training_pairs = load_pairs(&args.training_pairs_path);
-
     // Compute the contributions of residues within the target to the interaction score
     // for the peptides of interest (new)
-    let contributions = compute_contributions(
-        &args.target_name, &protein_set, &hsps, &training_pairs, args.kmer_size, 0, 1, true);
+    let mut contributions = if args.workers > 1 {
+        // Run the workers, then sum their contributions
+        create_dir_all(work_directory(&args.output_path))?;
+        let parameters = HspParameters { kmer_size: args.extraction.scoring.kmer_size, ..Default::default() };
+        save_binary_hsps(&hsps, &protein_set, &parameters, &prepared_hsps_path(&args.output_path))?;
+        drop(hsps);
+
+        launch_workers(&args.output_path, args.workers)?;
+        let contributions = merge_partial_contributions(&args.output_path, args.workers)?;
+        remove_work_directory(&args.output_path)?;
+        contributions
+    } else {
+        compute_contributions(
+            &args.target_name, &protein_set, &hsps, &training_pairs, &settings.scoring(), weighting, Worker::single(true))?
+    };
+
+    // Smooth the (merged) contributions
+    for contributions in contributions.values_mut() {
+        smooth_profile(contributions, args.window);
+    }
 
     // Save the scores to a file
-    save_contributions(&contributions, &protein_set, &args.output_path).unwrap();
+    save_contributions(&contributions, &protein_set, &args.output_path)?;
+
+    if let Some(segments_path) = &args.segments_path {
+        let mut peptides: Vec<&usize> = contributions.keys().collect();
+        peptides.sort();
+        let segments: Vec<(String, _)> = peptides
+            .into_iter()
+            .map(|peptide| {
+                (protein_set.get_protein_by_id(*peptide).name(), binding_segments(&contributions[peptide], args.num_segments))
+            })
+            .collect();
+        save_segments(&segments, "peptide", segments_path)?;
+    }
+
+    Ok(())
 }
@@ -0,0 +1,145 @@
+use clap::Parser;
+
+use sprint::sprint::{
+    error::SprintError,
+    fileio::{load_pairs, open_hsps, save_profiles_csv, save_profiles_track, save_segments},
+    options::{ScoringOptions, SequenceOptions},
+    prediction::missing_training_pairs,
+    sites::{
+        binding_segments, interface_propensity, normalize_profile, pair_profiles, smooth_profile, ProfileNormalization,
+        ResidueWeighting,
+    },
+};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct SiteProfilingArgs {
+    #[clap(value_parser, short = 'i', long = "sequences")]
+    pub sequences_path: String,
+
+    #[clap(value_parser, short = 's', long = "hsps")]
+    pub hsps_path: String,
+
+    #[clap(value_parser, short = 'r', long = "training_pairs")]
+    pub training_pairs_path: String,
+
+    #[clap(value_parser, short = 'a', long = "protein1")]
+    pub protein1: String,
+
+    /// Partner of the first protein (without it, the interface propensity
+    /// of the first protein with all the proteins is computed)
+    #[clap(value_parser, short = 'b', long = "protein2")]
+    pub protein2: Option<String>,
+
+    #[clap(value_parser, short = 'o', long = "output")]
+    pub output_path: String,
+
+    /// Distribution of the contributions over the similarity regions
+    #[clap(value_parser = ["uniform", "substitution"], long = "weighting", default_value = "uniform")]
+    pub weighting: String,
+
+    /// Size of the window of the moving average smoothing the profiles
+    #[clap(value_parser, long = "window", default_value = "1")]
+    pub window: usize,
+
+    #[clap(value_parser = ["none", "sum", "max", "zscore"], long = "normalization", default_value = "none")]
+    pub normalization: String,
+
+    /// Output format: CSV or bedGraph annotation track
+    #[clap(value_parser = ["csv", "track"], long = "format", default_value = "csv")]
+    pub format: String,
+
+    /// File of the predicted binding segments of every profile
+    #[clap(value_parser, long = "segments")]
+    pub segments_path: Option<String>,
+
+    /// Number of binding segments per profile
+    #[clap(value_parser, long = "num_segments", default_value = "5")]
+    pub num_segments: usize,
+
+    #[clap(flatten)]
+    pub scoring: ScoringOptions,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
+}
+
+fn main() -> Result<(), SprintError> {
+    let args = SiteProfilingArgs::parse();
+
+    // Load the sequences
+    let (protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
+
+    // Load the processed HSPs (binary files are memory-mapped)
+    let hsps = open_hsps(&args.hsps_path, &protein_set)?;
+
+    // Load the training pairs used for scoring
+    let training_pairs = load_pairs(&args.training_pairs_path)?;
+    missing_training_pairs(&protein_set, &training_pairs).print_warnings(&args.training_pairs_path);
+
+    // Load the substitution matrix
+    let matrix = args.scoring.matrix()?;
+    let settings = args.scoring.settings(&matrix);
+
+    let weighting = ResidueWeighting::from_name(&args.weighting)?;
+
+    let index1 = protein_set.get_protein_by_name(&args.protein1)?.index();
+    let (mut profiles, track_name) = match &args.protein2 {
+        Some(protein2) => {
+            let (profile1, profile2) = pair_profiles(
+                &protein_set,
+                hsps.as_ref(),
+                &training_pairs,
+                &args.protein1,
+                protein2,
+                &settings,
+                weighting,
+            )?;
+
+            // A protein with itself has a single profile (the sum of both sides)
+            let profiles = if *protein2 == args.protein1 {
+                let profile = profile1.iter().zip(profile2.iter()).map(|(value1, value2)| value1 + value2).collect();
+                vec![(index1, profile)]
+            } else {
+                vec![(index1, profile1), (protein_set.get_protein_by_name(protein2)?.index(), profile2)]
+            };
+            (profiles, format!("{} - {}", args.protein1, protein2))
+        }
+        None => {
+            let propensity = interface_propensity(
+                &protein_set,
+                hsps.as_ref(),
+                &training_pairs,
+                &args.protein1,
+                &settings,
+                weighting,
+                true,
+            )?;
+            (vec![(index1, propensity)], format!("{} interface propensity", args.protein1))
+        }
+    };
+
+    let normalization = ProfileNormalization::from_name(&args.normalization)?;
+    for (_, profile) in profiles.iter_mut() {
+        smooth_profile(profile, args.window);
+        normalize_profile(profile, normalization);
+    }
+
+    match args.format.as_str() {
+        "track" => save_profiles_track(&profiles, &protein_set, &track_name, &args.output_path)?,
+        _ => save_profiles_csv(&profiles, &protein_set, &args.output_path)?,
+    }
+
+    if let Some(segments_path) = &args.segments_path {
+        let segments: Vec<(String, _)> = profiles
+            .iter()
+            .map(|(protein, profile)| {
+                (protein_set.get_protein_by_id(*protein).name(), binding_segments(profile, args.num_segments))
+            })
+            .collect();
+        save_segments(&segments, "protein", segments_path)?;
+    }
+
+    Ok(())
+}
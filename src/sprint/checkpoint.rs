@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::fs::{create_dir_all, read, read_dir, read_to_string, remove_dir_all, rename, write, File};
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Write};
+use std::path::PathBuf;
+
+use crate::sprint::hsp::HSP;
+use crate::sprint::hspfile::{decode_records, encode_records, from_record, to_record};
+use crate::sprint::sink::HspSink;
+
+/// Name of the file describing the run a checkpoint belongs to
+const MANIFEST: &str = "manifest";
+
+/// Directory in which a long run saves its progress
+///
+/// Every completed unit of work is saved to its own file, written to a
+/// temporary file first and then renamed, so that an interruption never
+/// leaves a partial entry behind. The manifest holds a description of the
+/// run (inputs and parameters) and a checkpoint can only be resumed by a run
+/// with the same description.
+pub struct Checkpoint {
+    directory: PathBuf,
+    completed: HashSet<String>,
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint in `directory`, creating it if needed
+    pub fn open(directory: &str, manifest: &str) -> Result<Checkpoint, Error> {
+        let directory = PathBuf::from(directory);
+        create_dir_all(&directory)?;
+
+        let manifest_path = directory.join(MANIFEST);
+        if manifest_path.exists() {
+            if read_to_string(&manifest_path)? != manifest {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("The checkpoint in {} belongs to a run with different inputs or parameters.", directory.display()),
+                ));
+            }
+        } else {
+            write(&manifest_path, manifest)?;
+        }
+
+        let mut completed = HashSet::new();
+        for entry in read_dir(&directory)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if name != MANIFEST && !name.ends_with(".tmp") {
+                completed.insert(name);
+            }
+        }
+
+        Ok(Checkpoint { directory, completed })
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.completed.contains(key)
+    }
+
+    /// Keys of the saved entries (sorted)
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.completed.iter().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Saves an entry, replacing the previous entry with the same key
+    pub fn save(&mut self, key: &str, bytes: &[u8]) -> Result<(), Error> {
+        self.save_with(key, |writer| writer.write_all(bytes))
+    }
+
+    /// Saves an entry written by a function (e.g. streamed from a large
+    /// structure rather than copied to a buffer first)
+    pub fn save_with(&mut self, key: &str, write: impl FnOnce(&mut dyn Write) -> Result<(), Error>) -> Result<(), Error> {
+        let temporary = self.directory.join(format!("{}.tmp", key));
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        write(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        rename(&temporary, self.directory.join(key))?;
+        self.completed.insert(key.to_string());
+        Ok(())
+    }
+
+    pub fn load(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        if !self.contains(key) {
+            return Ok(None);
+        }
+        Ok(Some(read(self.directory.join(key))?))
+    }
+
+    /// Opens an entry to read it progressively
+    pub fn reader(&self, key: &str) -> Result<Option<BufReader<File>>, Error> {
+        if !self.contains(key) {
+            return Ok(None);
+        }
+        Ok(Some(BufReader::new(File::open(self.directory.join(key))?)))
+    }
+
+    /// Deletes the checkpoint (once the run has completed)
+    pub fn remove(self) -> Result<(), Error> {
+        remove_dir_all(&self.directory)
+    }
+}
+
+/// Saves the HSPs of every chunk of s-mers to a checkpoint before passing
+/// them on to another sink
+///
+/// When resuming, the HSPs of the chunks completed by the previous runs are
+/// first passed on to the sink, and these chunks are then skipped by the
+/// extraction.
+pub struct CheckpointedSink<'a> {
+    checkpoint: Checkpoint,
+    sink: &'a mut dyn HspSink,
+}
+
+impl<'a> CheckpointedSink<'a> {
+    pub fn new(checkpoint: Checkpoint, sink: &'a mut dyn HspSink) -> Result<Self, Error> {
+        for key in checkpoint.keys() {
+            let bytes = checkpoint.load(&key)?.ok_or_else(|| {
+                Error::new(ErrorKind::NotFound, format!("Missing checkpoint entry {}", key))
+            })?;
+            sink.send(decode_records(&bytes).iter().map(from_record).collect())?;
+        }
+        Ok(CheckpointedSink { checkpoint, sink })
+    }
+
+    pub fn into_checkpoint(self) -> Checkpoint {
+        self.checkpoint
+    }
+}
+
+impl<'a> HspSink for CheckpointedSink<'a> {
+    fn send(&mut self, hsps: Vec<HSP>) -> Result<(), Error> {
+        self.sink.send(hsps)
+    }
+
+    fn is_done(&self, seed: usize, chunk: usize) -> bool {
+        self.checkpoint.contains(&chunk_key(seed, chunk))
+    }
+
+    fn send_chunk(&mut self, seed: usize, chunk: usize, hsps: Vec<HSP>) -> Result<(), Error> {
+        let records: Vec<_> = hsps.iter().map(to_record).collect();
+        self.checkpoint.save(&chunk_key(seed, chunk), &encode_records(&records))?;
+        self.sink.send(hsps)
+    }
+}
+
+fn chunk_key(seed: usize, chunk: usize) -> String {
+    format!("seed_{}_chunk_{}", seed, chunk)
+}
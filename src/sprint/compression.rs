@@ -0,0 +1,177 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Error, Read, Write};
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+
+/// First bytes of gzip and zstd streams
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression level of the zstd outputs (the default of the zstd tool)
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compression of a file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Compression given by the extension of a path (`.gz` or `.zst`)
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(".gz") {
+            Compression::Gzip
+        } else if path.ends_with(".zst") {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Extension of the compressed files ("" without compression)
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    /// Compression of a file, given by its first bytes (whatever its
+    /// extension, so an empty `.gz` file is read as an empty text file)
+    pub fn detect(path: &str) -> Result<Self, Error> {
+        let mut magic = [0u8; 4];
+        let mut file = File::open(path)?;
+        let mut length = 0;
+        while length < magic.len() {
+            match file.read(&mut magic[length..])? {
+                0 => break,
+                read => length += read,
+            }
+        }
+
+        if length >= GZIP_MAGIC.len() && magic[..2] == GZIP_MAGIC {
+            Ok(Compression::Gzip)
+        } else if length == ZSTD_MAGIC.len() && magic == ZSTD_MAGIC {
+            Ok(Compression::Zstd)
+        } else {
+            Ok(Compression::None)
+        }
+    }
+}
+
+/// Opens a file for reading line by line, decompressing it on the fly if it
+/// is compressed (gzip, including multi-member files such as bgzip, or zstd)
+pub fn open_reader(path: &str) -> Result<Box<dyn BufRead + Send>, Error> {
+    let file = File::open(path)?;
+    match Compression::detect(path)? {
+        Compression::None => Ok(Box::new(BufReader::new(file))),
+        Compression::Gzip => Ok(Box::new(BufReader::new(MultiGzDecoder::new(file)))),
+        Compression::Zstd => Ok(Box::new(BufReader::new(zstd::Decoder::new(file)?))),
+    }
+}
+
+/// Temporary path a file is written to before being renamed to its path,
+/// keeping the extension of its compression (e.g. `hsps.tmp.gz` for
+/// `hsps.gz`)
+pub fn staged_path(path: &str) -> String {
+    let extension = Compression::from_path(path).extension();
+    format!("{}.tmp{}", &path[..path.len() - extension.len()], extension)
+}
+
+/// Buffered writer to a file, compressed according to the extension of its
+/// path (`finish` must be called to complete the file)
+pub enum CompressedWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl CompressedWriter {
+    pub fn create(path: &str) -> Result<Self, Error> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match Compression::from_path(path) {
+            Compression::None => CompressedWriter::Plain(file),
+            Compression::Gzip => CompressedWriter::Gzip(GzEncoder::new(file, flate2::Compression::default())),
+            Compression::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(file, ZSTD_LEVEL)?),
+        })
+    }
+
+    /// Writes the end of the compressed stream and flushes the file
+    pub fn finish(self) -> Result<(), Error> {
+        let mut file = match self {
+            CompressedWriter::Plain(file) => file,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        file.flush()
+    }
+}
+
+impl Write for CompressedWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        match self {
+            CompressedWriter::Plain(file) => file.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        match self {
+            CompressedWriter::Plain(file) => file.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temporary_path(name: &str, extension: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("sprint_compression_{}_{}{}", name, std::process::id(), extension))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn round_trip(name: &str, expected: Compression) {
+        let path = temporary_path(name, expected.extension());
+        let mut writer = CompressedWriter::create(&path).unwrap();
+        writer.write_all(b"P1 P2 1 2 20\nP1 P3 5 6 20\n").unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(Compression::detect(&path).unwrap(), expected);
+        let lines: Vec<String> = open_reader(&path).unwrap().lines().map(|line| line.unwrap()).collect();
+        assert_eq!(lines, vec!["P1 P2 1 2 20", "P1 P3 5 6 20"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn plain_files_round_trip() {
+        round_trip("plain", Compression::None);
+    }
+
+    #[test]
+    fn gzip_files_round_trip() {
+        round_trip("gzip", Compression::Gzip);
+    }
+
+    #[test]
+    fn zstd_files_round_trip() {
+        round_trip("zstd", Compression::Zstd);
+    }
+
+    #[test]
+    fn staged_paths_keep_the_compression() {
+        assert_eq!(staged_path("out.hsp"), "out.hsp.tmp");
+        assert_eq!(staged_path("out.hsp.gz"), "out.hsp.tmp.gz");
+        assert_eq!(staged_path("out.phsp.zst"), "out.phsp.tmp.zst");
+        assert_eq!(Compression::from_path(&staged_path("out.hsp.gz")), Compression::Gzip);
+    }
+}
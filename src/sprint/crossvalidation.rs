@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rayon::prelude::*;
+
+use crate::sprint::hspfile::HspSource;
+use crate::sprint::negatives::{candidate_proteins, sample_negative_indices};
+use crate::sprint::prediction::{build_hsp_table, index_pairs, prepare_training_pairs, relevant_pairs, score_cell, ScoringSettings};
+use crate::sprint::proteinset::ProteinSet;
+
+/// Number of folds beyond which the per-fold summary is not printed (e.g.
+/// leave-one-out)
+const MAX_REPORTED_FOLDS: usize = 100;
+
+/// Parameters of the held-out pairs of a cross-validation
+#[derive(Clone, Copy, Debug)]
+pub struct CrossValidationSettings {
+    pub folds: usize,
+    /// Number of random negative pairs held out per training pair
+    pub negatives_per_positive: usize,
+    /// Whether the proteins of the negative pairs are drawn in proportion to
+    /// their number of training pairs
+    pub degree_preserving: bool,
+    pub random_seed: u64,
+}
+
+/// Pair scored by a cross-validation, using only the training pairs of the
+/// other folds
+pub struct HeldOutPair {
+    pub fold: usize,
+    pub protein1: usize,
+    pub protein2: usize,
+    pub positive: bool,
+    pub score: f32,
+}
+
+/// Randomly assigns every pair to one of `folds` folds of (almost) equal
+/// sizes, or to its own fold if there are at least as many folds as pairs
+/// (leave-one-out)
+pub fn assign_folds(num_pairs: usize, folds: usize, random_seed: u64) -> Vec<usize> {
+    if folds >= num_pairs {
+        return (0..num_pairs).collect();
+    }
+
+    let mut order: Vec<usize> = (0..num_pairs).collect();
+    order.shuffle(&mut StdRng::seed_from_u64(random_seed));
+
+    let mut assignment = vec![0; num_pairs];
+    for (position, pair) in order.into_iter().enumerate() {
+        assignment[pair] = position % folds;
+    }
+    assignment
+}
+
+/// Scores every training pair using only the training pairs of the other
+/// folds, along with `negatives_per_positive` random pairs (that are not
+/// training pairs) per held-out pair, sampled like `negatives::sample_negatives`
+///
+/// Use as many folds as training pairs for a leave-one-out evaluation. The
+/// HSP table is built once, and the score of a held-out pair is the one
+/// `score_interactions` would give it without the pairs of its fold.
+pub fn cross_validate(
+    protein_set: &ProteinSet,
+    hsps: &dyn HspSource,
+    training_pairs: &[(String, String)],
+    validation: &CrossValidationSettings,
+    settings: &ScoringSettings,
+    verbose: bool,
+) -> Vec<HeldOutPair> {
+    let CrossValidationSettings { folds, negatives_per_positive, degree_preserving, random_seed } = *validation;
+    let (kmer_size, matrix) = (settings.kmer_size, settings.matrix);
+    let (pairs, interactors) = prepare_training_pairs(protein_set, training_pairs, 0, 1, verbose);
+    let fold_of = assign_folds(pairs.len(), std::cmp::max(folds, 1), random_seed);
+    let num_folds = fold_of.iter().max().map(|fold| fold + 1).unwrap_or(0);
+
+    if verbose {
+        println!("Cross-validation - {} training pairs in {} folds.", pairs.len(), num_folds);
+        println!("Cross-validation - Building the HSP table");
+    }
+    let hsp_table = build_hsp_table(hsps, protein_set, &interactors, kmer_size, matrix);
+
+    // The held-out pairs: the training pairs and the negatives of every fold
+    let mut held_out: Vec<(usize, usize, usize, bool)> = pairs
+        .iter()
+        .zip(fold_of.iter())
+        .map(|(pair, &fold)| (fold, pair.0, pair.1, true))
+        .collect();
+    let mut fold_sizes = vec![0; num_folds];
+    fold_of.iter().for_each(|&fold| fold_sizes[fold] += 1);
+
+    let mut rng = StdRng::seed_from_u64(random_seed);
+    let mut excluded: HashSet<(usize, usize)> = pairs.iter().map(|pair| ordered(*pair)).collect();
+    let candidates = candidate_proteins(protein_set, &pairs, false, degree_preserving);
+    for (fold, size) in fold_sizes.iter().enumerate() {
+        for negative in sample_negative_indices(&candidates, &mut excluded, size * negatives_per_positive, &mut rng) {
+            held_out.push((fold, negative.0, negative.1, false));
+        }
+    }
+
+    if verbose {
+        println!("Cross-validation - Scoring {} held-out pairs...", held_out.len());
+    }
+    let start = Instant::now();
+
+    let pairs_by_protein = index_pairs(&pairs, protein_set.len());
+    let results: Vec<HeldOutPair> = held_out
+        .par_iter()
+        .map(|&(fold, protein1, protein2, positive)| {
+            let protein = std::cmp::max(protein1, protein2);
+            let partner = std::cmp::min(protein1, protein2);
+
+            // Only the training pairs of the other folds are used
+            let training: Vec<usize> = relevant_pairs(hsp_table[protein].iter().map(|hsp| hsp.0), &pairs_by_protein)
+                .into_iter()
+                .filter(|&index| fold_of[index] != fold)
+                .collect();
+            let score = score_cell(protein, partner, &pairs, &training, &hsp_table, kmer_size as f32);
+
+            HeldOutPair { fold, protein1, protein2, positive, score }
+        })
+        .collect();
+
+    if verbose {
+        println!("Cross-validation - Scored the held-out pairs in {}...", start.elapsed().as_secs());
+        if num_folds <= MAX_REPORTED_FOLDS {
+            for fold in 0..num_folds {
+                let (positives, negatives): (Vec<&HeldOutPair>, Vec<&HeldOutPair>) = results
+                    .iter()
+                    .filter(|pair| pair.fold == fold)
+                    .partition(|pair| pair.positive);
+                println!(
+                    "Fold {} - {} positives (mean score {}), {} negatives (mean score {})",
+                    fold,
+                    positives.len(),
+                    mean_score(&positives),
+                    negatives.len(),
+                    mean_score(&negatives)
+                );
+            }
+        }
+    }
+
+    results
+}
+
+fn ordered(pair: (usize, usize)) -> (usize, usize) {
+    (std::cmp::min(pair.0, pair.1), std::cmp::max(pair.0, pair.1))
+}
+
+fn mean_score(pairs: &[&HeldOutPair]) -> f32 {
+    if pairs.is_empty() {
+        return 0f32;
+    }
+    pairs.iter().map(|pair| pair.score).sum::<f32>() / pairs.len() as f32
+}
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read, remove_dir_all, write, File};
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+use memmap2::Mmap;
+
+use crate::sprint::hsp::HSP;
+use crate::sprint::hspfile::HspFile;
+use crate::sprint::proteinset::ProteinSet;
+use crate::sprint::scorematrix::{num_entries, ScoreMatrix};
+use crate::sprint::sink::HspSink;
+
+/// First bytes of a file of partial scores
+const SCORES_MAGIC: &[u8; 8] = b"SPRNTSCR";
+
+/// First bytes of a file of partial contributions
+const CONTRIBUTIONS_MAGIC: &[u8; 8] = b"SPRNTCTR";
+
+/// Number of HSPs of a partial HSP file sent to a sink at a time
+const MERGE_BATCH_SIZE: usize = 100000;
+
+/// Process of a run split between `world_size` processes, doing the share
+/// of the work of its rank
+#[derive(Clone, Copy, Debug)]
+pub struct Worker {
+    pub rank: usize,
+    pub world_size: usize,
+    /// Whether the process prints its progress
+    pub verbose: bool,
+}
+
+impl Worker {
+    /// The only process of a run
+    pub fn single(verbose: bool) -> Self {
+        Worker { rank: 0, world_size: 1, verbose }
+    }
+}
+
+/// Directory holding the inputs prepared for the workers and their partial
+/// results (next to the output)
+pub fn work_directory(output: &str) -> PathBuf {
+    PathBuf::from(format!("{}.work", output))
+}
+
+/// Partial result of a worker
+pub fn partial_path(output: &str, rank: usize) -> String {
+    work_directory(output).join(format!("part_{}", rank)).to_string_lossy().to_string()
+}
+
+/// HSPs prepared by the coordinator for the workers (e.g. including the
+/// HSPs of new sequences)
+pub fn prepared_hsps_path(output: &str) -> String {
+    work_directory(output).join("hsps").to_string_lossy().to_string()
+}
+
+/// Runs the current binary as `workers` local processes, with the same
+/// arguments followed by `--rank <rank>`, and waits for all of them
+///
+/// Each worker processes the share of the work of its rank and saves its
+/// partial result to `partial_path(output, rank)`.
+pub fn launch_workers(output: &str, workers: usize) -> Result<(), Error> {
+    create_dir_all(work_directory(output))?;
+
+    let executable = std::env::current_exe()?;
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+
+    let mut children = vec![];
+    for rank in 0..workers {
+        let child = Command::new(&executable)
+            .args(&arguments)
+            .arg("--rank")
+            .arg(rank.to_string())
+            .spawn()?;
+        children.push(child);
+    }
+
+    // Wait for every worker before reporting a failure
+    let mut failures = vec![];
+    for (rank, mut child) in children.into_iter().enumerate() {
+        let status = child.wait()?;
+        if !status.success() {
+            failures.push(format!("worker {} ({})", rank, status));
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(Error::other(format!("Some workers failed: {}.", failures.join(", "))));
+    }
+    Ok(())
+}
+
+/// Deletes the inputs and partial results of the workers
+pub fn remove_work_directory(output: &str) -> Result<(), Error> {
+    remove_dir_all(work_directory(output))
+}
+
+/// Sends the HSPs of the partial (binary) HSP files of the workers to a sink
+pub fn merge_partial_hsps(
+    output: &str,
+    workers: usize,
+    protein_set: &ProteinSet,
+    sink: &mut dyn HspSink,
+) -> Result<(), Error> {
+    for rank in 0..workers {
+        let partial = HspFile::open(&partial_path(output, rank), protein_set)?;
+        let mut batch: Vec<HSP> = Vec::with_capacity(MERGE_BATCH_SIZE);
+        for hsp in partial.iter() {
+            batch.push(hsp);
+            if batch.len() == MERGE_BATCH_SIZE {
+                sink.send(std::mem::take(&mut batch))?;
+            }
+        }
+        sink.send(batch)?;
+    }
+    Ok(())
+}
+
+/// Saves a partial score matrix
+pub fn save_partial_scores(scores: &ScoreMatrix, filename: &str) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writer.write_all(SCORES_MAGIC)?;
+    writer.write_all(&(scores.size() as u64).to_le_bytes())?;
+    for score in scores.values() {
+        writer.write_all(&score.to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+/// Adds the scores of a partial score matrix to a matrix (the partial
+/// scores are memory-mapped rather than loaded)
+pub fn add_partial_scores(scores: &mut ScoreMatrix, filename: &str) -> Result<(), Error> {
+    let file = File::open(filename)?;
+    let bytes = unsafe { Mmap::map(&file)? };
+    if bytes.len() < 16 || &bytes[0..8] != SCORES_MAGIC {
+        return Err(invalid(filename));
+    }
+
+    let size = read_u64(&bytes, 8) as usize;
+    if size != scores.size() || bytes.len() != 16 + num_entries(size) * 4 {
+        return Err(invalid(filename));
+    }
+
+    scores.values_mut()
+        .iter_mut()
+        .zip(bytes[16..].chunks_exact(4))
+        .for_each(|(total, score)| *total += f32::from_le_bytes(score.try_into().unwrap()));
+    Ok(())
+}
+
+/// Sums the partial score matrices of the workers (into a memory-mapped
+/// file if one is given)
+pub fn merge_partial_scores(
+    output: &str,
+    workers: usize,
+    size: usize,
+    filename: Option<&str>,
+) -> Result<ScoreMatrix, Error> {
+    let mut scores = ScoreMatrix::allocate(size, filename)?;
+    for rank in 0..workers {
+        add_partial_scores(&mut scores, &partial_path(output, rank))?;
+    }
+    Ok(scores)
+}
+
+/// Saves partial contributions (peptide index, then the contribution of
+/// every residue of the target)
+pub fn save_partial_contributions(contributions: &HashMap<usize, Vec<f32>>, filename: &str) -> Result<(), Error> {
+    let mut bytes = CONTRIBUTIONS_MAGIC.to_vec();
+    bytes.extend_from_slice(&(contributions.len() as u64).to_le_bytes());
+    for (index, values) in contributions.iter() {
+        bytes.extend_from_slice(&(*index as u64).to_le_bytes());
+        bytes.extend_from_slice(&(values.len() as u64).to_le_bytes());
+        values.iter().for_each(|value| bytes.extend_from_slice(&value.to_le_bytes()));
+    }
+    write(filename, bytes)
+}
+
+pub fn load_partial_contributions(filename: &str) -> Result<HashMap<usize, Vec<f32>>, Error> {
+    let bytes = read(filename)?;
+    if bytes.len() < 16 || &bytes[0..8] != CONTRIBUTIONS_MAGIC {
+        return Err(invalid(filename));
+    }
+
+    let mut contributions = HashMap::new();
+    let mut offset = 16;
+    for _ in 0..read_u64(&bytes, 8) {
+        if bytes.len() < offset + 16 {
+            return Err(invalid(filename));
+        }
+        let index = read_u64(&bytes, offset) as usize;
+        let length = read_u64(&bytes, offset + 8) as usize;
+        offset += 16;
+
+        if bytes.len() < offset + length * 4 {
+            return Err(invalid(filename));
+        }
+        let values: Vec<f32> = bytes[offset..offset + length * 4]
+            .chunks_exact(4)
+            .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+            .collect();
+        offset += length * 4;
+
+        contributions.insert(index, values);
+    }
+    Ok(contributions)
+}
+
+/// Sums the partial contributions of the workers
+pub fn merge_partial_contributions(output: &str, workers: usize) -> Result<HashMap<usize, Vec<f32>>, Error> {
+    let mut contributions: HashMap<usize, Vec<f32>> = HashMap::new();
+    for rank in 0..workers {
+        for (index, values) in load_partial_contributions(&partial_path(output, rank))? {
+            let total = contributions.entry(index).or_insert_with(|| vec![0f32; values.len()]);
+            if total.len() != values.len() {
+                return Err(invalid(&partial_path(output, rank)));
+            }
+            total.iter_mut().zip(values.iter()).for_each(|(total, value)| *total += value);
+        }
+    }
+    Ok(contributions)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+fn invalid(filename: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid partial result ({})", filename))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use crate::sprint::hspfile::{save_binary_hsps, HspParameters};
+    use crate::sprint::location::Location;
+    use crate::sprint::prediction::{score_interactions, ScoringSettings};
+    use crate::sprint::protein::Protein;
+    use crate::sprint::substitution::SubstitutionMatrix;
+
+    fn protein_set() -> ProteinSet {
+        let sequences = ["MKTAYIAKQRQISFVKSHFSRQ", "MKTAYIAKQRQLSFVKSHFSRA", "GAVLIPFMWSTCYNQDEKRH", "GAVLIPFMWSTCYNQDEKRW"];
+        let proteins = sequences
+            .iter()
+            .enumerate()
+            .map(|(i, sequence)| Protein::new(i, format!("P{}", i), sequence.to_string(), false).unwrap())
+            .collect();
+        ProteinSet::new(proteins).unwrap()
+    }
+
+    fn output(name: &str) -> String {
+        let output = std::env::temp_dir().join(format!("sprint_distributed_{}_{}", name, std::process::id()));
+        let output = output.to_string_lossy().to_string();
+        create_dir_all(work_directory(&output)).unwrap();
+        output
+    }
+
+    #[test]
+    fn partial_scores_of_the_ranks_sum_to_the_scores() {
+        let protein_set = protein_set();
+        let hsps: HashSet<HSP> = [(0, 1, 0, 0, 20), (2, 3, 0, 0, 18), (1, 2, 5, 4, 10), (0, 3, 2, 1, 12)]
+            .iter()
+            .map(|&(protein1, protein2, position1, position2, length)| {
+                HSP::new(Location::new(protein1, position1), Location::new(protein2, position2), length)
+            })
+            .collect();
+        let training_pairs: Vec<(String, String)> = [("P0", "P2"), ("P1", "P3"), ("P0", "P3"), ("P2", "P2")]
+            .iter()
+            .map(|pair| (pair.0.to_string(), pair.1.to_string()))
+            .collect();
+        let matrix = SubstitutionMatrix::load("PAM120").unwrap();
+        let settings = ScoringSettings { kmer_size: 5, matrix: &matrix };
+        let expected = score_interactions(&protein_set, &hsps, &training_pairs, &settings, Worker::single(false));
+
+        let output = output("scores");
+        let workers = 3;
+        for rank in 0..workers {
+            let worker = Worker { rank, world_size: workers, verbose: false };
+            let scores = score_interactions(&protein_set, &hsps, &training_pairs, &settings, worker);
+            save_partial_scores(&scores, &partial_path(&output, rank)).unwrap();
+        }
+        let merged = merge_partial_scores(&output, workers, protein_set.len(), None).unwrap();
+        assert!(expected.values().iter().any(|&score| score > 0.0));
+        for (merged, expected) in merged.values().iter().zip(expected.values()) {
+            assert!((merged - expected).abs() <= 1e-4 * expected.abs().max(1.0), "{} != {}", merged, expected);
+        }
+
+        // The partial scores must have the size of the matrix
+        assert!(merge_partial_scores(&output, workers, protein_set.len() + 1, None).is_err());
+        remove_work_directory(&output).unwrap();
+    }
+
+    #[test]
+    fn partial_hsps_of_the_ranks_are_merged() {
+        let protein_set = protein_set();
+        let partials: Vec<HashSet<HSP>> = [vec![(0, 1, 0, 0, 20), (2, 3, 0, 0, 18)], vec![], vec![(1, 2, 5, 4, 10)]]
+            .iter()
+            .map(|hsps| {
+                hsps.iter()
+                    .map(|&(protein1, protein2, position1, position2, length)| {
+                        HSP::new(Location::new(protein1, position1), Location::new(protein2, position2), length)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let output = output("hsps");
+        for (rank, hsps) in partials.iter().enumerate() {
+            save_binary_hsps(hsps, &protein_set, &HspParameters::default(), &partial_path(&output, rank)).unwrap();
+        }
+        let mut merged: HashSet<HSP> = HashSet::new();
+        merge_partial_hsps(&output, partials.len(), &protein_set, &mut merged).unwrap();
+        assert!(merged == partials.iter().flatten().cloned().collect());
+        remove_work_directory(&output).unwrap();
+    }
+
+    #[test]
+    fn partial_contributions_are_summed() {
+        let output = output("contributions");
+        let partials = [
+            HashMap::from([(0, vec![1.0, 2.0]), (3, vec![0.5])]),
+            HashMap::from([(0, vec![0.25, 0.5])]),
+            HashMap::new(),
+        ];
+        for (rank, contributions) in partials.iter().enumerate() {
+            save_partial_contributions(contributions, &partial_path(&output, rank)).unwrap();
+        }
+        let merged = merge_partial_contributions(&output, partials.len()).unwrap();
+        assert_eq!(merged, HashMap::from([(0, vec![1.25, 2.5]), (3, vec![0.5])]));
+
+        // The contributions of a peptide must have the same length on every rank
+        save_partial_contributions(&HashMap::from([(3, vec![0.5, 1.0])]), &partial_path(&output, 2)).unwrap();
+        assert!(merge_partial_contributions(&output, partials.len()).is_err());
+        remove_work_directory(&output).unwrap();
+    }
+}
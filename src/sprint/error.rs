@@ -0,0 +1,107 @@
+use std::fmt;
+use std::io;
+
+use pyo3::exceptions::{PyFileNotFoundError, PyIOError, PyKeyError, PyValueError};
+use pyo3::PyErr;
+
+/// Errors of the loaders and pipelines
+pub enum SprintError {
+    /// A file could not be read or written
+    Io { path: Option<String>, source: io::Error },
+    /// Invalid content in a file (lines start at 1)
+    Parse { path: String, line: Option<usize>, message: String },
+    /// A character of a sequence is not an amino acid (positions start at 1)
+    InvalidResidue { protein: String, position: usize, residue: char },
+    /// A protein is not in the protein set
+    UnknownProtein(String),
+    /// Two proteins of a protein set have the same name
+    DuplicateProtein(String),
+    /// Invalid parameter or argument
+    InvalidInput(String),
+}
+
+impl SprintError {
+    pub fn io(path: &str, source: io::Error) -> Self {
+        SprintError::Io { path: Some(path.to_string()), source }
+    }
+
+    pub fn parse(path: &str, line: Option<usize>, message: impl Into<String>) -> Self {
+        SprintError::Parse { path: path.to_string(), line, message: message.into() }
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        SprintError::InvalidInput(message.into())
+    }
+
+    /// Locates an error in a file (I/O errors and errors already located are
+    /// kept as they are)
+    pub fn at(self, path: &str, line: Option<usize>) -> Self {
+        match self {
+            SprintError::Io { path: None, source } => SprintError::io(path, source),
+            SprintError::Io { .. } | SprintError::Parse { .. } => self,
+            error => SprintError::parse(path, line, error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for SprintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SprintError::Io { path: Some(path), source } => write!(f, "{}: {}", path, source),
+            SprintError::Io { path: None, source } => write!(f, "{}", source),
+            SprintError::Parse { path, line: Some(line), message } => write!(f, "{}, line {}: {}", path, line, message),
+            SprintError::Parse { path, line: None, message } => write!(f, "{}: {}", path, message),
+            SprintError::InvalidResidue { protein, position, residue } => {
+                write!(f, "Invalid residue '{}' at position {} of {}", residue.escape_debug(), position, protein)
+            }
+            SprintError::UnknownProtein(name) => write!(f, "Unknown protein: {}", name),
+            SprintError::DuplicateProtein(name) => write!(f, "Duplicate protein ID: {}", name),
+            SprintError::InvalidInput(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// The binaries return their errors from `main`, which prints them with
+/// `Debug`, so they are formatted as their messages
+impl fmt::Debug for SprintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for SprintError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SprintError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SprintError {
+    fn from(source: io::Error) -> Self {
+        SprintError::Io { path: None, source }
+    }
+}
+
+/// Missing files raise `FileNotFoundError`, other I/O errors `OSError`,
+/// unknown proteins `KeyError` and invalid contents or arguments (including
+/// invalid binary files) `ValueError`
+impl From<SprintError> for PyErr {
+    fn from(error: SprintError) -> PyErr {
+        let message = error.to_string();
+        match error {
+            SprintError::Io { source, .. } if source.kind() == io::ErrorKind::NotFound => {
+                PyFileNotFoundError::new_err(message)
+            }
+            SprintError::Io { source, .. }
+                if matches!(source.kind(), io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput) =>
+            {
+                PyValueError::new_err(message)
+            }
+            SprintError::Io { .. } => PyIOError::new_err(message),
+            SprintError::UnknownProtein(_) => PyKeyError::new_err(message),
+            _ => PyValueError::new_err(message),
+        }
+    }
+}
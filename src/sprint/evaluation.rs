@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+
+/// Point of the ROC and precision-recall curves: the pairs scoring at least
+/// `threshold` are predicted to interact
+pub struct CurvePoint {
+    pub threshold: f32,
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub precision: f32,
+    pub recall: f32,
+    pub false_positive_rate: f32,
+}
+
+/// Performance of a predictor on labeled pairs
+pub struct Evaluation {
+    pub positives: usize,
+    pub negatives: usize,
+    pub auroc: f32,
+    /// Area under the precision-recall curve (average precision)
+    pub aupr: f32,
+    /// Precision among the `k` best scoring pairs, for every requested `k`
+    pub precision_at_k: Vec<(usize, f32)>,
+    /// Best precision reaching at least the given recall, for every requested
+    /// recall
+    pub precision_at_recall: Vec<(f32, f32)>,
+    /// One point per distinct score, from the highest to the lowest
+    pub curve: Vec<CurvePoint>,
+}
+
+/// Evaluates scores given with their label (true for the positive pairs)
+///
+/// Pairs with the same score are predicted together, so ties count as half
+/// correctly ordered in the AUROC. The precision at `k` follows the order
+/// of the pairs among ties.
+pub fn evaluate(labeled_scores: &[(f32, bool)], top_k: &[usize], recalls: &[f32]) -> Evaluation {
+    let mut sorted: Vec<(f32, bool)> = labeled_scores.to_vec();
+    sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let positives = sorted.iter().filter(|pair| pair.1).count();
+    let negatives = sorted.len() - positives;
+
+    let mut curve: Vec<CurvePoint> = vec![];
+    let mut true_positives = 0;
+    let mut false_positives = 0;
+    for (i, &(score, positive)) in sorted.iter().enumerate() {
+        match positive {
+            true => true_positives += 1,
+            false => false_positives += 1,
+        }
+
+        // One point after the last pair with a given score
+        if i + 1 < sorted.len() && sorted[i + 1].0 == score {
+            continue;
+        }
+        curve.push(CurvePoint {
+            threshold: score,
+            true_positives,
+            false_positives,
+            precision: true_positives as f32 / (true_positives + false_positives) as f32,
+            recall: ratio(true_positives, positives),
+            false_positive_rate: ratio(false_positives, negatives),
+        });
+    }
+
+    // Trapezoids between the points of the ROC curve (from (0, 0))
+    let mut auroc = 0f32;
+    let mut previous = (0f32, 0f32);
+    for point in curve.iter() {
+        auroc += (point.false_positive_rate - previous.0) * (point.recall + previous.1) / 2f32;
+        previous = (point.false_positive_rate, point.recall);
+    }
+
+    // Precision weighted by the increase in recall
+    let mut aupr = 0f32;
+    let mut previous_recall = 0f32;
+    for point in curve.iter() {
+        aupr += (point.recall - previous_recall) * point.precision;
+        previous_recall = point.recall;
+    }
+
+    let precision_at_k = top_k
+        .iter()
+        .map(|&k| {
+            let top = std::cmp::min(k, sorted.len());
+            let correct = sorted[..top].iter().filter(|pair| pair.1).count();
+            (k, ratio(correct, top))
+        })
+        .collect();
+
+    let precision_at_recall = recalls
+        .iter()
+        .map(|&recall| {
+            let precision = curve
+                .iter()
+                .filter(|point| point.recall >= recall)
+                .map(|point| point.precision)
+                .fold(0f32, f32::max);
+            (recall, precision)
+        })
+        .collect();
+
+    Evaluation {
+        positives,
+        negatives,
+        auroc,
+        aupr,
+        precision_at_k,
+        precision_at_recall,
+        curve,
+    }
+}
+
+/// Looks up the scores of the positive and negative pairs
+///
+/// As in `load_pairs`, the order of the proteins of a pair does not matter
+/// and a pair is only used once: a negative pair that is also a positive pair
+/// is ignored. `score` gives the score of two proteins (in any order), if
+/// known. Returns the labeled scores and the number of pairs without a score.
+pub fn label_pairs(
+    positives: &[(String, String)],
+    negatives: &[(String, String)],
+    score: impl Fn(&str, &str) -> Option<f32>,
+) -> (Vec<(f32, bool)>, usize) {
+    let mut seen: HashSet<(String, String)> = HashSet::new();
+    let mut labeled_scores = vec![];
+    let mut missing = 0;
+
+    let labeled_pairs = positives
+        .iter()
+        .map(|pair| (pair, true))
+        .chain(negatives.iter().map(|pair| (pair, false)));
+    for (pair, positive) in labeled_pairs {
+        if !seen.insert(ordered_pair(&pair.0, &pair.1)) {
+            continue;
+        }
+        match score(&pair.0, &pair.1) {
+            Some(score) => labeled_scores.push((score, positive)),
+            None => missing += 1,
+        }
+    }
+
+    (labeled_scores, missing)
+}
+
+/// Pair of protein names in a canonical order, so that both orders of a
+/// pair have the same key
+pub fn ordered_pair(protein1: &str, protein2: &str) -> (String, String) {
+    match protein1 <= protein2 {
+        true => (protein1.to_string(), protein2.to_string()),
+        false => (protein2.to_string(), protein1.to_string()),
+    }
+}
+
+fn ratio(count: usize, total: usize) -> f32 {
+    match total {
+        0 => 0f32,
+        _ => count as f32 / total as f32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 1e-6, "{} != {}", value, expected);
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|pair| (pair.0.to_string(), pair.1.to_string())).collect()
+    }
+
+    #[test]
+    fn metrics_of_a_known_ranking() {
+        let evaluation = evaluate(&[(0.6, false), (0.9, true), (0.7, true), (0.8, false)], &[1, 3, 10], &[0.5, 1.0]);
+        assert_eq!((evaluation.positives, evaluation.negatives), (2, 2));
+
+        // 3 of the 4 (positive, negative) pairs are correctly ordered
+        assert_close(evaluation.auroc, 0.75);
+        assert_close(evaluation.aupr, 0.5 * 1.0 + 0.5 * 2.0 / 3.0);
+
+        let points: Vec<(f32, usize, usize)> = evaluation
+            .curve
+            .iter()
+            .map(|point| (point.threshold, point.true_positives, point.false_positives))
+            .collect();
+        assert_eq!(points, vec![(0.9, 1, 0), (0.8, 1, 1), (0.7, 2, 1), (0.6, 2, 2)]);
+
+        let precision_at_k: Vec<usize> = evaluation.precision_at_k.iter().map(|&(k, _)| k).collect();
+        assert_eq!(precision_at_k, vec![1, 3, 10]);
+        for (&(_, precision), expected) in evaluation.precision_at_k.iter().zip([1.0, 2.0 / 3.0, 0.5]) {
+            assert_close(precision, expected);
+        }
+        for (&(_, precision), expected) in evaluation.precision_at_recall.iter().zip([1.0, 2.0 / 3.0]) {
+            assert_close(precision, expected);
+        }
+    }
+
+    #[test]
+    fn ties_are_half_correctly_ordered() {
+        let evaluation = evaluate(&[(1.0, true), (1.0, false), (0.0, false)], &[], &[]);
+        assert_eq!(evaluation.curve.len(), 2);
+        assert_close(evaluation.auroc, 0.75);
+        assert_close(evaluation.aupr, 0.5);
+    }
+
+    #[test]
+    fn perfect_and_empty_rankings() {
+        let evaluation = evaluate(&[(0.9, true), (0.8, true), (0.1, false)], &[2], &[1.0]);
+        assert_close(evaluation.auroc, 1.0);
+        assert_close(evaluation.aupr, 1.0);
+        assert_close(evaluation.precision_at_k[0].1, 1.0);
+
+        let evaluation = evaluate(&[], &[5], &[0.5]);
+        assert_eq!((evaluation.positives, evaluation.negatives), (0, 0));
+        assert_close(evaluation.auroc, 0.0);
+        assert_close(evaluation.precision_at_k[0].1, 0.0);
+    }
+
+    #[test]
+    fn labeled_pairs_are_used_once() {
+        let positives = pairs(&[("A", "B"), ("B", "A"), ("C", "D")]);
+        let negatives = pairs(&[("D", "C"), ("A", "C"), ("E", "F")]);
+        let (labeled_scores, missing) = label_pairs(&positives, &negatives, |protein1, protein2| {
+            match ordered_pair(protein1, protein2) {
+                pair if pair == ordered_pair("A", "B") => Some(1.0),
+                pair if pair == ordered_pair("C", "D") => Some(0.5),
+                pair if pair == ordered_pair("A", "C") => Some(0.25),
+                _ => None,
+            }
+        });
+        assert_eq!(labeled_scores, vec![(1.0, true), (0.5, true), (0.25, false)]);
+        assert_eq!(missing, 1);
+    }
+}
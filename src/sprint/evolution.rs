@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+
+use crate::sprint::checkpoint::Checkpoint;
+use crate::sprint::constants::AMINO_ACIDS;
+use crate::sprint::error::SprintError;
+use crate::sprint::mutagenesis::PeptideScorer;
+use crate::sprint::protein::Protein;
+
+/// Prefix of the checkpoint entries holding the population of a generation
+const GENERATION_PREFIX: &str = "generation_";
+
+/// Parameters of the evolution of a population of peptides
+#[pyclass]
+#[derive(Clone)]
+pub struct EvolutionSettings {
+    #[pyo3(get, set)]
+    pub population_size: usize,
+    /// Number of generations bred after the initial population
+    #[pyo3(get, set)]
+    pub generations: usize,
+    /// Probability of substituting every residue of an offspring
+    #[pyo3(get, set)]
+    pub mutation_rate: f32,
+    /// Probability of breeding an offspring from two parents rather than one
+    #[pyo3(get, set)]
+    pub crossover_rate: f32,
+    /// Number of best peptides carried over unchanged to the next generation
+    #[pyo3(get, set)]
+    pub elite: usize,
+    /// Number of peptides competing for every parent
+    #[pyo3(get, set)]
+    pub tournament_size: usize,
+    /// Weight of the highest score with a decoy in the fitness
+    #[pyo3(get, set)]
+    pub decoy_weight: f32,
+    /// Number of peptides whose HSPs are extracted together
+    #[pyo3(get, set)]
+    pub batch_size: usize,
+    #[pyo3(get, set)]
+    pub random_seed: u64,
+}
+
+#[pymethods]
+impl EvolutionSettings {
+    /// Default settings, with the given fields (keyword arguments) replaced
+    #[new]
+    #[args(kwargs = "**")]
+    pub fn new(kwargs: Option<&PyDict>) -> PyResult<Self> {
+        let mut settings = EvolutionSettings::default();
+        for (key, value) in kwargs.into_iter().flatten() {
+            match key.extract::<&str>()? {
+                "population_size" => settings.population_size = value.extract()?,
+                "generations" => settings.generations = value.extract()?,
+                "mutation_rate" => settings.mutation_rate = value.extract()?,
+                "crossover_rate" => settings.crossover_rate = value.extract()?,
+                "elite" => settings.elite = value.extract()?,
+                "tournament_size" => settings.tournament_size = value.extract()?,
+                "decoy_weight" => settings.decoy_weight = value.extract()?,
+                "batch_size" => settings.batch_size = value.extract()?,
+                "random_seed" => settings.random_seed = value.extract()?,
+                key => return Err(PyTypeError::new_err(format!("Unknown evolution setting: {}", key))),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+impl Default for EvolutionSettings {
+    fn default() -> Self {
+        EvolutionSettings {
+            population_size: 50,
+            generations: 20,
+            mutation_rate: 0.05,
+            crossover_rate: 0.7,
+            elite: 2,
+            tournament_size: 3,
+            decoy_weight: 1.0,
+            batch_size: 20,
+            random_seed: 0,
+        }
+    }
+}
+
+/// Peptide of an evolved population
+#[derive(Clone)]
+pub struct Candidate {
+    pub sequence: String,
+    /// Score with the target
+    pub score: f32,
+    /// Highest score with a decoy (0 without decoys)
+    pub off_target: f32,
+    /// `score - decoy_weight * off_target`
+    pub fitness: f32,
+}
+
+/// Evolves a population of peptides toward a high score with a target, and
+/// low scores with some decoys
+///
+/// `scorer` scores the peptides with the target first, then with the decoys.
+/// The initial population is made of the `initial` peptides and of mutants
+/// of them, or of random peptides of `length` residues without initial
+/// peptides. Every generation keeps the `elite` best peptides and breeds the
+/// others from parents selected by tournament, by (one-point) crossover and
+/// mutation. The random generator of every generation is seeded from the
+/// random seed and the generation, so a run always gives the same peptides.
+///
+/// With a checkpoint, the population of every generation is saved, and a run
+/// resumes from the last saved generation. Returns the last population, from
+/// the fittest peptide to the least fit.
+pub fn evolve_peptides(
+    scorer: &PeptideScorer,
+    initial: &[String],
+    length: usize,
+    settings: &EvolutionSettings,
+    mut checkpoint: Option<&mut Checkpoint>,
+    verbose: bool,
+) -> Result<Vec<Candidate>, SprintError> {
+    for (name, rate) in [("mutation", settings.mutation_rate), ("crossover", settings.crossover_rate)] {
+        if !(0f32..=1f32).contains(&rate) {
+            return Err(SprintError::invalid_input(format!("Invalid {} rate: {}", name, rate)));
+        }
+    }
+    if settings.population_size == 0 {
+        return Err(SprintError::invalid_input("The population must not be empty."));
+    }
+    if initial.is_empty() && length == 0 {
+        return Err(SprintError::invalid_input("Initial peptides or a peptide length are needed."));
+    }
+    // The peptides are bred by cutting their sequences at byte positions
+    for peptide in initial {
+        if peptide.is_empty() || !peptide.chars().all(|residue| AMINO_ACIDS.contains(residue.to_ascii_uppercase())) {
+            return Err(SprintError::invalid_input(format!(
+                "Invalid initial peptide {} (only the standard amino acids {} are allowed).",
+                peptide, AMINO_ACIDS
+            )));
+        }
+    }
+
+    // Scores of the peptides already seen
+    let mut scored: HashMap<String, Candidate> = HashMap::new();
+
+    let last_saved = checkpoint.as_ref().and_then(|checkpoint| {
+        checkpoint
+            .keys()
+            .into_iter()
+            .filter(|key| key.starts_with(GENERATION_PREFIX))
+            .max()
+    });
+    let (mut generation, mut population) = match last_saved {
+        Some(key) => {
+            let bytes = checkpoint.as_ref().unwrap().load(&key)?.unwrap_or_default();
+            let population = parse_population(&String::from_utf8_lossy(&bytes))?;
+            let generation: usize = key[GENERATION_PREFIX.len()..]
+                .parse()
+                .map_err(|_| SprintError::invalid_input(format!("Invalid checkpoint entry: {}", key)))?;
+            if verbose {
+                println!("Evolution - Resuming from generation {}", generation);
+            }
+            (generation, population)
+        }
+        None => {
+            let mut rng = generation_rng(settings.random_seed, 0);
+            let sequences = initial_population(initial, length, settings, &mut rng);
+            (0, evaluate(scorer, sequences, settings, &mut scored, 0)?)
+        }
+    };
+    for candidate in population.iter() {
+        scored.insert(candidate.sequence.clone(), candidate.clone());
+    }
+
+    let start = Instant::now();
+    loop {
+        if let Some(checkpoint) = checkpoint.as_mut() {
+            checkpoint.save(&generation_key(generation), format_population(&population).as_bytes())?;
+        }
+        if verbose {
+            println!(
+                "Generation {} - Best fitness {} ({}, score {}, off-target {}), mean fitness {}, {} peptides scored in {}...",
+                generation,
+                population[0].fitness,
+                population[0].sequence,
+                population[0].score,
+                population[0].off_target,
+                population.iter().map(|candidate| candidate.fitness).sum::<f32>() / population.len() as f32,
+                scored.len(),
+                start.elapsed().as_secs()
+            );
+        }
+
+        if generation >= settings.generations {
+            break;
+        }
+        generation += 1;
+
+        let mut rng = generation_rng(settings.random_seed, generation);
+        let offspring = breed(&population, settings, &mut rng);
+        population = evaluate(scorer, offspring, settings, &mut scored, generation)?;
+    }
+
+    Ok(population)
+}
+
+/// Describes an evolution, so that a checkpoint is only resumed by the same
+/// evolution (the number of generations aside)
+pub fn evolution_manifest(scorer: &PeptideScorer, initial: &[String], length: usize, settings: &EvolutionSettings) -> String {
+    [
+        scorer.manifest(),
+        format!("initial={}", initial.join(",")),
+        format!("length={}", length),
+        format!("population_size={}", settings.population_size),
+        format!("mutation_rate={}", settings.mutation_rate),
+        format!("crossover_rate={}", settings.crossover_rate),
+        format!("elite={}", settings.elite),
+        format!("tournament_size={}", settings.tournament_size),
+        format!("decoy_weight={}", settings.decoy_weight),
+        format!("random_seed={}", settings.random_seed),
+    ]
+    .join("\n")
+}
+
+/// Random generator of a generation, seeded from the random seed and the
+/// generation (the generations of a run never share a generator, but a
+/// generation of a run may share the generator of another generation of a
+/// run with another random seed)
+fn generation_rng(random_seed: u64, generation: usize) -> StdRng {
+    StdRng::seed_from_u64(random_seed ^ (generation as u64).wrapping_mul(0x9e3779b97f4a7c15))
+}
+
+fn generation_key(generation: usize) -> String {
+    format!("{}{:06}", GENERATION_PREFIX, generation)
+}
+
+/// The initial peptides (up to the size of the population) followed by
+/// mutants of them, or random peptides
+fn initial_population(initial: &[String], length: usize, settings: &EvolutionSettings, rng: &mut StdRng) -> Vec<String> {
+    let amino_acids: Vec<char> = AMINO_ACIDS.chars().collect();
+    (0..settings.population_size)
+        .map(|i| match initial.is_empty() {
+            true => (0..length).map(|_| amino_acids[rng.random_range(0..amino_acids.len())]).collect(),
+            false if i < initial.len() => initial[i].to_ascii_uppercase(),
+            false => mutate(&initial[i % initial.len()].to_ascii_uppercase(), settings.mutation_rate, rng),
+        })
+        .collect()
+}
+
+/// The elite of the population followed by offspring of parents selected by
+/// tournament
+fn breed(population: &[Candidate], settings: &EvolutionSettings, rng: &mut StdRng) -> Vec<String> {
+    let mut offspring: Vec<String> = population
+        .iter()
+        .take(std::cmp::min(settings.elite, settings.population_size))
+        .map(|candidate| candidate.sequence.clone())
+        .collect();
+
+    while offspring.len() < settings.population_size {
+        let parent1 = tournament(population, settings.tournament_size, rng);
+        let child = match rng.random_bool(settings.crossover_rate as f64) {
+            true => crossover(parent1, tournament(population, settings.tournament_size, rng), rng),
+            false => parent1.to_string(),
+        };
+        offspring.push(mutate(&child, settings.mutation_rate, rng));
+    }
+    offspring
+}
+
+/// Fittest of `size` random peptides of a population (sorted by fitness)
+fn tournament<'a>(population: &'a [Candidate], size: usize, rng: &mut StdRng) -> &'a str {
+    let winner = (0..std::cmp::max(size, 1))
+        .map(|_| rng.random_range(0..population.len()))
+        .min()
+        .unwrap();
+    &population[winner].sequence
+}
+
+/// Start of a parent followed by the end of the other, cut at the same
+/// position
+fn crossover(parent1: &str, parent2: &str, rng: &mut StdRng) -> String {
+    let length = std::cmp::min(parent1.len(), parent2.len());
+    if length < 2 {
+        return parent1.to_string();
+    }
+    let cut = rng.random_range(1..length);
+    format!("{}{}", &parent1[..cut], &parent2[cut..])
+}
+
+/// Substitutes every residue by another standard amino acid with a
+/// probability of `rate`
+fn mutate(sequence: &str, rate: f32, rng: &mut StdRng) -> String {
+    sequence
+        .chars()
+        .map(|residue| {
+            if !rng.random_bool(rate as f64) {
+                return residue;
+            }
+            let substitutes: Vec<char> = AMINO_ACIDS.chars().filter(|&amino_acid| amino_acid != residue).collect();
+            substitutes[rng.random_range(0..substitutes.len())]
+        })
+        .collect()
+}
+
+/// Scores the peptides not seen yet, and returns the population from the
+/// fittest peptide to the least fit (the ties by sequence)
+fn evaluate(
+    scorer: &PeptideScorer,
+    sequences: Vec<String>,
+    settings: &EvolutionSettings,
+    scored: &mut HashMap<String, Candidate>,
+    generation: usize,
+) -> Result<Vec<Candidate>, SprintError> {
+    let mut unseen: Vec<String> = sequences.iter().filter(|sequence| !scored.contains_key(*sequence)).cloned().collect();
+    unseen.sort_unstable();
+    unseen.dedup();
+
+    let peptides: Vec<Protein> = unseen
+        .iter()
+        .enumerate()
+        .map(|(i, sequence)| Protein::new(i, format!("generation{}_peptide{}", generation, i), sequence.clone(), true))
+        .collect::<Result<_, SprintError>>()?;
+    for (sequence, scores) in unseen.into_iter().zip(scorer.score(&peptides, settings.batch_size)?) {
+        let off_target = scores[1..].iter().cloned().fold(0f32, f32::max);
+        let candidate = Candidate {
+            sequence: sequence.clone(),
+            score: scores[0],
+            off_target,
+            fitness: scores[0] - settings.decoy_weight * off_target,
+        };
+        scored.insert(sequence, candidate);
+    }
+
+    let mut population: Vec<Candidate> = sequences.iter().map(|sequence| scored[sequence].clone()).collect();
+    population.sort_by(|a, b| b.fitness.total_cmp(&a.fitness).then_with(|| a.sequence.cmp(&b.sequence)));
+    Ok(population)
+}
+
+fn format_population(population: &[Candidate]) -> String {
+    population
+        .iter()
+        .map(|candidate| {
+            format!(
+                "{}\t{}\t{}\t{}\n",
+                candidate.sequence, candidate.score, candidate.off_target, candidate.fitness
+            )
+        })
+        .collect()
+}
+
+fn parse_population(content: &str) -> Result<Vec<Candidate>, SprintError> {
+    let invalid = |line: &str| SprintError::invalid_input(format!("Invalid checkpoint line: {}", line));
+    let population: Vec<Candidate> = content
+        .lines()
+        .map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 4 {
+                return Err(invalid(line));
+            }
+            let value = |i: usize| fields[i].parse::<f32>().map_err(|_| invalid(line));
+            Ok(Candidate {
+                sequence: fields[0].to_string(),
+                score: value(1)?,
+                off_target: value(2)?,
+                fitness: value(3)?,
+            })
+        })
+        .collect::<Result<_, SprintError>>()?;
+    if population.is_empty() {
+        return Err(SprintError::invalid_input("Empty population in the checkpoint."));
+    }
+    Ok(population)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use crate::sprint::hsp::HSP;
+    use crate::sprint::extraction::ExtractionSettings;
+    use crate::sprint::proteinset::ProteinSet;
+    use crate::sprint::seed::default_seeds;
+    use crate::sprint::substitution::SubstitutionMatrix;
+
+    fn evolve(initial: &[&str], settings: &EvolutionSettings) -> Result<Vec<Candidate>, SprintError> {
+        let protein = Protein::new(0, "P0".to_string(), "MKTAYIAKQRQISFVKSHFSRQLEERLGLI".to_string(), false).unwrap();
+        let protein_set = ProteinSet::new(vec![protein]).unwrap();
+        let (matrix, seeds) = (SubstitutionMatrix::load("PAM120").unwrap(), default_seeds());
+        let extraction = ExtractionSettings { kmer_size: 12, t_sim: 15, t_hit: 35, matrix: &matrix, seeds: &seeds };
+        let hsps: HashSet<HSP> = HashSet::new();
+        let scorer = PeptideScorer::new(&protein_set, &hsps, &[], &["P0".to_string()], extraction).unwrap();
+        let initial: Vec<String> = initial.iter().map(|peptide| peptide.to_string()).collect();
+        evolve_peptides(&scorer, &initial, 12, settings, None, false)
+    }
+
+    #[test]
+    fn invalid_initial_peptides_are_rejected() {
+        let settings = EvolutionSettings::default();
+        for initial in ["", "AKXQ", "AKÉQRST", "AK-Q"] {
+            assert!(evolve(&[initial], &settings).is_err(), "{} accepted", initial);
+        }
+    }
+
+    #[test]
+    fn evolution_is_reproducible() {
+        let settings = EvolutionSettings { population_size: 6, generations: 3, mutation_rate: 0.2, random_seed: 7, ..Default::default() };
+        let sequences = |population: Vec<Candidate>| -> Vec<String> {
+            population.into_iter().map(|candidate| candidate.sequence).collect()
+        };
+
+        let population = sequences(evolve(&["akqrqisfvksh", "MKTAYIAKQRQI"], &settings).unwrap());
+        assert_eq!(population.len(), 6);
+        assert!(population.iter().all(|peptide| peptide.len() == 12 && peptide.chars().all(|residue| AMINO_ACIDS.contains(residue))));
+        assert_eq!(population, sequences(evolve(&["akqrqisfvksh", "MKTAYIAKQRQI"], &settings).unwrap()));
+
+        let random = sequences(evolve(&[], &settings).unwrap());
+        assert!(random.iter().all(|peptide| peptide.len() == 12));
+        assert_eq!(random, sequences(evolve(&[], &settings).unwrap()));
+    }
+}
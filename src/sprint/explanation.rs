@@ -0,0 +1,176 @@
+use std::collections::HashSet;
+
+use crate::sprint::error::SprintError;
+use crate::sprint::hspfile::HspSource;
+use crate::sprint::prediction::prepare_training_pairs;
+use crate::sprint::proteinset::ProteinSet;
+use crate::sprint::sites::{build_hsp_table, partner_range, PositionedHsp};
+use crate::sprint::substitution::SubstitutionMatrix;
+
+/// HSP between a protein of the explained pair and a protein of a training
+/// pair
+pub struct SupportingHsp {
+    pub protein: usize,
+    /// Protein of the training pair
+    pub partner: usize,
+    pub position: usize,
+    pub partner_position: usize,
+    pub length: usize,
+    /// Score of the HSP (`scoring::score_hsp`)
+    pub score: f32,
+}
+
+/// Contribution of a training pair to the score of the explained pair,
+/// through an HSP of each of its proteins
+pub struct Support {
+    pub training_pair: (usize, usize),
+    /// HSP of the first protein of the explained pair
+    pub hsp1: SupportingHsp,
+    /// HSP of the second protein of the explained pair
+    pub hsp2: SupportingHsp,
+    pub contribution: f32,
+}
+
+/// Why two proteins are predicted to interact
+pub struct Explanation {
+    pub protein1: usize,
+    pub protein2: usize,
+    /// Sum of the contributions (the score of the pair, up to rounding)
+    pub score: f32,
+    /// The supports, from the highest contribution to the lowest
+    pub supports: Vec<Support>,
+}
+
+/// Lists the training pairs and the pairs of HSPs that contribute to the
+/// score of two proteins, ranked by contribution
+///
+/// ```text
+///               Interactor 1 --------------- Interactor 2
+///                    |                            |
+///               HSP1 |                       HSP2 |
+///                    |                            |
+///                 Protein 1                    Protein 2
+/// ```
+///
+/// Every pair of HSPs `fill_row` uses for the score of the pair gives one
+/// support (a training pair contributes in both orientations).
+pub fn explain_pair(
+    protein_set: &ProteinSet,
+    hsps: &dyn HspSource,
+    training_pairs: &[(String, String)],
+    protein1: &str,
+    protein2: &str,
+    kmer_size: usize,
+    matrix: &SubstitutionMatrix,
+) -> Result<Explanation, SprintError> {
+    let protein1 = protein_set.get_protein_by_name(protein1)?.index();
+    let protein2 = protein_set.get_protein_by_name(protein2)?.index();
+
+    let (pairs, _) = prepare_training_pairs(protein_set, training_pairs, 0, 1, false);
+
+    // Only the HSPs of the explained proteins are needed
+    let queries: HashSet<usize> = [protein1, protein2].into_iter().collect();
+    let hsp_table = build_hsp_table(hsps, protein_set, &queries, kmer_size, matrix);
+
+    let pair_indices: Vec<usize> = (0..pairs.len()).collect();
+    let mut supports = find_supports(protein1, protein2, &pairs, &pair_indices, &hsp_table, kmer_size as f32);
+    supports.sort_by(|a, b| b.contribution.total_cmp(&a.contribution));
+
+    Ok(Explanation {
+        protein1,
+        protein2,
+        score: supports.iter().fold(0f32, |score, support| score + support.contribution),
+        supports,
+    })
+}
+
+/// Supports of the score of two proteins given by some training pairs
+/// (indices), in the order of the pairs
+///
+/// `hsp_table` is a table built by `sites::build_hsp_table` that includes the
+/// HSPs of both proteins. Every pair of HSPs `fill_row` uses for the score of
+/// the two proteins gives one support:
+///
+/// - for a training pair `(X, Y)` of distinct proteins, the HSPs of protein 1
+///   with `X` and of protein 2 with `Y`, then (for distinct proteins) the HSPs
+///   of protein 1 with `Y` and of protein 2 with `X`
+/// - for a homodimer `(X, X)`, the HSPs of protein 1 and of protein 2 with `X`,
+///   each unordered pair of HSPs being used once for a protein with itself
+pub fn find_supports(
+    protein1: usize,
+    protein2: usize,
+    pairs: &[(usize, usize)],
+    pair_indices: &[usize],
+    hsp_table: &[Vec<PositionedHsp>],
+    kmer_size: f32,
+) -> Vec<Support> {
+    let hsps_with = |interactor: usize, protein: usize| &hsp_table[interactor][partner_range(&hsp_table[interactor], protein)];
+
+    let mut supports = vec![];
+    for &index in pair_indices {
+        let (interactor1, interactor2) = pairs[index];
+
+        if interactor1 == interactor2 {
+            let hsps1 = hsps_with(interactor1, protein1);
+            for (i, hsp1) in hsps1.iter().enumerate() {
+                let hsps2 = match protein1 == protein2 {
+                    true => &hsps1[i..],
+                    false => hsps_with(interactor1, protein2),
+                };
+                for hsp2 in hsps2 {
+                    supports.push(support((interactor1, interactor2), (interactor1, hsp1), (interactor1, hsp2), kmer_size));
+                }
+            }
+            continue;
+        }
+
+        let mut orientations = vec![(interactor1, interactor2)];
+        if protein1 != protein2 {
+            orientations.push((interactor2, interactor1));
+        }
+        for (interactor_of_1, interactor_of_2) in orientations {
+            for hsp1 in hsps_with(interactor_of_1, protein1) {
+                for hsp2 in hsps_with(interactor_of_2, protein2) {
+                    supports.push(support(
+                        (interactor1, interactor2),
+                        (interactor_of_1, hsp1),
+                        (interactor_of_2, hsp2),
+                        kmer_size,
+                    ));
+                }
+            }
+        }
+    }
+
+    supports
+}
+
+/// Support of a pair of HSPs, given with their interactor (the row of the
+/// HSP table they are in)
+fn support(
+    training_pair: (usize, usize),
+    (interactor1, hsp1): (usize, &PositionedHsp),
+    (interactor2, hsp2): (usize, &PositionedHsp),
+    kmer_size: f32,
+) -> Support {
+    // Same contribution as in `prediction::fill_row`
+    let term1 = hsp1.3 * (hsp2.2 - kmer_size + 1f32);
+    let term2 = hsp2.3 * (hsp1.2 - kmer_size + 1f32);
+    Support {
+        training_pair,
+        hsp1: supporting_hsp(interactor1, hsp1),
+        hsp2: supporting_hsp(interactor2, hsp2),
+        contribution: (term1 + term2) / (hsp1.1 * hsp2.1),
+    }
+}
+
+fn supporting_hsp(interactor: usize, hsp: &PositionedHsp) -> SupportingHsp {
+    SupportingHsp {
+        protein: hsp.0,
+        partner: interactor,
+        position: hsp.5,
+        partner_position: hsp.4,
+        length: hsp.2 as usize,
+        score: hsp.3,
+    }
+}
@@ -1,7 +1,9 @@
 use std::collections::{HashMap, HashSet};
-use std::sync::Mutex;
 use rayon::prelude::*;
 use std::time::Instant;
+use crate::sprint::distributed::Worker;
+use crate::sprint::error::SprintError;
+use crate::sprint::prediction::ScoringSettings;
 use crate::sprint::proteinset::ProteinSet;
 use crate::sprint::scoring::{score_sequences, score_position};
 use crate::sprint::seed::Seed; 
@@ -9,8 +11,30 @@ use crate::sprint::smer::{Smer, SmerCollection};
 use crate::sprint::protein::Protein; 
 use crate::sprint::hsp::HSP;
 use crate::sprint::location::Location;
-use crate::sprint::constants::{RESIDUE_CODES, SEEDS};
+use crate::sprint::sanitization::is_standard;
 use crate::sprint::similarity::{compute_similar_smers};
+use crate::sprint::substitution::SubstitutionMatrix;
+use crate::sprint::sink::HspSink;
+
+/// Default number of s-mers processed between two sends to the sink
+pub const DEFAULT_BATCH_SIZE: usize = 10000;
+
+/// Parameters of an extraction of HSPs
+#[derive(Clone, Copy)]
+pub struct ExtractionSettings<'a> {
+    pub kmer_size: usize,
+    pub t_sim: i16,
+    pub t_hit: i16,
+    pub matrix: &'a SubstitutionMatrix,
+    pub seeds: &'a [Seed],
+}
+
+impl<'a> ExtractionSettings<'a> {
+    /// Settings of the scoring with the extracted HSPs
+    pub fn scoring(&self) -> ScoringSettings<'a> {
+        ScoringSettings { kmer_size: self.kmer_size, matrix: self.matrix }
+    }
+}
 
 /// Given a set of sequences (ProteinSet struct) and a Seed, extracts
 /// all the s-mers from the sequences
@@ -43,86 +67,133 @@ pub fn extract_all_smers(sequences: &ProteinSet, seed: &Seed) -> Vec<SmerCollect
 }
 
 /// Given a protein and a seed, extracts all the s-mers from that protein
+/// (the s-mers with ambiguous or masked residues at the matching positions
+/// of the seed are left out, so that such residues never seed hits)
 pub fn extract_smers(protein: &Protein, seed: &Seed) -> Vec<Smer> {
     let mut smers = vec![];
     let seed_length = seed.len();
     let protein_length = protein.len();
-    let num_smers = protein_length - seed_length + 1;
 
     if protein_length < seed_length {
         return smers;
     }
 
+    let num_smers = protein_length - seed_length + 1;
+
     for position in 0..num_smers {
         let start = position as usize;
-        let end = position + seed.len() as usize - 1;
-        let subsequence: &str = &protein.sub(start, end);
-        let smer_value: u64 = compute_smer(subsequence, &seed);
+        if seed.positions().iter().any(|&offset| !is_standard(protein.residue(start + offset))) {
+            continue;
+        }
+        let smer_value: u64 = compute_smer(protein, start, &seed);
         let location = Location::new(protein.index(), position);
         smers.push(Smer::new(smer_value, location));
     }
     smers
 }
 
-/// Computes the u64 value of an smer
-pub fn compute_smer(sequence: &str, seed: &Seed) -> u64 {
+/// Computes the u64 value of the smer starting at a position of a protein
+/// (only the residues at the matching positions of the seed are kept)
+pub fn compute_smer(protein: &Protein, start: usize, seed: &Seed) -> u64 {
     let mut u64_sequence: u64 = 0;
 
-    for amino_acid in sequence.chars() {
+    for &position in seed.positions() {
         u64_sequence = u64_sequence << 5;
-        u64_sequence = u64_sequence | *RESIDUE_CODES.get(&amino_acid).unwrap() as u64;
+        u64_sequence = u64_sequence | protein.residue(start + position) as u64;
     }
 
-    u64_sequence & seed.value()
+    u64_sequence
 }
 
 pub fn extract_hsps(
     protein_set: &ProteinSet,
-    kmer_size: usize,
-    t_sim: i16,
-    t_hit: i16,
-    process_rank: usize,
-    world_size: usize,
+    settings: &ExtractionSettings,
+    worker: Worker,
     new_only: bool,
     trivial_hsps: bool,
-    verbose: bool
 ) -> HashSet<HSP> {
+    let mut hsps: HashSet<HSP> = HashSet::new();
+    extract_hsps_to_sink(
+        protein_set,
+        settings,
+        worker,
+        new_only,
+        trivial_hsps,
+        DEFAULT_BATCH_SIZE,
+        &mut hsps,
+    ).unwrap(); // Collecting in memory cannot fail
+    hsps
+}
 
-    let hsps: Mutex<HashSet<HSP>> = Mutex::new(HashSet::new());
+/// Extracts the HSPs and sends them to a sink as they are found
+///
+/// The s-mers of every seed are processed in batches of `batch_size`. The
+/// threads accumulate the HSPs of a batch in their own buffers, which are
+/// then merged and sent to the sink, so that only one batch of HSPs is held
+/// in memory at a time.
+pub fn extract_hsps_to_sink(
+    protein_set: &ProteinSet,
+    settings: &ExtractionSettings,
+    worker: Worker,
+    new_only: bool,
+    trivial_hsps: bool,
+    batch_size: usize,
+    sink: &mut dyn HspSink,
+) -> Result<(), SprintError> {
+    let (kmer_size, seeds) = (settings.kmer_size, settings.seeds);
+    let (process_rank, world_size, verbose) = (worker.rank, worker.world_size, worker.verbose);
 
-    for (i, seed_string) in SEEDS.iter().enumerate() {
+    for (i, seed) in seeds.iter().enumerate() {
 
-        // Create the seed struct
-        let seed = Seed::new(seed_string);
+        // An s-mer must fit within the k-mers that are extended into HSPs
+        if seed.len() > kmer_size {
+            println!("WARNING: Skipping the seed {} which is longer than the k-mer size ({}).", seed.as_string(), kmer_size);
+            continue;
+        }
 
         // Extract the seed's smers
-        let smers = extract_all_smers(&protein_set, &seed);
+        let smers = extract_all_smers(protein_set, seed);
         let smer_map: HashMap<u64, usize> = smers.iter().enumerate().map(|(i, smer)| (smer.value(), i)).collect();
 
-        // Identify s-mers for this rank
+        // Identify s-mers for this rank (with new_only, only the s-mers of the
+        // new proteins, whose similar s-mers are then found in all the proteins)
+        let candidates: Vec<usize> = (0..smers.len())
+            .filter(|&i| !new_only || smers[i].locations().iter().any(|location| protein_set.is_new(location.index())))
+            .collect();
         let mut smers_to_process = vec![];
-        for i in 0..smers.len() {
+        for (i, &index) in candidates.iter().enumerate() {
             if (i + process_rank) % world_size == 0 {
-                smers_to_process.push(i);
+                smers_to_process.push(index);
             }
         }
 
         // Compute the HSPs
         if verbose {
-            println!("Process {} - Working with seed {}/{} ({}).", process_rank, i + 1, SEEDS.len(), seed_string);
+            println!("Process {} - Working with seed {}/{} ({}).", process_rank, i + 1, seeds.len(), seed.as_string());
         }
         let start = Instant::now();
-        
-        smers_to_process
-            .into_par_iter()
-            .for_each(|index| unsafe {
-                // Compute hsps
-                let results = compute_hsps_for_smer(index, &smers, &smer_map, &protein_set, &seed, kmer_size, t_sim, t_hit, new_only);
-
-                // Return results
-                let mut guard = hsps.lock().unwrap();
-                (*guard).extend(results);
-            });
+
+        for (chunk, batch) in smers_to_process.chunks(std::cmp::max(batch_size, 1)).enumerate() {
+            if sink.is_done(i, chunk) {
+                continue;
+            }
+
+            let batch_hsps = batch
+                .par_iter()
+                .fold(HashSet::new, |mut hsps, &index| unsafe {
+                    hsps.extend(compute_hsps_for_smer(index, &smers, &smer_map, protein_set, seed, settings, new_only));
+                    hsps
+                })
+                .reduce(HashSet::new, |mut hsps1, mut hsps2| {
+                    if hsps1.len() < hsps2.len() {
+                        std::mem::swap(&mut hsps1, &mut hsps2);
+                    }
+                    hsps1.extend(hsps2);
+                    hsps1
+                });
+
+            sink.send_chunk(i, chunk, batch_hsps.into_iter().collect())?;
+        }
 
         if verbose {
             println!("Process {} - Completed table {} in {}s...", process_rank, i + 1, start.elapsed().as_secs());
@@ -130,33 +201,121 @@ pub fn extract_hsps(
     }
 
     // Add the HSPs of proteins with themselves
-    if trivial_hsps {
-        if !new_only {
-            for protein in protein_set.iter() {
-                let mut guard = hsps.lock().unwrap();
+    if trivial_hsps && !sink.is_done(seeds.len(), 0) {
+        let trivial: Vec<HSP> = protein_set.iter()
+            .filter(|protein| !new_only || protein.is_new())
+            .map(|protein| {
                 let location = Location::new(protein.index(), 0);
-                let hsp = HSP::new(location.clone(), location.clone(), protein.len());
-                (*guard).insert(hsp);
-            }
-        } else {
-            for protein in protein_set.iter() {
-                if !protein.is_new() {
-                    continue;
-                }
-                let mut guard = hsps.lock().unwrap();
-                let location = Location::new(protein.index(), 0);
-                let hsp = HSP::new(location.clone(), location.clone(), protein.len());
-                (*guard).insert(hsp);
-            }
+                HSP::new(location.clone(), location, protein.len())
+            })
+            .collect();
+        sink.send_chunk(seeds.len(), 0, trivial)?;
+    }
+
+    Ok(())
+}
+
+/// Locations of the s-mers of a set of proteins for every seed, kept in
+/// memory to extract the HSPs of new proteins with these proteins
+pub struct SmerIndex {
+    /// Number of proteins indexed (the first proteins of the sets the new
+    /// proteins are added to)
+    num_proteins: usize,
+    /// Locations of every s-mer value, for every seed
+    tables: Vec<HashMap<u64, Vec<Location>>>,
+}
+
+impl SmerIndex {
+    pub fn new(protein_set: &ProteinSet, seeds: &[Seed]) -> Self {
+        let tables = seeds
+            .iter()
+            .map(|seed| {
+                extract_all_smers(protein_set, seed)
+                    .into_iter()
+                    .map(|smer| (smer.value(), smer.locations().iter().cloned().collect()))
+                    .collect()
+            })
+            .collect();
+
+        SmerIndex {
+            num_proteins: protein_set.len(),
+            tables,
         }
     }
-   
-    let guard = hsps.lock().unwrap();
-    (*guard).iter()
-        .map(|hsp| {
-            HSP::new(hsp.location(0).clone(), hsp.location(1).clone(), hsp.len())
-        })
-    .collect::<HashSet<HSP>>()
+
+    pub fn num_proteins(&self) -> usize {
+        self.num_proteins
+    }
+}
+
+/// Extracts the HSPs of the new proteins of a set with the indexed proteins
+///
+/// The indexed proteins must be the first proteins of the set (in the same
+/// order). These are the HSPs `extract_hsps` finds with `new_only` between a
+/// new protein and an indexed protein, but only the s-mers of the new
+/// proteins are extracted and they are only compared to the indexed s-mers.
+pub fn extract_hsps_with_index(protein_set: &ProteinSet, index: &SmerIndex, settings: &ExtractionSettings) -> HashSet<HSP> {
+    let (kmer_size, t_sim, t_hit, matrix) = (settings.kmer_size, settings.t_sim, settings.t_hit, settings.matrix);
+    let mut hsps = HashSet::new();
+
+    for (seed, table) in settings.seeds.iter().zip(index.tables.iter()) {
+        // An s-mer must fit within the k-mers that are extended into HSPs
+        if seed.len() > kmer_size {
+            continue;
+        }
+
+        let smers: Vec<Smer> = protein_set
+            .iter()
+            .skip(index.num_proteins)
+            .flat_map(|protein| extract_smers(protein, seed))
+            .collect();
+
+        let seed_hsps = smers
+            .par_iter()
+            .fold(HashSet::new, |mut hsps, smer| {
+                for value in compute_similar_smers(smer.value(), seed, t_sim, matrix) {
+                    for location in table.get(&value).into_iter().flatten() {
+                        let hits = unsafe { find_hits(smer.location(), location, protein_set, seed, kmer_size, t_hit, matrix) };
+                        hsps.extend(hits.iter().map(|hit| extend_hit(hit, protein_set, kmer_size, t_hit, matrix)));
+                    }
+                }
+                hsps
+            })
+            .reduce(HashSet::new, |mut hsps1, mut hsps2| {
+                if hsps1.len() < hsps2.len() {
+                    std::mem::swap(&mut hsps1, &mut hsps2);
+                }
+                hsps1.extend(hsps2);
+                hsps1
+            });
+        hsps.extend(seed_hsps);
+    }
+
+    hsps
+}
+
+/// Describes an extraction, so that a checkpoint is only resumed by the
+/// same extraction (the chunks of s-mers depend on all these parameters)
+pub fn extraction_manifest(
+    protein_set: &ProteinSet,
+    settings: &ExtractionSettings,
+    worker: Worker,
+    new_only: bool,
+    trivial_hsps: bool,
+    batch_size: usize
+) -> String {
+    [
+        format!("sequences={} {:016x}", protein_set.len(), protein_set.fingerprint(protein_set.len())),
+        format!("kmer_size={}", settings.kmer_size),
+        format!("t_sim={}", settings.t_sim),
+        format!("t_hit={}", settings.t_hit),
+        format!("matrix={} {:016x}", settings.matrix.name(), settings.matrix.fingerprint()),
+        format!("seeds={}", settings.seeds.iter().map(|seed| seed.as_string()).collect::<Vec<String>>().join(",")),
+        format!("process={}/{}", worker.rank, worker.world_size),
+        format!("new_only={}", new_only),
+        format!("trivial_hsps={}", trivial_hsps),
+        format!("batch_size={}", batch_size),
+    ].join("\n")
 }
 
 /// Computes the HSPs that arise from the smer
@@ -166,18 +325,19 @@ pub unsafe fn compute_hsps_for_smer(
      smer_map: &HashMap<u64, usize>,
      protein_set: &ProteinSet,
      seed: &Seed,
-     kmer_size: usize,
-     t_sim: i16,
-     t_hit: i16,
+     settings: &ExtractionSettings,
      new_only: bool
  ) -> HashSet<HSP> {
+    let (kmer_size, t_sim, t_hit, matrix) = (settings.kmer_size, settings.t_sim, settings.t_hit, settings.matrix);
     let mut hsps = HashSet::new();
     let smer = &smer_list[smer_index];
 
-    let raw_similar = compute_similar_smers(smer.value(), &seed, t_sim);
+    // Every pair of similar s-mers is processed once (from the lowest value),
+    // except with new_only where only the s-mers of new proteins are processed
+    let raw_similar = compute_similar_smers(smer.value(), &seed, t_sim, matrix);
     let similar_indices: Vec<usize> = raw_similar.into_iter()
-        .filter(|x| x >= &smer.value() && smer_map.contains_key(&x))
-        .map(|value| *smer_map.get(&value).unwrap())
+        .filter(|x| new_only || x >= &smer.value())
+        .filter_map(|value| smer_map.get(&value).copied())
         .collect();
     
     for index in similar_indices {
@@ -188,9 +348,9 @@ pub unsafe fn compute_hsps_for_smer(
                     if new_only && !protein_set.is_new(smer1_location.index()) && !protein_set.is_new(smer2_location.index()) {
                         continue
                     }
-                    let hits = find_hits(smer1_location, smer2_location, protein_set, kmer_size, t_hit);
+                    let hits = find_hits(smer1_location, smer2_location, protein_set, seed, kmer_size, t_hit, matrix);
                     let smer_hsps: Vec<HSP> = hits.iter().map(|hit| {
-                        let hit = extend_hit(hit, protein_set, kmer_size, t_hit);
+                        let hit = extend_hit(hit, protein_set, kmer_size, t_hit, matrix);
                         return hit;
                     }).collect();
                     hsps.extend(smer_hsps);
@@ -203,9 +363,9 @@ pub unsafe fn compute_hsps_for_smer(
                     if new_only && !protein_set.is_new(locations[i].index()) && !protein_set.is_new(locations[j].index()) {
                         continue
                     }
-                    let hits = find_hits(&locations[i], &locations[j], protein_set, kmer_size, t_hit);
+                    let hits = find_hits(&locations[i], &locations[j], protein_set, seed, kmer_size, t_hit, matrix);
                     let smer_hsps: Vec<HSP> = hits.iter().map(|hit| {
-                        let hit = extend_hit(hit, protein_set, kmer_size, t_hit);
+                        let hit = extend_hit(hit, protein_set, kmer_size, t_hit, matrix);
                         return hit;
                     }).collect();
                     hsps.extend(smer_hsps);
@@ -218,7 +378,7 @@ pub unsafe fn compute_hsps_for_smer(
 }
 
 /// Retrieves hits around similar smers
-pub unsafe fn find_hits(location1: &Location, location2: &Location, protein_set: &ProteinSet, kmer_size: usize, t_hit: i16) -> Vec<(usize, usize, usize, usize, i16)> {
+pub unsafe fn find_hits(location1: &Location, location2: &Location, protein_set: &ProteinSet, seed: &Seed, kmer_size: usize, t_hit: i16, matrix: &SubstitutionMatrix) -> Vec<(usize, usize, usize, usize, i16)> {
     //println!("start hit");
     let mut hits = vec![];
     
@@ -234,7 +394,7 @@ pub unsafe fn find_hits(location1: &Location, location2: &Location, protein_set:
     let protein2 = protein_set.get_protein_by_id(location2.index());
     let length2 = protein2.len();
     
-    for offset in 0..(kmer_size - 12 + 1) { // DO NOT
+    for offset in 0..(kmer_size - seed.len() + 1) {
 
         let start1: i16 = location1.position() as i16 - offset as i16;
         let end1: usize = location1.position() + kmer_size - offset - 1;
@@ -251,7 +411,7 @@ pub unsafe fn find_hits(location1: &Location, location2: &Location, protein_set:
 
         // If the subsequences do not meet the minimum score for this offset,
         // don't bother trying to extend this region
-        let score = score_sequences(protein1, protein2, start1 as usize, start2 as usize, kmer_size);
+        let score = score_sequences(protein1, protein2, start1 as usize, start2 as usize, kmer_size, matrix);
         if score < t_hit {
             continue
         }
@@ -268,7 +428,7 @@ pub unsafe fn find_hits(location1: &Location, location2: &Location, protein_set:
     hits
 }
 
-pub fn extend_hit(hit: &(usize, usize, usize, usize, i16), protein_set: &ProteinSet, kmer_size: usize, t_hit: i16) -> HSP {
+pub fn extend_hit(hit: &(usize, usize, usize, usize, i16), protein_set: &ProteinSet, kmer_size: usize, t_hit: i16, matrix: &SubstitutionMatrix) -> HSP {
 
     let protein1 = protein_set.get_protein_by_id(hit.0);
     let protein2 = protein_set.get_protein_by_id(hit.2);
@@ -291,8 +451,8 @@ pub fn extend_hit(hit: &(usize, usize, usize, usize, i16), protein_set: &Protein
 
     for i in 0..to_right {
         current_score = current_score
-            - score_position(protein1, protein2, pos_1l, pos_2l)
-            + score_position(protein1, protein2, pos_1r + 1, pos_2r + 1);
+            - score_position(protein1, protein2, pos_1l, pos_2l, matrix)
+            + score_position(protein1, protein2, pos_1r + 1, pos_2r + 1, matrix);
 
         if current_score >= t_hit {
             pos_1l += 1;
@@ -322,8 +482,8 @@ pub fn extend_hit(hit: &(usize, usize, usize, usize, i16), protein_set: &Protein
 
     for i in 0..to_left {
         current_score = current_score 
-            - score_position(protein1, protein2, pos_1r, pos_2r)
-            + score_position(protein1, protein2, pos_1l - 1, pos_2l - 1);
+            - score_position(protein1, protein2, pos_1r, pos_2r, matrix)
+            + score_position(protein1, protein2, pos_1l - 1, pos_2l - 1, matrix);
 
         if current_score >= t_hit {
             pos_1l -= 1;
@@ -1,71 +1,192 @@
-use std::fs::read_to_string;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::{collections::HashMap, collections::HashSet, io::Error};
 
 use bio::io::fasta::Reader;
-use ndarray::Array2;
 use rayon::prelude::*;
 
+use super::crossvalidation::HeldOutPair;
+use super::compression::{open_reader, CompressedWriter};
+use super::constants::AMINO_ACIDS;
+use super::error::SprintError;
+use super::evaluation::{ordered_pair, Evaluation};
+use super::evolution::Candidate;
+use super::explanation::{Explanation, SupportingHsp};
+use super::headers::HeaderFormat;
 use super::hsp::HSP;
+use super::hspfile::{is_binary_hsp_file, HspFile, HspSource, MAGIC};
 use super::protein::Protein;
 use super::proteinset::ProteinSet;
+use super::scorematrix::ScoreMatrix;
+use super::seed::Seed;
+use super::sanitization::{sanitize_proteins, SequencePolicy, ValidationReport};
+use super::sites::Segment;
+use super::utils::{fnv1a, fnv1a_extend};
+
+/// Number of rows of scores formatted at a time when saving scores
+const SCORE_ROWS_PER_BLOCK: usize = 256;
 
 /// Open a fasta file and returns a vector of Protein
-pub fn load_fasta(filename: &str, new: bool) -> Result<Vec<Protein>, Error> {
-    let reader = Reader::from_file(filename).unwrap();
-    let mut proteins = vec![];
+///
+/// The IDs are taken from the headers according to their format (the
+/// headers without the expected format keep their first word), and the
+/// non-standard residues are handled according to the policy. The records
+/// modified or dropped, and the unexpected headers, are listed in the report.
+/// The file may be compressed (gzip or zstd).
+pub fn load_fasta(
+    filename: &str,
+    new: bool,
+    policy: &SequencePolicy,
+    headers: &HeaderFormat,
+) -> Result<(Vec<Protein>, ValidationReport), SprintError> {
+    let reader = Reader::from_bufread(open_reader(filename).map_err(|error| SprintError::io(filename, error))?);
+    let mut records = vec![];
+    let mut unmatched_headers = vec![];
 
-    for (i, protein) in reader.records().enumerate() {
-        let record = protein?;
-        let protein_id = String::from(record.id());
-        let sequence_string: String = std::str::from_utf8(record.seq()).unwrap().to_string();
-        let new_protein: Protein = Protein::new(i, protein_id.clone(), sequence_string, new);
-        proteins.push(new_protein);
+    for protein in reader.records() {
+        let record = protein.map_err(|error| SprintError::io(filename, error))?;
+        let protein_id = headers.extract(record.id(), record.desc()).unwrap_or_else(|| {
+            unmatched_headers.push(record.id().to_string());
+            record.id().to_string()
+        });
+        let sequence_string: String = std::str::from_utf8(record.seq())
+            .map_err(|_| SprintError::parse(filename, None, format!("The sequence of {} is not valid UTF-8", protein_id)))?
+            .to_string();
+        records.push((protein_id, sequence_string));
     }
 
-    Ok(proteins)
+    let (proteins, mut report) = sanitize_proteins(records, new, policy).map_err(|error| error.at(filename, None))?;
+    report.unmatched_headers = unmatched_headers;
+    Ok((proteins, report))
+}
+
+/// Saves the sequences of a protein set to a FASTA file (with a record for
+/// every alias of the collapsed proteins)
+pub fn save_fasta(protein_set: &ProteinSet, filename: &str) -> Result<(), Error> {
+    let output = protein_set
+        .iter()
+        .flat_map(|protein| {
+            protein_set
+                .names(protein.index())
+                .into_iter()
+                .map(move |name| format!(">{}\n{}\n", name, protein.seq()))
+        })
+        .collect::<String>();
+
+    std::fs::write(filename, output)?;
+    Ok(())
 }
 
 ///// Saves a HashSet of HSPs to a file with the locations
 ///// for a protein pair sorted by position
+///// (compressed if the filename ends with .gz or .zst)
 pub fn save_hsps(
     hsps: HashSet<HSP>,
     protein_set: &ProteinSet,
     filename: &str,
 ) -> Result<(), Error> {
-    let output = hsps
-        .into_iter()
-        .map(|hsp| hsp.as_string(protein_set))
-        .collect::<Vec<String>>()
-        .join("\n");
+    let mut writer = CompressedWriter::create(filename)?;
+    for (i, hsp) in hsps.into_iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b"\n")?;
+        }
+        writer.write_all(hsp.as_string(protein_set).as_bytes())?;
+    }
 
-    std::fs::write(filename, output)?;
-    Ok(())
+    writer.finish()
+}
+
+/// Lines of a (possibly compressed) text file, read one at a time
+fn read_lines(filename: &str) -> Result<impl Iterator<Item = Result<String, SprintError>> + '_, SprintError> {
+    let reader = open_reader(filename).map_err(|error| SprintError::io(filename, error))?;
+    Ok(reader.lines().map(move |line| line.map_err(|error| SprintError::io(filename, error))))
 }
 
-/// Loads the HSPs from a file into a HashSet of HSPs
-pub fn load_hsps(filename: &str, protein_set: &ProteinSet) -> HashSet<HSP> {
-    let file_contents =
-        read_to_string(filename).expect(&format!("The file {} could not be read.", filename));
+/// Loads the HSPs from a file (text, possibly compressed, or binary) into a
+/// HashSet of HSPs
+pub fn load_hsps(filename: &str, protein_set: &ProteinSet) -> Result<HashSet<HSP>, SprintError> {
+    if is_binary_hsp_file(filename).map_err(|error| SprintError::io(filename, error))? {
+        let hsp_file = HspFile::open(filename, protein_set).map_err(|error| SprintError::io(filename, error))?;
+        return Ok(hsp_file.iter().collect());
+    }
+
+    // Binary files are memory-mapped, so they cannot be read compressed
+    let mut reader = open_reader(filename).map_err(|error| SprintError::io(filename, error))?;
+    if reader.fill_buf().map_err(|error| SprintError::io(filename, error))?.starts_with(MAGIC) {
+        return Err(SprintError::invalid_input(format!(
+            "{} is a compressed binary HSP file, decompress it first",
+            filename
+        )));
+    }
     let mut hsps: HashSet<HSP> = HashSet::new();
 
-    for line in file_contents.lines() {
-        hsps.insert(HSP::from(line.to_owned(), protein_set));
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|error| SprintError::io(filename, error))?;
+        let hsp = HSP::from(line, protein_set).map_err(|error| error.at(filename, Some(i + 1)))?;
+        hsps.insert(hsp);
     }
 
-    hsps
+    Ok(hsps)
 }
 
-/// Loads protein pairs
-pub fn load_pairs(filename: &str) -> Vec<(String, String)> {
-    let file_contents: String =
-        read_to_string(filename).expect(&format!("The file {} could not be read.", filename));
+/// Opens an HSP file for scoring: binary files are memory-mapped and
+/// text files are loaded into a HashSet of HSPs
+pub fn open_hsps(filename: &str, protein_set: &ProteinSet) -> Result<Box<dyn HspSource>, SprintError> {
+    if is_binary_hsp_file(filename).map_err(|error| SprintError::io(filename, error))? {
+        Ok(Box::new(HspFile::open(filename, protein_set).map_err(|error| SprintError::io(filename, error))?))
+    } else {
+        Ok(Box::new(load_hsps(filename, protein_set)?))
+    }
+}
+
+/// Fingerprint (FNV-1a hash) of the content of a file, read one buffer at
+/// a time
+pub fn fingerprint_file(filename: &str) -> Result<u64, SprintError> {
+    let file = File::open(filename).map_err(|error| SprintError::io(filename, error))?;
+    let mut reader = BufReader::new(file);
+    let mut hash = fnv1a(std::iter::empty());
+    loop {
+        let buffer = reader.fill_buf().map_err(|error| SprintError::io(filename, error))?;
+        if buffer.is_empty() {
+            return Ok(hash);
+        }
+        hash = fnv1a_extend(hash, buffer.iter().cloned());
+        let length = buffer.len();
+        reader.consume(length);
+    }
+}
 
+/// Loads spaced seeds from a file (one seed per line, lines starting
+/// with '#' are ignored)
+pub fn load_seeds(filename: &str) -> Result<Vec<Seed>, SprintError> {
+    let mut seeds = vec![];
+    for (i, line) in read_lines(filename)?.enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        seeds.push(Seed::new(line).map_err(|error| error.at(filename, Some(i + 1)))?);
+    }
+
+    if seeds.is_empty() {
+        return Err(SprintError::parse(filename, None, "The seed file does not contain any seed."));
+    }
 
+    Ok(seeds)
+}
+
+/// Loads protein pairs (blank lines are ignored, the file may be compressed)
+pub fn load_pairs(filename: &str) -> Result<Vec<(String, String)>, SprintError> {
     let mut parsed: HashSet<(String, String)> = HashSet::new();
     let mut pairs = Vec::new();
 
-    for (i, line) in file_contents.lines().enumerate() {
-        let mut delimiter: &str = " "; //default
+    for (i, line) in read_lines(filename)?.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let delimiter: &str;
         if line.contains(" ") {
             delimiter = " ";
         } else if line.contains(",") {
@@ -73,12 +194,11 @@ pub fn load_pairs(filename: &str) -> Vec<(String, String)> {
         } else if line.contains("\t") {
             delimiter = "\t";
         } else {
-            println!(
-                "WARNING: The pairs in a protein pair file must all be on their own
-                   line and separated by a space, a comma or a tab. ({}, line {})",
+            return Err(SprintError::parse(
                 filename,
-                i + 1
-            );
+                Some(i + 1),
+                "The proteins of a pair must be separated by a space, a comma or a tab.",
+            ));
         }
         let proteins: Vec<&str> = line.split(delimiter).collect();
 
@@ -98,77 +218,399 @@ pub fn load_pairs(filename: &str) -> Vec<(String, String)> {
         }
     }
 
-    pairs
+    Ok(pairs)
 }
 
-/// Saves scores
+/// Saves protein pairs (one `protein1 protein2` line per pair), e.g. to be
+/// loaded with `load_pairs`
+pub fn save_pairs(pairs: &[(String, String)], filename: &str) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    for pair in pairs {
+        writeln!(writer, "{} {}", pair.0, pair.1)?;
+    }
+    writer.flush()
+}
+
+/// Loads the scores of some pairs (keyed by `ordered_pair`) from a file of
+/// scores (`protein1 protein2 score` lines, as saved by `save_scores` or
+/// `save_pair_scores`)
+///
+/// The file is read line by line and only the scores of the given pairs are
+/// kept, so that the scores of a large protein set are never all loaded.
+/// Empty lines are skipped and any other line without a valid score is an
+/// error.
+pub fn load_scores(
+    filename: &str,
+    pairs: &HashSet<(String, String)>,
+) -> Result<HashMap<(String, String), f32>, SprintError> {
+    let mut scores = HashMap::new();
+    for (i, line) in read_lines(filename)?.enumerate() {
+        let line = line?;
+        let fields: Vec<&str> = line.split([' ', ',', '\t']).filter(|field| !field.is_empty()).collect();
+        if fields.is_empty() {
+            continue;
+        }
+        let score = match fields.len() {
+            3 => fields[2].parse::<f32>().ok(),
+            _ => None,
+        };
+        let score = score.ok_or_else(|| SprintError::parse(
+            filename,
+            Some(i + 1),
+            format!("Expected `protein1 protein2 score`, found `{}`", line),
+        ))?;
+
+        let pair = ordered_pair(fields[0], fields[1]);
+        if pairs.contains(&pair) {
+            scores.insert(pair, score);
+        }
+    }
+    Ok(scores)
+}
+
+/// Saves scores (compressed if the filename ends with .gz or .zst)
 pub fn save_scores(
-    scores: &Array2<f32>,
+    scores: &ScoreMatrix,
     protein_set: &ProteinSet,
     filename: &str,
 ) -> std::io::Result<()> {
-    let file_contents: String = (0..protein_set.len())
-        .collect::<Vec<usize>>()
-        .par_iter()
-        .map(|&row_index| {
-            let mut row_scores: Vec<String> = vec![];
-            let num_sequences_in_row = row_index + 1;
-            for j in 0..num_sequences_in_row {
-                let protein1 = protein_set.get_protein_by_id(row_index).name();
-                let protein2 = protein_set.get_protein_by_id(j).name();
-                row_scores.push(format!(
-                    "{} {} {}",
-                    protein1,
-                    protein2,
-                    scores[[row_index, j]]
-                ));
-            }
-            row_scores.join("\n")
-        })
-        .collect::<Vec<String>>()
-        .join("\n");
-
-    //Parallel
-    std::fs::write(filename, file_contents)?;
-    Ok(())
+    write_scores(scores, protein_set, filename, false)
 }
 
-/// Saves scores
+/// Saves the scores of the pairs involving at least one new protein (e.g.
+/// peptides)
 pub fn save_peptide_scores(
-    scores: &Array2<f32>,
+    scores: &ScoreMatrix,
     protein_set: &ProteinSet,
     filename: &str,
 ) -> std::io::Result<()> {
-    let file_contents: String = (0..protein_set.len())
-        .collect::<Vec<usize>>()
-        .par_iter()
-        .map(|&row_index| {
-            let mut row_scores: Vec<String> = vec![];
-            let num_sequences_in_row = row_index + 1;
-            for j in 0..num_sequences_in_row {
-                let protein1 = protein_set.get_protein_by_id(row_index);
-                let protein2 = protein_set.get_protein_by_id(j);
+    write_scores(scores, protein_set, filename, true)
+}
+
+/// Saves the scores of given pairs of proteins (one `protein1 protein2 score`
+/// line per pair, in the order of the pairs)
+pub fn save_pair_scores(
+    pairs: &[(String, String)],
+    scores: &[f32],
+    filename: &str,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    for (i, (pair, score)) in pairs.iter().zip(scores.iter()).enumerate() {
+        if i > 0 {
+            writer.write_all(b"\n")?;
+        }
+        write!(writer, "{} {} {}", pair.0, pair.1, score)?;
+    }
+    writer.flush()
+}
 
-                // Only save pairs where at least one of the two proteins was newly scored
-                if !protein1.is_new() && !protein2.is_new() {
-                    continue;
+/// Saves the scores of a cross-validation (one
+/// `fold protein1 protein2 label score` line per held-out pair, with a label
+/// of 1 for the training pairs and 0 for the negatives)
+pub fn save_cross_validation(
+    results: &[HeldOutPair],
+    protein_set: &ProteinSet,
+    filename: &str,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writer.write_all(b"fold protein1 protein2 label score")?;
+    for pair in results {
+        write!(
+            writer,
+            "\n{} {} {} {} {}",
+            pair.fold,
+            protein_set.get_protein_by_id(pair.protein1).name(),
+            protein_set.get_protein_by_id(pair.protein2).name(),
+            pair.positive as u8,
+            pair.score
+        )?;
+    }
+    writer.flush()
+}
+
+/// Saves the summary of an evaluation (one `metric value` line per metric)
+pub fn save_evaluation(evaluation: &Evaluation, filename: &str) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "positives {}", evaluation.positives)?;
+    writeln!(writer, "negatives {}", evaluation.negatives)?;
+    writeln!(writer, "auroc {}", evaluation.auroc)?;
+    writeln!(writer, "aupr {}", evaluation.aupr)?;
+    for (k, precision) in evaluation.precision_at_k.iter() {
+        writeln!(writer, "precision_at_{} {}", k, precision)?;
+    }
+    for (recall, precision) in evaluation.precision_at_recall.iter() {
+        writeln!(writer, "precision_at_recall_{} {}", recall, precision)?;
+    }
+    writer.flush()
+}
+
+/// Saves the points of the ROC and precision-recall curves of an evaluation
+pub fn save_curve(evaluation: &Evaluation, filename: &str) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "threshold true_positives false_positives precision recall false_positive_rate")?;
+    for point in evaluation.curve.iter() {
+        writeln!(
+            writer,
+            "{} {} {} {} {} {}",
+            point.threshold,
+            point.true_positives,
+            point.false_positives,
+            point.precision,
+            point.recall,
+            point.false_positive_rate
+        )?;
+    }
+    writer.flush()
+}
+
+/// Saves per-residue profiles (one `protein,position,residue,contribution`
+/// line per residue, positions starting at 0)
+pub fn save_profiles_csv(
+    profiles: &[(usize, Vec<f32>)],
+    protein_set: &ProteinSet,
+    filename: &str,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "protein,position,residue,contribution")?;
+    for (index, profile) in profiles {
+        let protein = protein_set.get_protein_by_id(*index);
+        for (position, value) in profile.iter().enumerate() {
+            writeln!(writer, "{},{},{},{}", protein.name(), position, protein.sub(position, position), value)?;
+        }
+    }
+    writer.flush()
+}
+
+/// Saves per-residue profiles as a bedGraph annotation track, with the
+/// proteins as sequences (one `protein start end contribution` line per
+/// residue)
+pub fn save_profiles_track(
+    profiles: &[(usize, Vec<f32>)],
+    protein_set: &ProteinSet,
+    track_name: &str,
+    filename: &str,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "track type=bedGraph name=\"{}\"", track_name)?;
+    for (index, profile) in profiles {
+        let name = protein_set.get_protein_by_id(*index).name();
+        for (position, value) in profile.iter().enumerate() {
+            writeln!(writer, "{}\t{}\t{}\t{}", name, position, position + 1, value)?;
+        }
+    }
+    writer.flush()
+}
+
+/// Saves predicted binding segments (one `<label>,rank,start,end,score` line
+/// per segment, for every named profile; positions start at 0 and the ends
+/// are excluded)
+pub fn save_segments(segments: &[(String, Vec<Segment>)], label: &str, filename: &str) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "{},rank,start,end,score", label)?;
+    for (name, segments) in segments {
+        for (rank, segment) in segments.iter().enumerate() {
+            writeln!(writer, "{},{},{},{},{}", name, rank + 1, segment.start, segment.end, segment.score)?;
+        }
+    }
+    writer.flush()
+}
+
+/// Saves the score deltas of the single substitutions of peptides (one
+/// `peptide,position,wild_type,A,R,...` line per position of every peptide,
+/// positions starting at 1)
+pub fn save_delta_matrices(matrices: &[(&Protein, Vec<Vec<f32>>)], filename: &str) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    let columns: Vec<String> = AMINO_ACIDS.chars().map(|residue| residue.to_string()).collect();
+    writeln!(writer, "peptide,position,wild_type,{}", columns.join(","))?;
+    for (peptide, matrix) in matrices {
+        for (position, deltas) in matrix.iter().enumerate() {
+            let deltas: Vec<String> = deltas.iter().map(|delta| delta.to_string()).collect();
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                peptide.name(),
+                position + 1,
+                peptide.sub(position, position),
+                deltas.join(",")
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+/// Saves the scores of variants (one `variant,score,delta` line per
+/// variant)
+pub fn save_variant_scores(variants: &[(String, f32, f32)], filename: &str) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "variant,score,delta")?;
+    for (name, score, delta) in variants {
+        writeln!(writer, "{},{},{}", name, score, delta)?;
+    }
+    writer.flush()
+}
+
+/// Saves a population of peptides (one
+/// `rank,sequence,fitness,score,off_target` line per peptide)
+pub fn save_candidates(population: &[Candidate], filename: &str) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "rank,sequence,fitness,score,off_target")?;
+    for (rank, candidate) in population.iter().enumerate() {
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            rank + 1,
+            candidate.sequence,
+            candidate.fitness,
+            candidate.score,
+            candidate.off_target
+        )?;
+    }
+    writer.flush()
+}
+
+/// Saves an explanation as tab-separated values: a `# protein1 protein2
+/// score` comment line, then one line per support, from the highest
+/// contribution to the lowest
+pub fn save_explanation_tsv(explanation: &Explanation, protein_set: &ProteinSet, filename: &str) -> std::io::Result<()> {
+    let name = |index: usize| protein_set.get_protein_by_id(index).name();
+
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "# {} {} {}", name(explanation.protein1), name(explanation.protein2), explanation.score)?;
+    writeln!(
+        writer,
+        "rank\tinteractor1\tinteractor2\tcontribution\t\
+         partner1\tposition1\tpartner_position1\tlength1\thsp_score1\t\
+         partner2\tposition2\tpartner_position2\tlength2\thsp_score2"
+    )?;
+    for (rank, support) in explanation.supports.iter().enumerate() {
+        write!(
+            writer,
+            "{}\t{}\t{}\t{}",
+            rank + 1,
+            name(support.training_pair.0),
+            name(support.training_pair.1),
+            support.contribution
+        )?;
+        for hsp in [&support.hsp1, &support.hsp2] {
+            write!(
+                writer,
+                "\t{}\t{}\t{}\t{}\t{}",
+                name(hsp.partner),
+                hsp.position,
+                hsp.partner_position,
+                hsp.length,
+                hsp.score
+            )?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+/// Saves an explanation as a JSON object
+pub fn save_explanation_json(explanation: &Explanation, protein_set: &ProteinSet, filename: &str) -> std::io::Result<()> {
+    let name = |index: usize| json_string(&protein_set.get_protein_by_id(index).name());
+    let hsp_json = |hsp: &SupportingHsp| {
+        format!(
+            "{{\"protein\": {}, \"partner\": {}, \"position\": {}, \"partner_position\": {}, \"length\": {}, \"score\": {}}}",
+            name(hsp.protein),
+            name(hsp.partner),
+            hsp.position,
+            hsp.partner_position,
+            hsp.length,
+            hsp.score
+        )
+    };
+
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "{{")?;
+    writeln!(writer, "  \"protein1\": {},", name(explanation.protein1))?;
+    writeln!(writer, "  \"protein2\": {},", name(explanation.protein2))?;
+    writeln!(writer, "  \"score\": {},", explanation.score)?;
+    write!(writer, "  \"supports\": [")?;
+    for (i, support) in explanation.supports.iter().enumerate() {
+        write!(
+            writer,
+            "{}\n    {{\"training_pair\": [{}, {}], \"contribution\": {}, \"hsp1\": {}, \"hsp2\": {}}}",
+            if i > 0 { "," } else { "" },
+            name(support.training_pair.0),
+            name(support.training_pair.1),
+            support.contribution,
+            hsp_json(&support.hsp1),
+            hsp_json(&support.hsp2)
+        )?;
+    }
+    if !explanation.supports.is_empty() {
+        write!(writer, "\n  ")?;
+    }
+    writeln!(writer, "]")?;
+    writeln!(writer, "}}")?;
+    writer.flush()
+}
+
+/// Quoted JSON string
+fn json_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for character in value.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            character if (character as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", character as u32)),
+            character => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes the lower triangle of the scores, formatting blocks of rows in
+/// parallel so that the whole file is never held in memory
+/// (compressed according to the extension of the filename)
+fn write_scores(
+    scores: &ScoreMatrix,
+    protein_set: &ProteinSet,
+    filename: &str,
+    new_only: bool,
+) -> std::io::Result<()> {
+    let mut writer = CompressedWriter::create(filename)?;
+    let names: Vec<Vec<String>> = (0..protein_set.len()).map(|i| protein_set.names(i)).collect();
+    let mut first = true;
+
+    let rows: Vec<usize> = (0..protein_set.len()).collect();
+    for block in rows.chunks(SCORE_ROWS_PER_BLOCK) {
+        let lines: Vec<String> = block
+            .par_iter()
+            .map(|&row_index| {
+                let protein1 = protein_set.get_protein_by_id(row_index);
+                let mut row_scores: Vec<String> = vec![];
+                for (j, score) in scores.row(row_index).iter().enumerate() {
+                    // Only save pairs where at least one of the two proteins was newly scored
+                    if new_only && !protein1.is_new() && !protein_set.is_new(j) {
+                        continue;
+                    }
+                    // The scores of collapsed proteins are saved for all
+                    // their aliases
+                    for (a, name1) in names[row_index].iter().enumerate() {
+                        for name2 in names[j].iter().take(if j == row_index { a + 1 } else { usize::MAX }) {
+                            row_scores.push(format!("{} {} {}", name1, name2, score));
+                        }
+                    }
                 }
+                row_scores.join("\n")
+            })
+            .filter(|row| !row.is_empty())
+            .collect();
 
-                row_scores.push(format!(
-                    "{} {} {}",
-                    protein1.name(),
-                    protein2.name(),
-                    scores[[row_index, j]]
-                ));
+        for line in lines {
+            if !first {
+                writer.write_all(b"\n")?;
             }
-            row_scores.join("\n")
-        })
-        .collect::<Vec<String>>()
-        .join("\n");
+            writer.write_all(line.as_bytes())?;
+            first = false;
+        }
+    }
 
-    //Parallel
-    std::fs::write(filename, file_contents)?;
-    Ok(())
+    writer.finish()
 }
 
 /// Save contributions
@@ -179,7 +621,11 @@ pub fn save_contributions(
 ) -> std::io::Result<()> {
     let mut file_contents = "peptide,target_position,contribution\n".to_owned();
 
-    for (peptide_index, vec) in contributions.iter() {
+    let mut peptide_indices: Vec<&usize> = contributions.keys().collect();
+    peptide_indices.sort();
+
+    for peptide_index in peptide_indices {
+        let vec = &contributions[peptide_index];
         let peptide_name = protein_set.get_protein_by_id(*peptide_index).name();
 
         for position in 0..vec.len() {
@@ -192,3 +638,62 @@ pub fn save_contributions(
     std::fs::write(filename, file_contents)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temporary_file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("sprint_fileio_{}_{}", name, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn ids_are_taken_from_the_headers() {
+        let filename = temporary_file(
+            "headers.fasta",
+            ">sp|P12345|ABC_HUMAN ABC transporter GN=ABC1\nACDE\n>tr|Q67890|DEF_HUMAN\nFGHI\n>local protein\nKLMN\n",
+        );
+        let policy = SequencePolicy::default();
+
+        let (proteins, report) = load_fasta(&filename, false, &policy, &HeaderFormat::Accession).unwrap();
+        let names: Vec<String> = proteins.iter().map(|protein| protein.name()).collect();
+        assert_eq!(names, vec!["P12345", "Q67890", "local"]);
+        assert_eq!(report.unmatched_headers, vec!["local"]);
+
+        let (proteins, report) = load_fasta(&filename, false, &policy, &HeaderFormat::GeneName).unwrap();
+        let names: Vec<String> = proteins.iter().map(|protein| protein.name()).collect();
+        assert_eq!(names, vec!["ABC1", "tr|Q67890|DEF_HUMAN", "local"]);
+        assert_eq!(report.unmatched_headers, vec!["tr|Q67890|DEF_HUMAN", "local"]);
+
+        let (_, report) = load_fasta(&filename, false, &policy, &HeaderFormat::Id).unwrap();
+        assert!(report.is_empty());
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn only_the_scores_of_the_given_pairs_are_loaded() {
+        let filename = temporary_file("scores", "A B 1.5\nC,A,0.25\n\nB\tC\t-2\nD E 3\n");
+        let pairs: HashSet<(String, String)> = [ordered_pair("B", "A"), ordered_pair("A", "C"), ordered_pair("A", "D")].into();
+        let scores = load_scores(&filename, &pairs).unwrap();
+        assert_eq!(scores, HashMap::from([(ordered_pair("A", "B"), 1.5), (ordered_pair("A", "C"), 0.25)]));
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn malformed_score_lines_are_rejected() {
+        let files = [("missing", "A B 1.5\nA C\n", 2), ("invalid", "A B 1.5\nA C high\n", 2), ("extra", "A B 1.5 2\n", 1)];
+        for (name, content, expected_line) in files {
+            let filename = temporary_file(name, content);
+            match load_scores(&filename, &HashSet::new()) {
+                Err(SprintError::Parse { path, line, .. }) => {
+                    assert_eq!(path, filename);
+                    assert_eq!(line, Some(expected_line));
+                }
+                _ => panic!("{} line accepted", name),
+            }
+            std::fs::remove_file(&filename).unwrap();
+        }
+    }
+}
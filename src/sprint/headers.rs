@@ -0,0 +1,125 @@
+use regex::Regex;
+
+use crate::sprint::error::SprintError;
+
+/// How the ID of a protein is taken from its FASTA header
+#[derive(Clone, Debug, Default)]
+pub enum HeaderFormat {
+    /// The first word of the header (e.g. `sp|P12345|ABC_HUMAN`)
+    #[default]
+    Id,
+    /// The accession of a UniProt header (`P12345`)
+    Accession,
+    /// The entry name of a UniProt header (`ABC_HUMAN`)
+    EntryName,
+    /// The gene name of a UniProt header (the `GN=` field)
+    GeneName,
+    /// The first group of a regular expression matched against the whole
+    /// header (or the whole match without groups)
+    Pattern(Regex),
+}
+
+impl HeaderFormat {
+    /// Parses `id`, `accession`, `entry_name`, `gene` or `regex:<pattern>`
+    pub fn from_name(name: &str) -> Result<Self, SprintError> {
+        if let Some(pattern) = name.strip_prefix("regex:") {
+            return Regex::new(pattern)
+                .map(HeaderFormat::Pattern)
+                .map_err(|error| SprintError::invalid_input(format!("Invalid header pattern: {}", error)));
+        }
+        match name.to_lowercase().as_str() {
+            "id" => Ok(HeaderFormat::Id),
+            "accession" => Ok(HeaderFormat::Accession),
+            "entry_name" => Ok(HeaderFormat::EntryName),
+            "gene" => Ok(HeaderFormat::GeneName),
+            _ => Err(SprintError::invalid_input(format!(
+                "Unknown header format: {} (expected id, accession, entry_name, gene or regex:<pattern>)",
+                name
+            ))),
+        }
+    }
+
+    /// ID of a protein given the first word of its header and the rest of it
+    /// (None if the header does not have the expected format)
+    pub fn extract(&self, id: &str, description: Option<&str>) -> Option<String> {
+        match self {
+            HeaderFormat::Id => Some(id.to_string()),
+            HeaderFormat::Accession => uniprot_fields(id).map(|(accession, _)| accession.to_string()),
+            HeaderFormat::EntryName => uniprot_fields(id).map(|(_, entry_name)| entry_name.to_string()),
+            HeaderFormat::GeneName => description?
+                .split_whitespace()
+                .find_map(|field| field.strip_prefix("GN="))
+                .filter(|gene| !gene.is_empty())
+                .map(|gene| gene.to_string()),
+            HeaderFormat::Pattern(regex) => {
+                let header = match description {
+                    Some(description) => format!("{} {}", id, description),
+                    None => id.to_string(),
+                };
+                let captures = regex.captures(&header)?;
+                captures
+                    .get(1)
+                    .or_else(|| captures.get(0))
+                    .map(|id| id.as_str().to_string())
+                    .filter(|id| !id.is_empty())
+            }
+        }
+    }
+}
+
+/// Accession and entry name of a UniProt ID (`db|accession|entry_name`)
+fn uniprot_fields(id: &str) -> Option<(&str, &str)> {
+    let fields: Vec<&str> = id.split('|').collect();
+    match fields.as_slice() {
+        [_, accession, entry_name] if !accession.is_empty() && !entry_name.is_empty() => Some((accession, entry_name)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "sp|P12345|ABC_HUMAN";
+    const DESCRIPTION: &str = "ABC transporter OS=Homo sapiens OX=9606 GN=ABC1 PE=1 SV=2";
+
+    fn extract(format: &str, id: &str, description: Option<&str>) -> Option<String> {
+        HeaderFormat::from_name(format).unwrap().extract(id, description)
+    }
+
+    #[test]
+    fn uniprot_fields_are_extracted() {
+        assert_eq!(extract("id", ID, Some(DESCRIPTION)).as_deref(), Some(ID));
+        assert_eq!(extract("accession", ID, Some(DESCRIPTION)).as_deref(), Some("P12345"));
+        assert_eq!(extract("ENTRY_NAME", ID, None).as_deref(), Some("ABC_HUMAN"));
+        assert_eq!(extract("gene", ID, Some(DESCRIPTION)).as_deref(), Some("ABC1"));
+    }
+
+    #[test]
+    fn headers_without_the_fields_do_not_match() {
+        assert_eq!(extract("accession", "P12345", None), None);
+        assert_eq!(extract("entry_name", "sp||ABC_HUMAN", None), None);
+        assert_eq!(extract("accession", "a|b|c|d", None), None);
+        assert_eq!(extract("gene", ID, None), None);
+        assert_eq!(extract("gene", ID, Some("ABC transporter GN= PE=1")), None);
+        assert_eq!(extract("gene", ID, Some("ABC transporter OS=Homo sapiens")), None);
+    }
+
+    #[test]
+    fn patterns_are_matched_against_the_whole_header() {
+        // The first group, or else the whole match
+        assert_eq!(extract(r"regex:OX=(\d+)", ID, Some(DESCRIPTION)).as_deref(), Some("9606"));
+        assert_eq!(extract(r"regex:\|[A-Z0-9]+\|", ID, None).as_deref(), Some("|P12345|"));
+        assert_eq!(extract(r"regex:^(\w+)\|", ID, None).as_deref(), Some("sp"));
+        assert_eq!(extract(r"regex:GN=(\S+)", ID, Some("no gene")), None);
+        assert_eq!(extract(r"regex:GN=(\S*)", ID, Some("GN= empty")), None);
+    }
+
+    #[test]
+    fn unknown_formats_and_invalid_patterns_are_rejected() {
+        assert!(HeaderFormat::from_name("uniprot").is_err());
+        assert!(HeaderFormat::from_name("regex:(").is_err());
+        assert!(matches!(HeaderFormat::from_name("Gene"), Ok(HeaderFormat::GeneName)));
+        assert!(matches!(HeaderFormat::default(), HeaderFormat::Id));
+    }
+}
@@ -1,8 +1,13 @@
 use pyo3::prelude::*;
 
+use crate::sprint::error::SprintError;
 use crate::sprint::proteinset::ProteinSet;
 use crate::sprint::location::Location;
 
+/// HSP identified by the names of its proteins: (protein 1, protein 2,
+/// position 1, position 2, length)
+pub type NamedHsp = (String, String, usize, usize, usize);
+
 #[pyclass]
 #[derive(Hash)]
 pub struct HSP {
@@ -57,6 +62,9 @@ impl HSP {
         self.length
     }
 
+    /// Location of the first (0) or second (1) side of the HSP (the callers
+    /// only ever pass these constants, so another index is a bug and panics
+    /// like an out-of-bounds index)
     pub fn location(&self, index: usize) -> &Location {
         if index == 0 {
             return &self.location1;
@@ -85,37 +93,46 @@ impl HSP {
 
 
 impl HSP {
-    pub fn from(string: String, protein_set: &ProteinSet) -> Self {
+    /// Parses an HSP (`protein1 protein2 position1 position2 length`)
+    pub fn from(string: String, protein_set: &ProteinSet) -> Result<Self, SprintError> {
         let tokens: Vec<&str> = string.split_whitespace().collect();
+        if tokens.len() != 5 {
+            return Err(SprintError::invalid_input(format!("Expected 5 fields in an HSP, found {}", tokens.len())));
+        }
+        let number = |token: &str| {
+            token.parse::<usize>().map_err(|_| SprintError::invalid_input(format!("Invalid number in an HSP: {}", token)))
+        };
         let protein1 = tokens[0].to_owned();
         let protein2 = tokens[1].to_owned();
-        let position1 = tokens[2].parse::<usize>().unwrap();
-        let position2 = tokens[3].parse::<usize>().unwrap();
-        let length = tokens[4].parse::<usize>().unwrap();
+        let position1 = number(tokens[2])?;
+        let position2 = number(tokens[3])?;
+        let length = number(tokens[4])?;
+        let index1 = protein_set.get_protein_by_name(&protein1)?.index();
+        let index2 = protein_set.get_protein_by_name(&protein2)?.index();
         match protein1 < protein2 {
-            true => HSP {
-                location1: Location::new(protein_set.get_protein_by_name(&protein1).index(), position1),
-                location2: Location::new(protein_set.get_protein_by_name(&protein2).index(), position2),
+            true => Ok(HSP {
+                location1: Location::new(index1, position1),
+                location2: Location::new(index2, position2),
                 length
-            },
-            false => HSP {
-                location2: Location::new(protein_set.get_protein_by_name(&protein1).index(), position1),
-                location1: Location::new(protein_set.get_protein_by_name(&protein2).index(), position2),
+            }),
+            false => Ok(HSP {
+                location2: Location::new(index1, position1),
+                location1: Location::new(index2, position2),
                 length
-            }
+            })
         }
         
     }
 
-    pub fn from_named_tuple(tuple: (String, String, usize, usize, usize), protein_set: &ProteinSet) -> Self {
-        let id1 = protein_set.get_protein_by_name(&tuple.0).index();
-        let id2 = protein_set.get_protein_by_name(&tuple.1).index();
+    pub fn from_named_tuple(tuple: NamedHsp, protein_set: &ProteinSet) -> Result<Self, SprintError> {
+        let id1 = protein_set.get_protein_by_name(&tuple.0)?.index();
+        let id2 = protein_set.get_protein_by_name(&tuple.1)?.index();
         let location1 = Location::new(id1, tuple.2);
         let location2 = Location::new(id2, tuple.3);
-        HSP::new(location1, location2, tuple.4)
+        Ok(HSP::new(location1, location2, tuple.4))
     }
 
-    pub fn to_named_tuple(&self, protein_set: &ProteinSet) -> (String, String, usize, usize, usize) {
+    pub fn to_named_tuple(&self, protein_set: &ProteinSet) -> NamedHsp {
         let protein1 = protein_set.get_protein_by_id(self.location1.index()).name();
         let protein2 = protein_set.get_protein_by_id(self.location2.index()).name();
         (protein1, protein2, self.location1.position(), self.location2.position(), self.length)
@@ -0,0 +1,572 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Read, Write};
+
+use memmap2::Mmap;
+
+use crate::sprint::error::SprintError;
+use crate::sprint::hsp::HSP;
+use crate::sprint::location::Location;
+use crate::sprint::proteinset::ProteinSet;
+
+/// First bytes of a binary HSP file
+pub const MAGIC: &[u8; 8] = b"SPRNTHSP";
+
+/// Version of the binary HSP format
+pub const VERSION: u32 = 1;
+
+/// Size of the fixed part of the header (magic, version, fingerprint,
+/// number of proteins, number of records and length of the parameters)
+const FIXED_HEADER_SIZE: usize = 8 + 4 + 8 + 8 + 8 + 8;
+
+/// Size of a record (partner index, position in the protein of the row,
+/// position in the partner and length, all u32)
+const RECORD_SIZE: usize = 16;
+
+/// Parameters used to produce a set of HSPs, stored in the header of
+/// binary HSP files
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HspParameters {
+    pub kmer_size: usize,
+    pub t_sim: Option<i16>,
+    pub t_hsp: Option<i16>,
+    pub t_count: Option<u16>,
+    pub matrix: Option<String>,
+    pub seeds: Vec<String>,
+}
+
+impl HspParameters {
+    /// Serializes the parameters as "key=value" lines
+    fn encode(&self) -> String {
+        let mut lines = vec![format!("kmer_size={}", self.kmer_size)];
+        if let Some(t_sim) = self.t_sim {
+            lines.push(format!("t_sim={}", t_sim));
+        }
+        if let Some(t_hsp) = self.t_hsp {
+            lines.push(format!("t_hsp={}", t_hsp));
+        }
+        if let Some(t_count) = self.t_count {
+            lines.push(format!("t_count={}", t_count));
+        }
+        if let Some(matrix) = &self.matrix {
+            lines.push(format!("matrix={}", matrix));
+        }
+        if !self.seeds.is_empty() {
+            lines.push(format!("seeds={}", self.seeds.join(",")));
+        }
+        lines.join("\n")
+    }
+
+    fn decode(string: &str) -> Result<Self, Error> {
+        let invalid = |line: &str| {
+            Error::new(ErrorKind::InvalidData, format!("Invalid parameter in HSP file header: {}", line))
+        };
+        let mut parameters = HspParameters::default();
+
+        for line in string.lines() {
+            let (key, value) = line.split_once('=').ok_or_else(|| invalid(line))?;
+            match key {
+                "kmer_size" => parameters.kmer_size = value.parse().map_err(|_| invalid(line))?,
+                "t_sim" => parameters.t_sim = Some(value.parse().map_err(|_| invalid(line))?),
+                "t_hsp" => parameters.t_hsp = Some(value.parse().map_err(|_| invalid(line))?),
+                "t_count" => parameters.t_count = Some(value.parse().map_err(|_| invalid(line))?),
+                "matrix" => parameters.matrix = Some(value.to_string()),
+                "seeds" => parameters.seeds = value.split(',').map(|seed| seed.to_string()).collect(),
+                _ => {} // Unknown parameters are ignored
+            }
+        }
+
+        Ok(parameters)
+    }
+
+    /// Checks that HSPs extracted with the given parameters can be added
+    /// to these ones (the parameters that are not stored are not checked)
+    pub fn check_extraction(&self, requested: &HspParameters) -> Result<(), SprintError> {
+        let mismatch = |field: &str, stored: String, requested: String| {
+            Err(SprintError::invalid_input(format!(
+                "The HSPs were extracted with {}={}, not {}",
+                field, stored, requested
+            )))
+        };
+
+        if self.kmer_size != requested.kmer_size {
+            return mismatch("kmer_size", self.kmer_size.to_string(), requested.kmer_size.to_string());
+        }
+        if let (Some(stored), Some(new)) = (self.t_sim, requested.t_sim) {
+            if stored != new {
+                return mismatch("t_sim", stored.to_string(), new.to_string());
+            }
+        }
+        if let (Some(stored), Some(new)) = (self.t_hsp, requested.t_hsp) {
+            if stored != new {
+                return mismatch("t_hsp", stored.to_string(), new.to_string());
+            }
+        }
+        if let (Some(stored), Some(new)) = (&self.matrix, &requested.matrix) {
+            if stored != new {
+                return mismatch("matrix", stored.clone(), new.clone());
+            }
+        }
+        if !self.seeds.is_empty() && self.seeds != requested.seeds {
+            return mismatch("seeds", self.seeds.join(","), requested.seeds.join(","));
+        }
+        Ok(())
+    }
+}
+
+/// Header of a binary HSP file
+#[derive(Clone, Debug)]
+pub struct HspFileHeader {
+    pub fingerprint: u64,
+    pub num_proteins: usize,
+    pub num_records: usize,
+    pub parameters: HspParameters,
+}
+
+/// Collection of HSPs that can be visited once each (e.g. to build the HSP
+/// table used in scoring)
+pub trait HspSource: Sync {
+    fn hsps(&self) -> Box<dyn Iterator<Item = HSP> + '_>;
+}
+
+impl HspSource for HashSet<HSP> {
+    fn hsps(&self) -> Box<dyn Iterator<Item = HSP> + '_> {
+        Box::new(self.iter().cloned())
+    }
+}
+
+/// Memory-mapped binary HSP file
+///
+/// Layout (little endian):
+///
+/// ```text
+/// magic (8 bytes) | version (u32) | fingerprint (u64) | number of proteins (u64)
+/// number of records (u64) | length of the parameters (u64) | parameters (UTF-8)
+/// padding to 8 bytes | index: number of proteins + 1 offsets (u64)
+/// records: partner (u32) | position (u32) | partner position (u32) | length (u32)
+/// ```
+///
+/// Every HSP is stored once, in the row of the protein with the lowest index
+/// (the lowest position first for HSPs within a protein). Rows are sorted by
+/// protein index and the records of protein `i` are the records
+/// `index[i]..index[i + 1]`.
+pub struct HspFile {
+    mmap: Mmap,
+    header: HspFileHeader,
+    index_start: usize,
+    records_start: usize,
+}
+
+impl HspFile {
+    /// Maps an HSP file in memory, checking that it was extracted from
+    /// (the first proteins of) `protein_set`
+    pub fn open(filename: &str, protein_set: &ProteinSet) -> Result<HspFile, Error> {
+        let invalid = |message: &str| {
+            Error::new(ErrorKind::InvalidData, format!("{} ({})", message, filename))
+        };
+
+        let file = File::open(filename)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        if mmap.len() < FIXED_HEADER_SIZE || &mmap[0..8] != MAGIC {
+            return Err(invalid("Not a binary HSP file"));
+        }
+
+        let version = read_u32(&mmap, 8);
+        if version != VERSION {
+            return Err(invalid(&format!("Unsupported HSP file version {}", version)));
+        }
+
+        let fingerprint = read_u64(&mmap, 12);
+        let num_proteins = read_u64(&mmap, 20) as usize;
+        let num_records = read_u64(&mmap, 28) as usize;
+        let parameters_length = read_u64(&mmap, 36) as usize;
+
+        let (index_start, records_start) = match layout(parameters_length, num_proteins, num_records) {
+            Some((index_start, records_start, file_size)) if file_size == mmap.len() => (index_start, records_start),
+            _ => return Err(invalid("Truncated or corrupted HSP file")),
+        };
+
+        let parameters = std::str::from_utf8(&mmap[FIXED_HEADER_SIZE..FIXED_HEADER_SIZE + parameters_length])
+            .map_err(|_| invalid("Invalid parameters in the HSP file header"))?;
+        let parameters = HspParameters::decode(parameters)?;
+
+        if num_proteins > protein_set.len() || protein_set.fingerprint(num_proteins) != fingerprint {
+            return Err(invalid("The HSPs were not extracted from these sequences"));
+        }
+
+        // The rows must cover the records in order, the partners must be
+        // proteins of the file and the HSPs must lie within both proteins,
+        // so that neither reading a row nor scoring its HSPs goes out of bounds
+        let offset = |protein_index: usize| read_u64(&mmap, index_start + protein_index * 8) as usize;
+        if offset(0) != 0
+            || offset(num_proteins) != num_records
+            || (0..num_proteins).any(|protein_index| offset(protein_index) > offset(protein_index + 1))
+        {
+            return Err(invalid("Corrupted index in the HSP file"));
+        }
+        for protein_index in 0..num_proteins {
+            let protein_length = protein_set.get_protein_by_id(protein_index).len();
+            for record in offset(protein_index)..offset(protein_index + 1) {
+                let record_start = records_start + record * RECORD_SIZE;
+                let partner = read_u32(&mmap, record_start) as usize;
+                if partner >= num_proteins {
+                    return Err(invalid("Invalid partner in the HSP file"));
+                }
+                let position = read_u32(&mmap, record_start + 4) as usize;
+                let partner_position = read_u32(&mmap, record_start + 8) as usize;
+                let length = read_u32(&mmap, record_start + 12) as usize;
+                if position + length > protein_length
+                    || partner_position + length > protein_set.get_protein_by_id(partner).len()
+                {
+                    return Err(invalid("HSP out of the bounds of its proteins in the HSP file"));
+                }
+            }
+        }
+
+        Ok(HspFile {
+            header: HspFileHeader { fingerprint, num_proteins, num_records, parameters },
+            mmap,
+            index_start,
+            records_start,
+        })
+    }
+
+    pub fn header(&self) -> &HspFileHeader {
+        &self.header
+    }
+
+    pub fn len(&self) -> usize {
+        self.header.num_records
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.header.num_records == 0
+    }
+
+    /// HSPs stored in the row of a protein (i.e. involving this protein
+    /// and a protein with a higher or equal index)
+    pub fn row(&self, protein_index: usize) -> impl Iterator<Item = HSP> + '_ {
+        let (start, end) = if protein_index < self.header.num_proteins {
+            (
+                read_u64(&self.mmap, self.index_start + protein_index * 8) as usize,
+                read_u64(&self.mmap, self.index_start + (protein_index + 1) * 8) as usize,
+            )
+        } else {
+            (0, 0)
+        };
+
+        (start..end).map(move |record| {
+            let offset = self.records_start + record * RECORD_SIZE;
+            HSP::new(
+                Location::new(protein_index, read_u32(&self.mmap, offset + 4) as usize),
+                Location::new(read_u32(&self.mmap, offset) as usize, read_u32(&self.mmap, offset + 8) as usize),
+                read_u32(&self.mmap, offset + 12) as usize,
+            )
+        })
+    }
+
+    /// All the HSPs in the file
+    pub fn iter(&self) -> impl Iterator<Item = HSP> + '_ {
+        (0..self.header.num_proteins).flat_map(move |protein_index| self.row(protein_index))
+    }
+}
+
+impl HspSource for HspFile {
+    fn hsps(&self) -> Box<dyn Iterator<Item = HSP> + '_> {
+        Box::new(self.iter())
+    }
+}
+
+/// Checks whether a file starts with the magic bytes of binary HSP files
+pub fn is_binary_hsp_file(filename: &str) -> Result<bool, Error> {
+    let mut magic = [0u8; 8];
+    let mut file = File::open(filename)?;
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == MAGIC),
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Saves HSPs to a binary HSP file
+pub fn save_binary_hsps(
+    hsps: &dyn HspSource,
+    protein_set: &ProteinSet,
+    parameters: &HspParameters,
+    filename: &str,
+) -> Result<(), Error> {
+    // Store every HSP in the row of its protein with the lowest index
+    let mut records: Vec<HspRecord> = hsps.hsps().map(|hsp| to_record(&hsp)).collect();
+    records.sort_unstable();
+    records.dedup();
+
+    let num_proteins = protein_set.len();
+    let mut index = vec![0u64; num_proteins + 1];
+    for record in records.iter() {
+        index[record.0 as usize + 1] += 1;
+    }
+    for i in 0..num_proteins {
+        index[i + 1] += index[i];
+    }
+
+    let mut writer = BufWriter::new(File::create(filename)?);
+    write_header(&mut writer, protein_set, parameters, &index)?;
+    for record in records.iter() {
+        write_record(&mut writer, record)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// HSP in the order in which it is stored: protein with the lowest index,
+/// partner, position in the protein, position in the partner and length
+pub(crate) type HspRecord = (u32, u32, u32, u32, u32);
+
+pub(crate) fn to_record(hsp: &HSP) -> HspRecord {
+    let (location1, location2) = (hsp.location(0), hsp.location(1));
+    let first = (location1.index(), location1.position()) <= (location2.index(), location2.position());
+    let (location1, location2) = if first { (location1, location2) } else { (location2, location1) };
+    (
+        location1.index() as u32,
+        location2.index() as u32,
+        location1.position() as u32,
+        location2.position() as u32,
+        hsp.len() as u32,
+    )
+}
+
+pub(crate) fn from_record(record: &HspRecord) -> HSP {
+    HSP::new(
+        Location::new(record.0 as usize, record.2 as usize),
+        Location::new(record.1 as usize, record.3 as usize),
+        record.4 as usize,
+    )
+}
+
+/// Serializes records as they are spilled to temporary files (5 u32 each)
+pub(crate) fn encode_records(records: &[HspRecord]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(records.len() * 20);
+    for record in records {
+        for field in [record.0, record.1, record.2, record.3, record.4] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+pub(crate) fn decode_records(bytes: &[u8]) -> Vec<HspRecord> {
+    bytes
+        .chunks_exact(20)
+        .map(|record| (
+            read_u32(record, 0),
+            read_u32(record, 4),
+            read_u32(record, 8),
+            read_u32(record, 12),
+            read_u32(record, 16),
+        ))
+        .collect()
+}
+
+/// Writes the header, parameters and index of a binary HSP file (`index`
+/// holds the cumulative number of records of every row)
+pub(crate) fn write_header<W: Write>(
+    writer: &mut W,
+    protein_set: &ProteinSet,
+    parameters: &HspParameters,
+    index: &[u64],
+) -> Result<(), Error> {
+    let num_proteins = index.len() - 1;
+    let num_records = index[num_proteins];
+    let parameters = parameters.encode();
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&protein_set.fingerprint(num_proteins).to_le_bytes())?;
+    writer.write_all(&(num_proteins as u64).to_le_bytes())?;
+    writer.write_all(&num_records.to_le_bytes())?;
+    writer.write_all(&(parameters.len() as u64).to_le_bytes())?;
+    writer.write_all(parameters.as_bytes())?;
+
+    let header_size = FIXED_HEADER_SIZE + parameters.len();
+    writer.write_all(&vec![0u8; align(header_size) - header_size])?;
+
+    for offset in index {
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Writes a record (the protein of the row is implied by the index)
+pub(crate) fn write_record<W: Write>(writer: &mut W, record: &HspRecord) -> Result<(), Error> {
+    writer.write_all(&record.1.to_le_bytes())?;
+    writer.write_all(&record.2.to_le_bytes())?;
+    writer.write_all(&record.3.to_le_bytes())?;
+    writer.write_all(&record.4.to_le_bytes())?;
+    Ok(())
+}
+
+#[inline(always)]
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[inline(always)]
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Rounds up to a multiple of 8 bytes
+fn align(size: usize) -> usize {
+    (size + 7) & !7
+}
+
+/// Start of the index, start of the records and size of a binary HSP file
+/// (None if the sizes read from the header overflow)
+fn layout(parameters_length: usize, num_proteins: usize, num_records: usize) -> Option<(usize, usize, usize)> {
+    let index_start = FIXED_HEADER_SIZE.checked_add(parameters_length)?.checked_add(7)? & !7;
+    let records_start = num_proteins.checked_add(1)?.checked_mul(8)?.checked_add(index_start)?;
+    let file_size = num_records.checked_mul(RECORD_SIZE)?.checked_add(records_start)?;
+    Some((index_start, records_start, file_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprint::protein::Protein;
+
+    fn protein_set() -> ProteinSet {
+        let sequences = ["MKTAYIAKQRQISFVKSHFSRQ", "MKTAYIAKQRQISFVKSHFSRQLEER", "GSHMLEDPVAG"];
+        let proteins = sequences
+            .iter()
+            .enumerate()
+            .map(|(i, sequence)| Protein::new(i, format!("P{}", i), sequence.to_string(), false).unwrap())
+            .collect();
+        ProteinSet::new(proteins).unwrap()
+    }
+
+    fn hsps() -> HashSet<HSP> {
+        [
+            HSP::new(Location::new(0, 0), Location::new(1, 0), 20),
+            HSP::new(Location::new(1, 5), Location::new(0, 3), 12),
+            HSP::new(Location::new(2, 1), Location::new(1, 14), 8),
+            HSP::new(Location::new(0, 2), Location::new(0, 10), 6),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("sprint_hspfile_{}_{}", name, std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn save(name: &str) -> (String, ProteinSet) {
+        let (path, protein_set) = (temp_path(name), protein_set());
+        let parameters = HspParameters { kmer_size: 20, t_sim: Some(15), ..Default::default() };
+        save_binary_hsps(&hsps(), &protein_set, &parameters, &path).unwrap();
+        (path, protein_set)
+    }
+
+    /// Overwrites the bytes of a file at an offset
+    fn patch(path: &str, offset: usize, bytes: &[u8]) {
+        let mut contents = std::fs::read(path).unwrap();
+        contents[offset..offset + bytes.len()].copy_from_slice(bytes);
+        std::fs::write(path, contents).unwrap();
+    }
+
+    fn index_start(path: &str) -> usize {
+        let contents = std::fs::read(path).unwrap();
+        align(FIXED_HEADER_SIZE + read_u64(&contents, 36) as usize)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (path, protein_set) = save("round_trip");
+        let hsp_file = HspFile::open(&path, &protein_set).unwrap();
+        assert_eq!(hsp_file.len(), 4);
+        assert_eq!(hsp_file.header().parameters.t_sim, Some(15));
+        assert!(hsp_file.iter().collect::<HashSet<HSP>>() == hsps());
+        assert!(is_binary_hsp_file(&path).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_non_monotonic_index() {
+        let (path, protein_set) = save("index");
+        // Row 1 ends before it starts
+        patch(&path, index_start(&path) + 16, &0u64.to_le_bytes());
+        assert_eq!(HspFile::open(&path, &protein_set).err().unwrap().kind(), ErrorKind::InvalidData);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_an_invalid_partner() {
+        let (path, protein_set) = save("partner");
+        let records_start = index_start(&path) + (protein_set.len() + 1) * 8;
+        patch(&path, records_start, &7u32.to_le_bytes());
+        assert!(HspFile::open(&path, &protein_set).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_an_hsp_out_of_bounds() {
+        let (path, protein_set) = save("bounds");
+        let records_start = index_start(&path) + (protein_set.len() + 1) * 8;
+        // The first HSP ends past the end of its proteins
+        patch(&path, records_start + 12, &23u32.to_le_bytes());
+        assert_eq!(HspFile::open(&path, &protein_set).err().unwrap().kind(), ErrorKind::InvalidData);
+        // The first HSP starts past the end of its partner
+        patch(&path, records_start + 12, &20u32.to_le_bytes());
+        patch(&path, records_start + 8, &u32::MAX.to_le_bytes());
+        assert_eq!(HspFile::open(&path, &protein_set).err().unwrap().kind(), ErrorKind::InvalidData);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_overflowing_sizes() {
+        let (path, protein_set) = save("overflow");
+        patch(&path, 28, &u64::MAX.to_le_bytes());
+        assert!(HspFile::open(&path, &protein_set).is_err());
+        patch(&path, 36, &(u64::MAX - 3).to_le_bytes());
+        assert!(HspFile::open(&path, &protein_set).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_truncated_file() {
+        let (path, protein_set) = save("truncated");
+        let contents = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &contents[..contents.len() - RECORD_SIZE]).unwrap();
+        assert!(HspFile::open(&path, &protein_set).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn extraction_parameters_must_match() {
+        let stored = HspParameters {
+            kmer_size: 20,
+            t_sim: Some(15),
+            t_hsp: Some(35),
+            t_count: None,
+            matrix: Some("PAM120".to_string()),
+            seeds: vec!["11011".to_string()],
+        };
+        assert!(stored.check_extraction(&stored.clone()).is_ok());
+
+        let requested = HspParameters { t_hsp: Some(30), ..stored.clone() };
+        let error = stored.check_extraction(&requested).unwrap_err().to_string();
+        assert!(error.contains("t_hsp=35"), "{}", error);
+        let requested = HspParameters { matrix: Some("BLOSUM62".to_string()), ..stored.clone() };
+        assert!(stored.check_extraction(&requested).is_err());
+        let requested = HspParameters { seeds: vec!["111".to_string()], ..stored.clone() };
+        assert!(stored.check_extraction(&requested).is_err());
+
+        // The parameters that are not stored cannot be checked
+        let unknown = HspParameters { kmer_size: 20, ..Default::default() };
+        assert!(unknown.check_extraction(&requested).is_ok());
+    }
+}
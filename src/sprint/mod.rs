@@ -2,19 +2,38 @@
 
 pub mod protein;
 pub mod proteinset;
+pub mod compression;
 pub mod constants;
+pub mod error;
 pub mod fileio;
+pub mod headers;
 pub mod extraction;
 pub mod seed;
 pub mod scoring;
 pub mod smer;
 pub mod hsp;
+pub mod hspfile;
 pub mod location;
 pub mod similarity;
 pub mod utils;
 pub mod processing;
 pub mod prediction;
 pub mod sites;
+pub mod substitution;
+pub mod sink;
+pub mod checkpoint;
+pub mod distributed;
+pub mod scorematrix;
+pub mod crossvalidation;
+pub mod evaluation;
+pub mod evolution;
+pub mod explanation;
+pub mod model;
+pub mod mutagenesis;
+pub mod negatives;
+pub mod normalization;
+pub mod sanitization;
+pub mod options;
 
 pub mod pymodules;
 
@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use rayon::prelude::*;
+
+use crate::sprint::distributed::Worker;
+use crate::sprint::error::SprintError;
+use crate::sprint::extraction::{extract_hsps_with_index, ExtractionSettings, SmerIndex};
+use crate::sprint::hsp::HSP;
+use crate::sprint::hspfile::HspSource;
+use crate::sprint::prediction::{build_hsp_table, fill_new_row, index_pairs, prepare_training_pairs, relevant_pairs, ScoringSettings};
+use crate::sprint::protein::Protein;
+use crate::sprint::proteinset::ProteinSet;
+use crate::sprint::seed::Seed;
+use crate::sprint::sites::{compute_contributions, ResidueWeighting};
+use crate::sprint::substitution::SubstitutionMatrix;
+
+/// Proteins, processed HSPs and training pairs kept in memory to score new
+/// sequences (e.g. peptides) repeatedly
+///
+/// The s-mers of the proteins and the HSP table are built once. The HSPs of
+/// new sequences are then extracted against the s-mers of the proteins only,
+/// and the scores are the ones `peptide-scorer` would give the sequences.
+pub struct SprintModel {
+    protein_set: ProteinSet,
+    hsps: HashSet<HSP>,
+    training_pairs: Vec<(String, String)>,
+    /// Training pairs of proteins of the set (indices)
+    pairs: Vec<(usize, usize)>,
+    pairs_by_protein: Vec<Vec<usize>>,
+    interactors: HashSet<usize>,
+    hsp_table: Vec<Vec<(usize, f32, f32, f32)>>,
+    smer_index: SmerIndex,
+    kmer_size: usize,
+    t_sim: i16,
+    t_hit: i16,
+    matrix: SubstitutionMatrix,
+    seeds: Vec<Seed>,
+}
+
+impl SprintModel {
+    /// Builds the model of the proteins, which extracts the HSPs of new
+    /// sequences with `settings`
+    pub fn new(
+        protein_set: ProteinSet,
+        hsps: &dyn HspSource,
+        training_pairs: Vec<(String, String)>,
+        settings: &ExtractionSettings,
+        verbose: bool,
+    ) -> Self {
+        let start = Instant::now();
+        if verbose {
+            println!("Model - Indexing the s-mers of {} proteins", protein_set.len());
+        }
+        let smer_index = SmerIndex::new(&protein_set, settings.seeds);
+
+        let mut model = SprintModel {
+            protein_set,
+            hsps: hsps.hsps().collect(),
+            training_pairs: vec![],
+            pairs: vec![],
+            pairs_by_protein: vec![],
+            interactors: HashSet::new(),
+            hsp_table: vec![],
+            smer_index,
+            kmer_size: settings.kmer_size,
+            t_sim: settings.t_sim,
+            t_hit: settings.t_hit,
+            matrix: settings.matrix.clone(),
+            seeds: settings.seeds.to_vec(),
+        };
+        if verbose {
+            println!("Model - Building the HSP table");
+        }
+        model.add_training_pairs(&training_pairs);
+
+        if verbose {
+            println!("Model - Built the model in {}...", start.elapsed().as_secs());
+        }
+        model
+    }
+
+    pub fn protein_set(&self) -> &ProteinSet {
+        &self.protein_set
+    }
+
+    pub fn training_pairs(&self) -> &[(String, String)] {
+        &self.training_pairs
+    }
+
+    /// Adds training pairs (the pairs of unknown proteins are ignored, as in
+    /// `score_interactions`)
+    ///
+    /// The HSP table is only rebuilt if the pairs involve new interactors.
+    pub fn add_training_pairs(&mut self, training_pairs: &[(String, String)]) {
+        self.training_pairs.extend(training_pairs.iter().cloned());
+        let (pairs, interactors) = prepare_training_pairs(&self.protein_set, &self.training_pairs, 0, 1, false);
+
+        if interactors != self.interactors || self.hsp_table.is_empty() {
+            self.hsp_table = build_hsp_table(&self.hsps, &self.protein_set, &interactors, self.kmer_size, &self.matrix);
+        }
+        self.pairs_by_protein = index_pairs(&pairs, self.protein_set.len());
+        self.pairs = pairs;
+        self.interactors = interactors;
+    }
+
+    /// Scores of every new sequence with every protein of the model (in the
+    /// order of the set)
+    pub fn score(&self, sequences: &[Protein]) -> Result<Vec<Vec<f32>>, SprintError> {
+        let (batch_set, new_hsps) = self.extract_new_hsps(sequences)?;
+        let new_table = build_hsp_table(&new_hsps, &batch_set, &self.interactors, self.kmer_size, &self.matrix);
+
+        let num_proteins = self.protein_set.len();
+        let scores = (num_proteins..batch_set.len())
+            .into_par_iter()
+            .map(|protein| {
+                // The entries of the new sequence in the rows of its partners
+                let mut entries: HashMap<usize, Vec<(usize, f32, f32, f32)>> = HashMap::new();
+                for hsp in new_table[protein].iter() {
+                    entries.entry(hsp.0).or_insert_with(|| {
+                        new_table[hsp.0].iter().filter(|entry| entry.0 == protein).cloned().collect()
+                    });
+                }
+
+                let mut row = vec![0f32; num_proteins];
+                let pairs = relevant_pairs(entries.keys().cloned(), &self.pairs_by_protein);
+                fill_new_row(&mut row, &self.pairs, &pairs, &self.hsp_table, &entries, self.kmer_size as f32);
+                row
+            })
+            .collect();
+
+        Ok(scores)
+    }
+
+    /// Contributions of the residues of a protein of the model to its scores
+    /// with every new sequence (see `sites::compute_contributions`)
+    pub fn contributions(
+        &self,
+        sequences: &[Protein],
+        target: &str,
+        weighting: ResidueWeighting,
+    ) -> Result<Vec<Vec<f32>>, SprintError> {
+        let target_index = self.protein_set.get_protein_by_name(target)?.index();
+
+        // Only the HSPs of the target and of the new sequences are needed
+        let (batch_set, mut hsps) = self.extract_new_hsps(sequences)?;
+        hsps.extend(
+            self.hsps
+                .iter()
+                .filter(|hsp| hsp.location(0).index() == target_index || hsp.location(1).index() == target_index)
+                .cloned(),
+        );
+
+        let mut contributions = compute_contributions(
+            &target.to_string(),
+            &batch_set,
+            &hsps,
+            &self.training_pairs,
+            &ScoringSettings { kmer_size: self.kmer_size, matrix: &self.matrix },
+            weighting,
+            Worker::single(false),
+        )?;
+        Ok((self.protein_set.len()..batch_set.len())
+            .map(|protein| contributions.remove(&protein).unwrap_or_default())
+            .collect())
+    }
+
+    /// The proteins of the model followed by the new sequences, and the HSPs
+    /// of the new sequences with the proteins
+    fn extract_new_hsps(&self, sequences: &[Protein]) -> Result<(ProteinSet, HashSet<HSP>), SprintError> {
+        // The training pairs are mapped by name
+        for sequence in sequences {
+            if self.protein_set.contains(&sequence.name()) {
+                return Err(SprintError::invalid_input(format!(
+                    "A new sequence has the name of a protein of the model: {}",
+                    sequence.name()
+                )));
+            }
+        }
+
+        let mut batch_set = self.protein_set.clone();
+        batch_set.add_new(sequences.to_vec(), true)?;
+
+        let settings = ExtractionSettings {
+            kmer_size: self.kmer_size,
+            t_sim: self.t_sim,
+            t_hit: self.t_hit,
+            matrix: &self.matrix,
+            seeds: &self.seeds,
+        };
+        let hsps = extract_hsps_with_index(&batch_set, &self.smer_index, &settings);
+        Ok((batch_set, hsps))
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use crate::sprint::constants::AMINO_ACIDS;
+use crate::sprint::distributed::Worker;
+use crate::sprint::error::SprintError;
+use crate::sprint::extraction::{extract_hsps, ExtractionSettings};
+use crate::sprint::hsp::HSP;
+use crate::sprint::hspfile::HspSource;
+use crate::sprint::location::Location;
+use crate::sprint::prediction::score_pairs;
+use crate::sprint::protein::Protein;
+use crate::sprint::proteinset::ProteinSet;
+use crate::sprint::utils::fnv1a;
+
+/// All the single substitutions of a sequence by the standard amino acids
+/// (position, residue)
+pub fn single_substitutions(sequence: &str) -> Vec<Vec<(usize, char)>> {
+    sequence
+        .chars()
+        .enumerate()
+        .flat_map(|(position, wild_type)| {
+            AMINO_ACIDS
+                .chars()
+                .filter(move |&residue| residue != wild_type.to_ascii_uppercase())
+                .map(move |residue| vec![(position, residue)])
+        })
+        .collect()
+}
+
+/// All the double substitutions of a sequence (at two different positions)
+pub fn double_substitutions(sequence: &str) -> Vec<Vec<(usize, char)>> {
+    let singles = single_substitutions(sequence);
+    let mut doubles = vec![];
+    for (i, first) in singles.iter().enumerate() {
+        for second in singles[i + 1..].iter() {
+            if second[0].0 != first[0].0 {
+                doubles.push(vec![first[0], second[0]]);
+            }
+        }
+    }
+    doubles
+}
+
+/// Name of a variant, in the usual mutation notation (e.g. `pep1:A5G,K7R`,
+/// with positions starting at 1)
+pub fn variant_name(peptide: &Protein, substitutions: &[(usize, char)]) -> String {
+    if substitutions.is_empty() {
+        return peptide.name();
+    }
+    let mutations: Vec<String> = substitutions
+        .iter()
+        .map(|&(position, residue)| format!("{}{}{}", peptide.sub(position, position), position + 1, residue))
+        .collect();
+    format!("{}:{}", peptide.name(), mutations.join(","))
+}
+
+/// Score deltas of substitutions as a position x amino acid matrix (in the
+/// order of `constants::AMINO_ACIDS`), the other entries being 0
+pub fn delta_matrix(peptide: &Protein, variants: &[Vec<(usize, char)>], deltas: &[f32]) -> Vec<Vec<f32>> {
+    let mut matrix = vec![vec![0f32; AMINO_ACIDS.len()]; peptide.len()];
+    for (substitutions, &delta) in variants.iter().zip(deltas.iter()) {
+        if let [(position, residue)] = substitutions[..] {
+            if let Some(column) = AMINO_ACIDS.find(residue) {
+                matrix[position][column] = delta;
+            }
+        }
+    }
+    matrix
+}
+
+/// Scores new peptides with some targets, extracting only the HSPs of the
+/// peptides
+///
+/// Only the proteins that can contribute to a score with a target are kept:
+/// the targets, the proteins sharing an HSP with a target (in `hsps`) and
+/// their training partners. The HSPs of the peptides are extracted (with
+/// `new_only`) against these proteins only, so that the scores are the ones
+/// `peptide-scorer` would give the peptides, without rebuilding the whole
+/// protein set for every batch of peptides.
+pub struct PeptideScorer<'a> {
+    proteins: ProteinSet,
+    /// Indices of the targets among `proteins`
+    targets: Vec<usize>,
+    /// HSPs of the targets (with the indices of `proteins`)
+    target_hsps: HashSet<HSP>,
+    training_pairs: &'a [(String, String)],
+    settings: ExtractionSettings<'a>,
+}
+
+impl<'a> PeptideScorer<'a> {
+    pub fn new(
+        protein_set: &ProteinSet,
+        hsps: &dyn HspSource,
+        training_pairs: &'a [(String, String)],
+        targets: &[String],
+        settings: ExtractionSettings<'a>,
+    ) -> Result<PeptideScorer<'a>, SprintError> {
+        let target_indices: Vec<usize> = targets
+            .iter()
+            .map(|target| Ok(protein_set.get_protein_by_name(target)?.index()))
+            .collect::<Result<_, SprintError>>()?;
+        let is_target: HashSet<usize> = target_indices.iter().cloned().collect();
+
+        // The HSPs of the targets and the proteins they involve
+        let target_hsps: Vec<HSP> = hsps
+            .hsps()
+            .filter(|hsp| is_target.contains(&hsp.location(0).index()) || is_target.contains(&hsp.location(1).index()))
+            .collect();
+        let mut similar: HashSet<usize> = target_hsps
+            .iter()
+            .flat_map(|hsp| [hsp.location(0).index(), hsp.location(1).index()])
+            .collect();
+        similar.extend(is_target.iter());
+
+        // The proteins whose HSPs with a peptide can contribute to its score
+        // with a target
+        let mut relevant = similar.clone();
+        for pair in training_pairs {
+            let (protein1, protein2) = match (protein_set.get_protein_by_name(&pair.0), protein_set.get_protein_by_name(&pair.1)) {
+                (Ok(protein1), Ok(protein2)) => (protein1.index(), protein2.index()),
+                _ => continue,
+            };
+            if similar.contains(&protein1) {
+                relevant.insert(protein2);
+            }
+            if similar.contains(&protein2) {
+                relevant.insert(protein1);
+            }
+        }
+
+        // Reduced protein set (in the order of the full set) and HSPs of the
+        // targets in this set
+        let mut relevant: Vec<usize> = relevant.into_iter().collect();
+        relevant.sort_unstable();
+        let reindex: HashMap<usize, usize> = relevant.iter().enumerate().map(|(new, &old)| (old, new)).collect();
+        let proteins = protein_set.subset(&relevant);
+        let target_hsps: HashSet<HSP> = target_hsps
+            .iter()
+            .map(|hsp| {
+                let location = |i: usize| Location::new(reindex[&hsp.location(i).index()], hsp.location(i).position());
+                HSP::new(location(0), location(1), hsp.len())
+            })
+            .collect();
+
+        Ok(PeptideScorer {
+            proteins,
+            targets: target_indices.iter().map(|target| reindex[target]).collect(),
+            target_hsps,
+            training_pairs,
+            settings,
+        })
+    }
+
+    /// Number of proteins the HSPs of the peptides are extracted against
+    pub fn len(&self) -> usize {
+        self.proteins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.proteins.is_empty()
+    }
+
+    /// Describes the scoring (proteins, targets, HSPs of the targets,
+    /// training pairs and extraction parameters), so that a checkpoint is
+    /// only resumed with the same scores
+    pub fn manifest(&self) -> String {
+        let (proteins, settings) = (&self.proteins, &self.settings);
+        let targets: Vec<String> = self.targets.iter().map(|&target| proteins.get_protein_by_id(target).name()).collect();
+        let training_pairs = fnv1a(
+            self.training_pairs
+                .iter()
+                .flat_map(|pair| format!("{}\t{}\n", pair.0, pair.1).into_bytes()),
+        );
+        [
+            format!("sequences={} {:016x}", proteins.len(), proteins.fingerprint(proteins.len())),
+            format!("targets={}", targets.join(",")),
+            format!("target_hsps={}", self.target_hsps.len()),
+            format!("training_pairs={} {:016x}", self.training_pairs.len(), training_pairs),
+            format!("kmer_size={}", settings.kmer_size),
+            format!("t_sim={}", settings.t_sim),
+            format!("t_hit={}", settings.t_hit),
+            format!("matrix={} {:016x}", settings.matrix.name(), settings.matrix.fingerprint()),
+            format!("seeds={}", settings.seeds.iter().map(|seed| seed.as_string()).collect::<Vec<String>>().join(",")),
+        ]
+        .join("\n")
+    }
+
+    /// Scores of every peptide with every target (in the order of the
+    /// targets), extracting the HSPs of `batch_size` peptides at a time
+    ///
+    /// The names of the peptides of a batch must be distinct, and distinct
+    /// from the names of the proteins (see `ProteinSet::add_new`).
+    pub fn score(&self, peptides: &[Protein], batch_size: usize) -> Result<Vec<Vec<f32>>, SprintError> {
+        let mut scores = vec![];
+        for batch in peptides.chunks(std::cmp::max(batch_size, 1)) {
+            let mut batch_set = self.proteins.clone();
+            batch_set.add_new(batch.to_vec(), true)?;
+
+            let mut batch_hsps = extract_hsps(&batch_set, &self.settings, Worker::single(false), true, true);
+            batch_hsps.extend(self.target_hsps.iter().cloned());
+
+            let queries: Vec<(usize, usize)> = (0..batch.len())
+                .flat_map(|i| self.targets.iter().map(move |&target| (self.proteins.len() + i, target)))
+                .collect();
+            let batch_scores = score_pairs(
+                &batch_set,
+                &batch_hsps,
+                self.training_pairs,
+                &queries,
+                &self.settings.scoring(),
+                Worker::single(false),
+            );
+            scores.extend(batch_scores.chunks(self.targets.len()).map(|chunk| chunk.to_vec()));
+        }
+        Ok(scores)
+    }
+}
+
+/// Scores variants of a peptide (substitutions of its residues) with the
+/// first target of a scorer
+///
+/// The variants are scored in batches of `batch_size` variants, so the
+/// scores are the ones `peptide-scorer` would give them.
+pub fn mutation_scan(
+    scorer: &PeptideScorer,
+    peptide: &Protein,
+    variants: &[Vec<(usize, char)>],
+    batch_size: usize,
+    verbose: bool,
+) -> Result<Vec<f32>, SprintError> {
+    if verbose {
+        println!(
+            "Mutation scan - Scoring {} variants of {} against {} proteins...",
+            variants.len(),
+            peptide.name(),
+            scorer.len()
+        );
+    }
+    let start = Instant::now();
+
+    let variants: Vec<Protein> = variants
+        .iter()
+        .enumerate()
+        .map(|(i, substitutions)| peptide.mutate(i, variant_name(peptide, substitutions), substitutions))
+        .collect::<Result<_, SprintError>>()?;
+    let scores = scorer.score(&variants, batch_size)?.into_iter().map(|scores| scores[0]).collect();
+
+    if verbose {
+        println!("Mutation scan - Scored the variants in {}...", start.elapsed().as_secs());
+    }
+
+    Ok(scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprint::prediction::score_interactions;
+    use crate::sprint::seed::default_seeds;
+    use crate::sprint::substitution::SubstitutionMatrix;
+
+    const SEQUENCES: [&str; 7] = [
+        "MKTAYIAKQRQISFVKSHFSRQLEERLGLIEVQAPILSRVGDGTQDNLSG",
+        "GSHMWDNPQRCTEYLGVAPWDNHKKFTCMAEQLRSGLNDTYVGHKPAEIW",
+        "MKTAYIAKQRQISFVKAHFSRQLEEKLGLVEVQAPILSRVGDGTQDNLSA",
+        "GSHMWDNPQRCTEYLGVSPWDNHKRFTCMAEQLRSGLNDTYVGHKPAEIF",
+        "PLNVTIFFGLLLAGSRWCEDGHYIQPTNMLKVELSSDGHAPYTCRQWETA",
+        "QQRSTWYNPLHGAAAEEDDKKLLMMVVFFWPLNVTIFFGLLLAGSRWCED",
+        "CHCHCHCHCHCHCHCHCHCHCHCHCHCHCHCHCHCHCHCHCHCHCHCHCH",
+    ];
+
+    const PEPTIDES: [&str; 3] = ["AYIAKQRQISFVKSHFSRQL", "WDNPQRCTEYLGVAPWDNHK", "LNVTIFFGLLLAGSRWCEDG"];
+
+    fn proteins(sequences: &[&str], prefix: &str) -> Vec<Protein> {
+        sequences
+            .iter()
+            .enumerate()
+            .map(|(i, sequence)| Protein::new(i, format!("{}{}", prefix, i), sequence.to_string(), false).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn peptide_scores_match_the_scores_of_the_full_set() {
+        let protein_set = ProteinSet::new(proteins(&SEQUENCES, "P")).unwrap();
+        let peptides = proteins(&PEPTIDES, "pep");
+        let training_pairs: Vec<(String, String)> = [("P0", "P1"), ("P4", "P5"), ("P2", "P5")]
+            .iter()
+            .map(|pair| (pair.0.to_string(), pair.1.to_string()))
+            .collect();
+        let targets = vec!["P3".to_string(), "P5".to_string()];
+        let matrix = SubstitutionMatrix::load("PAM120").unwrap();
+        let seeds = default_seeds();
+        let settings = ExtractionSettings { kmer_size: 12, t_sim: 15, t_hit: 35, matrix: &matrix, seeds: &seeds };
+        let hsps = extract_hsps(&protein_set, &settings, Worker::single(false), false, true);
+
+        let scorer = PeptideScorer::new(&protein_set, &hsps, &training_pairs, &targets, settings).unwrap();
+        assert!(scorer.len() < protein_set.len());
+        let scores = scorer.score(&peptides, 2).unwrap();
+
+        // The peptides added to the full set, as peptide-scorer does
+        let mut full_set = protein_set.clone();
+        full_set.add_new(peptides.clone(), true).unwrap();
+        let mut full_hsps = hsps.clone();
+        full_hsps.extend(extract_hsps(&full_set, &settings, Worker::single(false), true, true));
+        let expected = score_interactions(&full_set, &full_hsps, &training_pairs, &settings.scoring(), Worker::single(false));
+
+        let mut scored = 0;
+        for (i, peptide_scores) in scores.iter().enumerate() {
+            for (target, &score) in targets.iter().zip(peptide_scores.iter()) {
+                let target = full_set.get_protein_by_name(target).unwrap().index();
+                let expected = expected.get(protein_set.len() + i, target);
+                assert!((score - expected).abs() <= 1e-4 * expected.abs().max(1.0), "{} != {}", score, expected);
+                scored += (score > 0.0) as usize;
+            }
+        }
+        assert!(scored > 0);
+
+        // A mutation scan scores the variants with the first target
+        let variants = vec![vec![], vec![(0, 'G')]];
+        let scan = mutation_scan(&scorer, &peptides[0], &variants, 1, false).unwrap();
+        assert_eq!(scan[0], scores[0][0]);
+        assert_eq!(scan.len(), 2);
+    }
+}
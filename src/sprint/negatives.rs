@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+
+use crate::sprint::proteinset::ProteinSet;
+
+/// Number of random draws per requested negative pair before giving up (when
+/// almost all the pairs are positives)
+const MAX_ATTEMPTS_PER_PAIR: usize = 100;
+
+/// Samples random negative pairs: pairs of distinct proteins of the set that
+/// are not positive pairs
+///
+/// `ratio` is the number of negative pairs per positive pair. The positive
+/// pairs (e.g. from `load_pairs`) involving proteins that are not in the set
+/// are ignored, and, as in `load_pairs`, the order of the proteins of a pair
+/// does not matter. With `interactors_only`, only the proteins of the
+/// positive pairs are used. With `degree_preserving` (which implies
+/// `interactors_only`), every protein is drawn with a probability
+/// proportional to its number of positive pairs, so that the proteins have
+/// (on average) the same degree in the negative pairs as in the positive
+/// pairs. The same seed always gives the same pairs.
+pub fn sample_negatives(
+    protein_set: &ProteinSet,
+    positives: &[(String, String)],
+    ratio: f32,
+    interactors_only: bool,
+    degree_preserving: bool,
+    random_seed: u64,
+) -> Vec<(String, String)> {
+    let positives: Vec<(usize, usize)> = positives
+        .iter()
+        .filter_map(|pair| {
+            Some((
+                protein_set.get_protein_by_name(&pair.0).ok()?.index(),
+                protein_set.get_protein_by_name(&pair.1).ok()?.index(),
+            ))
+        })
+        .collect();
+
+    let count = (positives.len() as f32 * ratio).round() as usize;
+    let mut excluded: HashSet<(usize, usize)> = positives.iter().map(|pair| ordered(*pair)).collect();
+    let candidates = candidate_proteins(protein_set, &positives, interactors_only, degree_preserving);
+
+    sample_negative_indices(&candidates, &mut excluded, count, &mut StdRng::seed_from_u64(random_seed))
+        .into_iter()
+        .map(|pair| {
+            (
+                protein_set.get_protein_by_id(pair.0).name(),
+                protein_set.get_protein_by_id(pair.1).name(),
+            )
+        })
+        .collect()
+}
+
+/// Proteins from which the proteins of the negative pairs are drawn
+/// (uniformly): every protein appears once, or once per positive pair it is
+/// involved in if `degree_preserving`
+pub fn candidate_proteins(
+    protein_set: &ProteinSet,
+    positives: &[(usize, usize)],
+    interactors_only: bool,
+    degree_preserving: bool,
+) -> Vec<usize> {
+    if degree_preserving {
+        return positives.iter().flat_map(|pair| [pair.0, pair.1]).collect();
+    }
+
+    if interactors_only {
+        let mut interactors: Vec<usize> = positives.iter().flat_map(|pair| [pair.0, pair.1]).collect();
+        interactors.sort_unstable();
+        interactors.dedup();
+        return interactors;
+    }
+
+    protein_set.iter().map(|protein| protein.index()).collect()
+}
+
+/// Draws `count` distinct pairs of distinct proteins from the candidates
+/// that are not in `excluded`, and adds them to it
+pub fn sample_negative_indices(
+    candidates: &[usize],
+    excluded: &mut HashSet<(usize, usize)>,
+    count: usize,
+    rng: &mut StdRng,
+) -> Vec<(usize, usize)> {
+    let mut negatives = vec![];
+    if candidates.is_empty() {
+        return negatives;
+    }
+
+    let mut attempts = 0;
+    while negatives.len() < count && attempts < count * MAX_ATTEMPTS_PER_PAIR {
+        attempts += 1;
+        let pair = (
+            candidates[rng.random_range(0..candidates.len())],
+            candidates[rng.random_range(0..candidates.len())],
+        );
+        if pair.0 != pair.1 && excluded.insert(ordered(pair)) {
+            negatives.push(pair);
+        }
+    }
+
+    if negatives.len() < count {
+        println!("WARNING: Only {} of the {} requested negative pairs could be sampled.", negatives.len(), count);
+    }
+    negatives
+}
+
+fn ordered(pair: (usize, usize)) -> (usize, usize) {
+    (std::cmp::min(pair.0, pair.1), std::cmp::max(pair.0, pair.1))
+}
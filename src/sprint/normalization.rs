@@ -0,0 +1,472 @@
+use std::fs::remove_file;
+use std::io::Error;
+
+use rayon::prelude::*;
+
+use crate::sprint::error::SprintError;
+use crate::sprint::evaluation::label_pairs;
+use crate::sprint::proteinset::ProteinSet;
+use crate::sprint::scorematrix::ScoreMatrix;
+
+/// Number of rows of scores sorted at a time by the rank normalization
+const RANK_ROWS_PER_BLOCK: usize = 64;
+
+/// Post-processing of the scores, so that they can be compared across
+/// proteins (e.g. proteins with many HSPs or training partners otherwise
+/// dominate the best scoring pairs)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Normalization {
+    None,
+    /// Average of the z-scores of the pair among the scores of each protein
+    ZScore,
+    /// Average of the percentile ranks of the pair among the scores of each
+    /// protein (requires a second score matrix, memory-mapped like the scores)
+    Rank,
+    /// Score divided by `sqrt((1 + d1) * (1 + d2))`, where `d1` and `d2` are
+    /// the numbers of training pairs of the proteins
+    Degree,
+}
+
+impl Normalization {
+    pub fn from_name(name: &str) -> Result<Self, SprintError> {
+        match name.to_lowercase().as_str() {
+            "none" => Ok(Normalization::None),
+            "zscore" => Ok(Normalization::ZScore),
+            "rank" => Ok(Normalization::Rank),
+            "degree" => Ok(Normalization::Degree),
+            _ => Err(SprintError::invalid_input(format!(
+                "Unknown normalization {} (none, zscore, rank or degree).",
+                name
+            ))),
+        }
+    }
+}
+
+/// Normalizes the scores in place (the scores stay symmetric)
+///
+/// `degrees` is the number of training pairs of every protein, only used by
+/// `Normalization::Degree`.
+pub fn normalize(scores: &mut ScoreMatrix, normalization: Normalization, degrees: &[usize]) -> Result<(), Error> {
+    match normalization {
+        Normalization::None => {}
+        Normalization::ZScore => normalize_zscore(scores),
+        Normalization::Rank => normalize_rank(scores)?,
+        Normalization::Degree => normalize_degree(scores, degrees),
+    }
+    Ok(())
+}
+
+/// Number of training pairs of every protein (a homodimer counts once, and
+/// the pairs of unknown proteins are ignored)
+pub fn training_degrees(protein_set: &ProteinSet, training_pairs: &[(String, String)]) -> Vec<usize> {
+    let mut degrees = vec![0; protein_set.len()];
+    for pair in training_pairs {
+        let (protein1, protein2) = match (protein_set.get_protein_by_name(&pair.0), protein_set.get_protein_by_name(&pair.1)) {
+            (Ok(protein1), Ok(protein2)) => (protein1.index(), protein2.index()),
+            _ => continue,
+        };
+        degrees[protein1] += 1;
+        if protein2 != protein1 {
+            degrees[protein2] += 1;
+        }
+    }
+    degrees
+}
+
+/// Normalizes the scores, then, if a calibration method is given, replaces
+/// them by probabilities fitted on the positive and negative pairs
+pub fn postprocess_scores(
+    scores: &mut ScoreMatrix,
+    protein_set: &ProteinSet,
+    training_pairs: &[(String, String)],
+    normalization: Normalization,
+    calibration: Option<&str>,
+    positives: &[(String, String)],
+    negatives: &[(String, String)],
+) -> Result<(), SprintError> {
+    normalize(scores, normalization, &training_degrees(protein_set, training_pairs))?;
+
+    if let Some(method) = calibration {
+        let (labeled_scores, _) = label_pairs(positives, negatives, |protein1, protein2| {
+            let protein1 = protein_set.get_protein_by_name(protein1).ok()?;
+            let protein2 = protein_set.get_protein_by_name(protein2).ok()?;
+            Some(scores.get(protein1.index(), protein2.index()))
+        });
+        Calibration::fit(method, &labeled_scores)?.calibrate(scores);
+    }
+    Ok(())
+}
+
+fn normalize_zscore(scores: &mut ScoreMatrix) {
+    let size = scores.size();
+
+    // Sums of the scores and of their squares for every protein (the full
+    // row of a protein holds its scores with every protein)
+    let mut sums = vec![0f64; size];
+    let mut squares = vec![0f64; size];
+    for i in 0..size {
+        for (j, &score) in scores.row(i).iter().enumerate() {
+            let score = score as f64;
+            sums[i] += score;
+            squares[i] += score * score;
+            if j != i {
+                sums[j] += score;
+                squares[j] += score * score;
+            }
+        }
+    }
+
+    let statistics: Vec<(f32, f32)> = (0..size)
+        .map(|i| {
+            let mean = sums[i] / size as f64;
+            let variance = (squares[i] / size as f64 - mean * mean).max(0f64);
+            (mean as f32, variance.sqrt() as f32)
+        })
+        .collect();
+
+    let zscore = |score: f32, (mean, deviation): (f32, f32)| match deviation > 0f32 {
+        true => (score - mean) / deviation,
+        false => 0f32,
+    };
+
+    scores.rows_mut().into_par_iter().enumerate().for_each(|(i, row)| {
+        for (j, score) in row.iter_mut().enumerate() {
+            *score = (zscore(*score, statistics[i]) + zscore(*score, statistics[j])) / 2f32;
+        }
+    });
+}
+
+/// Replaces the scores by their average percentile rank among the scores of
+/// both proteins
+///
+/// The full row of every protein is sorted in turn (a block of rows at a
+/// time), and the half of the percentile of every score is added to a second
+/// score matrix, memory-mapped next to the scores if they are memory-mapped.
+fn normalize_rank(scores: &mut ScoreMatrix) -> Result<(), Error> {
+    let size = scores.size();
+    let ranks_filename = scores.filename().map(|filename| format!("{}.ranks", filename));
+    let mut ranks = ScoreMatrix::allocate(size, ranks_filename.as_deref())?;
+
+    // Fraction of the scores of a protein below a score (ties count half)
+    let percentile = |sorted: &[f32], score: f32| {
+        let below = sorted.partition_point(|&value| value < score);
+        let equal = sorted.partition_point(|&value| value <= score) - below;
+        (below as f32 + equal as f32 / 2f32) / sorted.len() as f32
+    };
+
+    let proteins: Vec<usize> = (0..size).collect();
+    for block in proteins.chunks(RANK_ROWS_PER_BLOCK) {
+        let percentiles: Vec<Vec<f32>> = block
+            .par_iter()
+            .map(|&i| {
+                let row: Vec<f32> = (0..size).map(|j| scores.get(i, j)).collect();
+                let mut sorted = row.clone();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                row.iter().map(|&score| percentile(&sorted, score)).collect()
+            })
+            .collect();
+
+        // Halving is exact, so the sum of the halves is the average of the
+        // two percentiles
+        for (&i, percentiles) in block.iter().zip(percentiles.iter()) {
+            for (j, &percentile) in percentiles.iter().enumerate() {
+                ranks.add(i, j, if i == j { percentile } else { percentile / 2f32 });
+            }
+        }
+    }
+
+    scores.values_mut().copy_from_slice(ranks.values());
+    drop(ranks);
+    if let Some(filename) = ranks_filename {
+        remove_file(filename)?;
+    }
+    Ok(())
+}
+
+fn normalize_degree(scores: &mut ScoreMatrix, degrees: &[usize]) {
+    scores.rows_mut().into_par_iter().enumerate().for_each(|(i, row)| {
+        for (j, score) in row.iter_mut().enumerate() {
+            *score /= (((1 + degrees[i]) * (1 + degrees[j])) as f32).sqrt();
+        }
+    });
+}
+
+/// Maps scores to probabilities of interaction, fitted on labeled pairs
+pub enum Calibration {
+    /// Logistic function `1 / (1 + exp(a * score + b))` (Platt scaling)
+    Platt { a: f64, b: f64 },
+    /// Non-decreasing function fitted by isotonic regression, interpolated
+    /// linearly between the (mean) scores of its steps
+    Isotonic { scores: Vec<f32>, probabilities: Vec<f32> },
+}
+
+impl Calibration {
+    /// Fits a calibration (`platt` or `isotonic`) to scores given with
+    /// their label (true for the positive pairs)
+    pub fn fit(method: &str, labeled_scores: &[(f32, bool)]) -> Result<Self, SprintError> {
+        if !labeled_scores.iter().any(|pair| pair.1) || labeled_scores.iter().all(|pair| pair.1) {
+            return Err(SprintError::invalid_input(
+                "Calibrating requires both positive and negative pairs with a score.",
+            ));
+        }
+
+        match method.to_lowercase().as_str() {
+            "platt" => Ok(fit_platt(labeled_scores)),
+            "isotonic" => Ok(fit_isotonic(labeled_scores)),
+            _ => Err(SprintError::invalid_input(format!(
+                "Unknown calibration {} (platt or isotonic).",
+                method
+            ))),
+        }
+    }
+
+    pub fn probability(&self, score: f32) -> f32 {
+        match self {
+            Calibration::Platt { a, b } => (1f64 / (1f64 + (a * score as f64 + b).exp())) as f32,
+            Calibration::Isotonic { scores, probabilities } => {
+                let next = scores.partition_point(|&value| value <= score);
+                if next == 0 {
+                    probabilities[0]
+                } else if next == scores.len() {
+                    probabilities[scores.len() - 1]
+                } else {
+                    let fraction = (score - scores[next - 1]) / (scores[next] - scores[next - 1]);
+                    probabilities[next - 1] + fraction * (probabilities[next] - probabilities[next - 1])
+                }
+            }
+        }
+    }
+
+    /// Replaces the scores by probabilities
+    pub fn calibrate(&self, scores: &mut ScoreMatrix) {
+        scores.values_mut().par_iter_mut().for_each(|score| *score = self.probability(*score));
+    }
+}
+
+/// Platt scaling, fitted by Newton's method with the regularized targets of
+/// Lin, Lin and Weng (2007)
+fn fit_platt(labeled_scores: &[(f32, bool)]) -> Calibration {
+    let positives = labeled_scores.iter().filter(|pair| pair.1).count() as f64;
+    let negatives = labeled_scores.len() as f64 - positives;
+    let high_target = (positives + 1f64) / (positives + 2f64);
+    let low_target = 1f64 / (negatives + 2f64);
+    let data: Vec<(f64, f64)> = labeled_scores
+        .iter()
+        .map(|&(score, positive)| (score as f64, if positive { high_target } else { low_target }))
+        .collect();
+
+    let objective = |a: f64, b: f64| -> f64 {
+        data.iter()
+            .map(|&(score, target)| {
+                let f = a * score + b;
+                // Numerically stable form of the cross-entropy
+                match f >= 0f64 {
+                    true => target * f + (1f64 + (-f).exp()).ln(),
+                    false => (target - 1f64) * f + (1f64 + f.exp()).ln(),
+                }
+            })
+            .sum()
+    };
+
+    let mut a = 0f64;
+    let mut b = ((negatives + 1f64) / (positives + 1f64)).ln();
+    let mut value = objective(a, b);
+
+    for _ in 0..100 {
+        // Gradient and Hessian (with a small ridge to keep it invertible)
+        let (mut h11, mut h22, mut h21, mut g1, mut g2) = (1e-12, 1e-12, 0f64, 0f64, 0f64);
+        for &(score, target) in data.iter() {
+            let f = a * score + b;
+            let (p, q) = match f >= 0f64 {
+                true => ((-f).exp() / (1f64 + (-f).exp()), 1f64 / (1f64 + (-f).exp())),
+                false => (1f64 / (1f64 + f.exp()), f.exp() / (1f64 + f.exp())),
+            };
+            let d2 = p * q;
+            h11 += score * score * d2;
+            h22 += d2;
+            h21 += score * d2;
+            let d1 = target - p;
+            g1 += score * d1;
+            g2 += d1;
+        }
+        if g1.abs() < 1e-5 && g2.abs() < 1e-5 {
+            break;
+        }
+
+        let determinant = h11 * h22 - h21 * h21;
+        let da = -(h22 * g1 - h21 * g2) / determinant;
+        let db = -(-h21 * g1 + h11 * g2) / determinant;
+        let gd = g1 * da + g2 * db;
+
+        // Backtracking line search
+        let mut step = 1f64;
+        while step >= 1e-10 {
+            let new_value = objective(a + step * da, b + step * db);
+            if new_value < value + 1e-4 * step * gd {
+                a += step * da;
+                b += step * db;
+                value = new_value;
+                break;
+            }
+            step /= 2f64;
+        }
+        if step < 1e-10 {
+            break;
+        }
+    }
+
+    Calibration::Platt { a, b }
+}
+
+/// Isotonic regression by pool adjacent violators
+fn fit_isotonic(labeled_scores: &[(f32, bool)]) -> Calibration {
+    let mut sorted = labeled_scores.to_vec();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // Blocks of (sum of the scores, sum of the labels, number of pairs),
+    // pairs with the same score always being in the same block
+    let mut blocks: Vec<(f64, f64, usize)> = vec![];
+    let mut previous_score = None;
+    for &(score, positive) in sorted.iter() {
+        let label = positive as u8 as f64;
+        match blocks.last_mut() {
+            Some(block) if previous_score == Some(score) => {
+                block.0 += score as f64;
+                block.1 += label;
+                block.2 += 1;
+            }
+            _ => blocks.push((score as f64, label, 1)),
+        }
+        previous_score = Some(score);
+
+        while blocks.len() > 1 {
+            let last = blocks[blocks.len() - 1];
+            let before = blocks[blocks.len() - 2];
+            if before.1 / before.2 as f64 <= last.1 / last.2 as f64 {
+                break;
+            }
+            blocks.pop();
+            let merged = blocks.last_mut().unwrap();
+            merged.0 += last.0;
+            merged.1 += last.1;
+            merged.2 += last.2;
+        }
+    }
+
+    Calibration::Isotonic {
+        scores: blocks.iter().map(|block| (block.0 / block.2 as f64) as f32).collect(),
+        probabilities: blocks.iter().map(|block| (block.1 / block.2 as f64) as f32).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Symmetric scores with ties, from a linear congruential generator
+    fn scores(size: usize, filename: Option<&str>) -> ScoreMatrix {
+        let mut scores = ScoreMatrix::allocate(size, filename).unwrap();
+        let mut state: u64 = 7;
+        for value in scores.values_mut() {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            *value = ((state >> 33) % 20) as f32 / 4f32;
+        }
+        scores
+    }
+
+    /// Rank normalization from the sorted rows of all the proteins
+    fn naive_ranks(scores: &ScoreMatrix) -> Vec<f32> {
+        let size = scores.size();
+        let sorted_rows: Vec<Vec<f32>> = (0..size)
+            .map(|i| {
+                let mut row: Vec<f32> = (0..size).map(|j| scores.get(i, j)).collect();
+                row.sort_by(|a, b| a.total_cmp(b));
+                row
+            })
+            .collect();
+        let percentile = |i: usize, score: f32| {
+            let below = sorted_rows[i].iter().filter(|&&value| value < score).count();
+            let equal = sorted_rows[i].iter().filter(|&&value| value == score).count();
+            (below as f32 + equal as f32 / 2f32) / size as f32
+        };
+        (0..size)
+            .flat_map(|i| (0..=i).map(move |j| (i, j)))
+            .map(|(i, j)| (percentile(i, scores.get(i, j)) + percentile(j, scores.get(i, j))) / 2f32)
+            .collect()
+    }
+
+    #[test]
+    fn rank_normalization_matches_the_percentiles() {
+        let size = RANK_ROWS_PER_BLOCK + 37;
+        let mut scores = scores(size, None);
+        let expected = naive_ranks(&scores);
+        normalize(&mut scores, Normalization::Rank, &[]).unwrap();
+        assert_eq!(scores.values(), &expected[..]);
+    }
+
+    #[test]
+    fn rank_normalization_of_mapped_scores() {
+        let filename = std::env::temp_dir().join(format!("sprint_normalization_ranks_{}", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        let mut scores = scores(20, Some(filename));
+        let expected = naive_ranks(&scores);
+        normalize(&mut scores, Normalization::Rank, &[]).unwrap();
+        assert_eq!(scores.values(), &expected[..]);
+
+        // The ranks are accumulated in a temporary file next to the scores
+        assert!(!std::path::Path::new(&format!("{}.ranks", filename)).exists());
+        drop(scores);
+        remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn platt_scaling_fits_the_labels() {
+        let labeled_scores: Vec<(f32, bool)> = (0..40).map(|i| (i as f32 / 4f32, i % 4 != 0 && i >= 15)).collect();
+        let calibration = Calibration::fit("platt", &labeled_scores).unwrap();
+        match calibration {
+            Calibration::Platt { a, .. } => assert!(a < 0f64),
+            _ => panic!("not a Platt scaling"),
+        }
+
+        // The gradient of the cross-entropy with the regularized targets
+        // vanishes at the fit
+        let positives = labeled_scores.iter().filter(|pair| pair.1).count() as f64;
+        let negatives = labeled_scores.len() as f64 - positives;
+        let (mut g1, mut g2) = (0f64, 0f64);
+        for &(score, positive) in labeled_scores.iter() {
+            let target = if positive { (positives + 1f64) / (positives + 2f64) } else { 1f64 / (negatives + 2f64) };
+            let probability = calibration.probability(score) as f64;
+            g1 += score as f64 * (target - probability);
+            g2 += target - probability;
+        }
+        assert!(g1.abs() < 1e-3 && g2.abs() < 1e-3, "{} {}", g1, g2);
+
+        let probabilities: Vec<f32> = [0f32, 5f32, 10f32].iter().map(|&score| calibration.probability(score)).collect();
+        assert!(probabilities[0] < 0.1 && probabilities[1] < probabilities[2] && probabilities[2] > 0.9);
+    }
+
+    #[test]
+    fn isotonic_regression_pools_the_violators() {
+        let labeled_scores = [(1.0, false), (2.0, true), (3.0, false), (4.0, true), (4.0, true), (5.0, false), (6.0, true)];
+        let calibration = Calibration::fit("isotonic", &labeled_scores).unwrap();
+        match &calibration {
+            Calibration::Isotonic { scores, probabilities } => {
+                assert_eq!(scores, &vec![1.0, 2.5, 13.0 / 3.0, 6.0]);
+                assert_eq!(probabilities, &vec![0.0, 0.5, 2.0 / 3.0, 1.0]);
+            }
+            _ => panic!("not an isotonic regression"),
+        }
+
+        // Constant outside the scores, linear between the steps
+        assert_eq!(calibration.probability(0.0), 0.0);
+        assert_eq!(calibration.probability(7.0), 1.0);
+        assert!((calibration.probability(1.75) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn calibration_requires_both_labels() {
+        assert!(Calibration::fit("platt", &[(1.0, true), (2.0, true)]).is_err());
+        assert!(Calibration::fit("isotonic", &[]).is_err());
+        assert!(Calibration::fit("spline", &[(1.0, true), (2.0, false)]).is_err());
+    }
+}
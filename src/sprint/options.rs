@@ -0,0 +1,125 @@
+use clap::Args;
+
+use crate::sprint::error::SprintError;
+use crate::sprint::extraction::ExtractionSettings;
+use crate::sprint::fileio::load_fasta;
+use crate::sprint::headers::HeaderFormat;
+use crate::sprint::prediction::ScoringSettings;
+use crate::sprint::protein::Protein;
+use crate::sprint::proteinset::{DuplicatePolicy, ProteinSet};
+use crate::sprint::sanitization::{SequencePolicy, ValidationReport};
+use crate::sprint::seed::{select_seeds, Seed};
+use crate::sprint::substitution::SubstitutionMatrix;
+
+// Options of the binaries reading sequences (flattened into their arguments,
+// so this is not a doc comment: clap would use it as their description)
+#[derive(Args)]
+pub struct SequenceOptions {
+    /// Handling of the non-standard residues, as comma-separated class=policy
+    /// entries (classes: ambiguous, unknown, rare, invalid or all; policies:
+    /// substitute, reject, skip, mask or average)
+    #[clap(value_parser, long = "residues", default_value = "")]
+    pub residues: String,
+
+    /// What to do with FASTA records sharing an ID: error or suffix (rename
+    /// them <id>_2, <id>_3, ...)
+    #[clap(value_parser = ["error", "suffix"], long = "duplicates", default_value = "error")]
+    pub duplicates: String,
+
+    /// Keep only the first of the proteins with identical sequences (the
+    /// others are aliases of it, and their scores are the ones of it)
+    #[clap(value_parser, long = "collapse_identical")]
+    pub collapse_identical: bool,
+
+    /// How the protein IDs are taken from the FASTA headers: id (first
+    /// word), accession or entry_name (UniProt), gene (GN= field) or
+    /// regex:<pattern> (first group of the pattern)
+    #[clap(value_parser, long = "headers", default_value = "id")]
+    pub headers: String,
+}
+
+impl SequenceOptions {
+    pub fn policy(&self) -> Result<SequencePolicy, SprintError> {
+        SequencePolicy::parse(&self.residues)
+    }
+
+    pub fn header_format(&self) -> Result<HeaderFormat, SprintError> {
+        HeaderFormat::from_name(&self.headers)
+    }
+
+    /// Loads a protein set from a FASTA file
+    pub fn load(&self, filepath: &str) -> Result<(ProteinSet, ValidationReport), SprintError> {
+        let duplicates = DuplicatePolicy::from_name(&self.duplicates)?;
+        ProteinSet::from_file(filepath, &self.policy()?, &self.header_format()?, duplicates, self.collapse_identical)
+    }
+
+    /// Loads other sequences (e.g. peptides) from a FASTA file
+    pub fn load_fasta(&self, filepath: &str, new: bool) -> Result<(Vec<Protein>, ValidationReport), SprintError> {
+        load_fasta(filepath, new, &self.policy()?, &self.header_format()?)
+    }
+}
+
+// Options of the binaries scoring with the HSPs (flattened into their
+// arguments, so this is not a doc comment either)
+#[derive(Args)]
+pub struct ScoringOptions {
+    #[clap(value_parser, short = 'k', long = "kmer_size", default_value = "20")]
+    pub kmer_size: usize,
+
+    /// Substitution matrix: PAM120, PAM250, BLOSUM45, BLOSUM62 or the path of
+    /// a matrix file
+    #[clap(value_parser, long = "matrix", default_value = "PAM120")]
+    pub matrix: String,
+}
+
+impl ScoringOptions {
+    pub fn matrix(&self) -> Result<SubstitutionMatrix, SprintError> {
+        SubstitutionMatrix::load(&self.matrix)
+    }
+
+    pub fn settings<'a>(&self, matrix: &'a SubstitutionMatrix) -> ScoringSettings<'a> {
+        ScoringSettings { kmer_size: self.kmer_size, matrix }
+    }
+}
+
+// Options of the binaries extracting HSPs (flattened into their arguments)
+#[derive(Args)]
+pub struct ExtractionOptions {
+    #[clap(flatten)]
+    pub scoring: ScoringOptions,
+
+    #[clap(value_parser, long = "t_sim", default_value = "15")]
+    pub t_sim: i16,
+
+    #[clap(value_parser, long = "t_hsp", default_value = "35")]
+    pub t_hsp: i16,
+
+    /// Spaced seeds, comma-separated (e.g. 11**1*1,1*1**11)
+    #[clap(value_parser, long = "seeds", conflicts_with = "seed-file")]
+    pub seeds: Option<String>,
+
+    /// File of spaced seeds, one per line
+    #[clap(value_parser, long = "seed_file")]
+    pub seed_file: Option<String>,
+}
+
+impl ExtractionOptions {
+    pub fn matrix(&self) -> Result<SubstitutionMatrix, SprintError> {
+        self.scoring.matrix()
+    }
+
+    /// The given seeds, or else the default ones
+    pub fn seeds(&self) -> Result<Vec<Seed>, SprintError> {
+        select_seeds(&self.seeds, &self.seed_file)
+    }
+
+    pub fn settings<'a>(&self, matrix: &'a SubstitutionMatrix, seeds: &'a [Seed]) -> ExtractionSettings<'a> {
+        ExtractionSettings {
+            kmer_size: self.scoring.kmer_size,
+            t_sim: self.t_sim,
+            t_hit: self.t_hsp,
+            matrix,
+            seeds,
+        }
+    }
+}
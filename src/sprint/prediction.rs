@@ -1,44 +1,256 @@
-use crate::sprint::hsp::HSP;
+use crate::sprint::checkpoint::Checkpoint;
+use crate::sprint::distributed::Worker;
+use crate::sprint::error::SprintError;
+use crate::sprint::hspfile::HspSource;
 use crate::sprint::proteinset::ProteinSet;
 use crate::sprint::scoring::score_hsp;
-use ndarray::{Array2, Axis};
+use crate::sprint::substitution::SubstitutionMatrix;
+use crate::sprint::scorematrix::ScoreMatrix;
 use rayon::prelude::*;
-use std::cell::UnsafeCell;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::ops::Range;
 use std::time::Instant;
 
-pub struct PredictionMatrix {
-    pub scores: UnsafeCell<Array2<f32>>,
+use crate::sprint::utils::fnv1a;
+
+/// Prefix of the checkpoint entries holding the rows of scores changed by
+/// every chunk of training pairs
+const SCORES_PREFIX: &str = "scores_chunk_";
+
+/// Parameters of the scoring of pairs of proteins with their HSPs
+#[derive(Clone, Copy)]
+pub struct ScoringSettings<'a> {
+    pub kmer_size: usize,
+    pub matrix: &'a SubstitutionMatrix,
+}
+
+pub fn score_interactions(
+    protein_set: &ProteinSet,
+    hsps: &dyn HspSource,
+    training_pairs: &Vec<(String, String)>,
+    settings: &ScoringSettings,
+    worker: Worker,
+) -> ScoreMatrix {
+    let scores = ScoreMatrix::new(protein_set.len());
+    score_interactions_into(protein_set, hsps, training_pairs, settings, worker, scores, None)
+        .unwrap() // Cannot fail without a checkpoint
+}
+
+/// Describes a scoring run, so that a checkpoint is only resumed by the same
+/// run (the HSPs should be described by the caller)
+pub fn scoring_manifest(
+    protein_set: &ProteinSet,
+    training_pairs: &[(String, String)],
+    settings: &ScoringSettings,
+    worker: Worker,
+    pairs_per_checkpoint: usize,
+) -> String {
+    let pairs = fnv1a(training_pairs.iter().flat_map(|pair| {
+        format!("{}\t{}\n", pair.0, pair.1).into_bytes()
+    }));
+
+    [
+        format!("sequences={} {:016x}", protein_set.len(), protein_set.fingerprint(protein_set.len())),
+        format!("training_pairs={} {:016x}", training_pairs.len(), pairs),
+        format!("kmer_size={}", settings.kmer_size),
+        format!("matrix={} {:016x}", settings.matrix.name(), settings.matrix.fingerprint()),
+        format!("process={}/{}", worker.rank, worker.world_size),
+        format!("pairs_per_checkpoint={}", pairs_per_checkpoint),
+    ]
+    .join("\n")
+}
+
+/// Scores the interactions like `score_interactions`, adding the scores to
+/// a given score matrix (e.g. stored in a memory-mapped file)
+///
+/// If a checkpoint is given with a number of training pairs, the rows of
+/// scores changed by every chunk of this number of pairs are saved to the
+/// checkpoint, and the scoring resumes from the scores of the last saved
+/// chunk. Only the changed rows are written (streamed from the matrix), so a
+/// chunk touching few proteins is saved quickly, but the checkpoint may grow
+/// larger than the matrix.
+pub fn score_interactions_into(
+    protein_set: &ProteinSet,
+    hsps: &dyn HspSource,
+    training_pairs: &Vec<(String, String)>,
+    settings: &ScoringSettings,
+    worker: Worker,
+    scores: ScoreMatrix,
+    mut checkpoint: Option<(&mut Checkpoint, usize)>,
+) -> Result<ScoreMatrix, SprintError> {
+    let (kmer_size, matrix) = (settings.kmer_size, settings.matrix);
+    let (process_rank, world_size, verbose) = (worker.rank, worker.world_size, worker.verbose);
+    let (training_pairs_to_process, interactors) =
+        prepare_training_pairs(protein_set, training_pairs, process_rank, world_size, verbose);
+
+    if verbose {
+        println!("Process {} -: Building the HSP table", process_rank);
+    }
+    let hsp_table = build_hsp_table(hsps, &protein_set, &interactors, kmer_size, matrix);
+
+    if verbose {
+        println!("Process {} - Initializing the score matrix.", process_rank);
+    }
+    let mut scores = scores;
+
+    // Resume from the scores of the last completed chunk of training pairs
+    let chunk_size = match &checkpoint {
+        Some((_, pairs_per_checkpoint)) => std::cmp::max(*pairs_per_checkpoint, 1),
+        None => std::cmp::max(training_pairs_to_process.len(), 1),
+    };
+    let mut completed_chunks = 0;
+    if let Some((checkpoint, _)) = &checkpoint {
+        while let Some(mut reader) = checkpoint.reader(&scores_key(completed_chunks))? {
+            load_rows(&mut reader, &mut scores)?;
+            completed_chunks += 1;
+        }
+        if verbose && completed_chunks > 0 {
+            println!("Process {} - Resuming after {} chunks of training pairs.", process_rank, completed_chunks);
+        }
+    }
+
+    if verbose {
+        println!("Process {} - Scoring the interactions...", process_rank);
+    }
+    let start = Instant::now();
+
+    for (chunk_index, chunk) in training_pairs_to_process.chunks(chunk_size).enumerate().skip(completed_chunks) {
+        // Every row of the matrix is filled by a single task, so the scores
+        // do not depend on the number of threads
+        let pairs_by_protein = index_pairs(chunk, protein_set.len());
+        let changed: Vec<bool> = scores.rows_mut().into_par_iter().enumerate().map(|(protein, row)| {
+            let pairs = relevant_pairs(hsp_table[protein].iter().map(|hsp| hsp.0), &pairs_by_protein);
+            fill_row(protein, row, chunk, &pairs, &hsp_table, kmer_size as f32);
+            !pairs.is_empty()
+        }).collect();
+
+        if let Some((checkpoint, _)) = &mut checkpoint {
+            checkpoint.save_with(&scores_key(chunk_index), |writer| save_rows(writer, &scores, &changed))?;
+        }
+    }
+
+    if verbose {
+        println!(
+            "Process {} - Scored the interactions in {}...",
+            process_rank,
+            start.elapsed().as_secs()
+        );
+    }
+
+    Ok(scores)
+}
+
+fn scores_key(chunk_index: usize) -> String {
+    format!("{}{}", SCORES_PREFIX, chunk_index)
+}
+
+/// Writes some rows of scores, each one as its index (u64) followed by its
+/// scores (f32)
+fn save_rows(writer: &mut dyn Write, scores: &ScoreMatrix, rows: &[bool]) -> Result<(), std::io::Error> {
+    for protein in (0..scores.size()).filter(|&protein| rows[protein]) {
+        writer.write_all(&(protein as u64).to_le_bytes())?;
+        for score in scores.row(protein) {
+            writer.write_all(&score.to_le_bytes())?;
+        }
+    }
+    Ok(())
 }
 
-impl PredictionMatrix {
-    pub fn new(protein_set_size: usize) -> PredictionMatrix {
-        PredictionMatrix {
-            scores: UnsafeCell::new(Array2::zeros((protein_set_size, protein_set_size))),
+/// Reads rows of scores written by `save_rows`, replacing the rows of the matrix
+fn load_rows(reader: &mut impl BufRead, scores: &mut ScoreMatrix) -> Result<(), SprintError> {
+    let invalid = || SprintError::invalid_input("The scores of the checkpoint do not match the score matrix.");
+    let mut rows = scores.rows_mut();
+    let mut bytes = [0u8; 8];
+    while !reader.fill_buf()?.is_empty() {
+        reader.read_exact(&mut bytes).map_err(|_| invalid())?;
+        let protein = u64::from_le_bytes(bytes) as usize;
+        let row = rows.get_mut(protein).ok_or_else(invalid)?;
+        for score in row.iter_mut() {
+            reader.read_exact(&mut bytes[..4]).map_err(|_| invalid())?;
+            *score = f32::from_le_bytes(bytes[..4].try_into().unwrap());
         }
     }
+    Ok(())
 }
 
-unsafe impl Sync for PredictionMatrix {}
-unsafe impl Send for PredictionMatrix {}
+/// Training pairs with proteins that are not in a protein set (the scoring
+/// leaves them out)
+#[derive(Clone, Debug, Default)]
+pub struct MissingPairs {
+    /// Number of training pairs
+    pub total: usize,
+    /// Training pairs with an unknown protein, in the order of the pairs
+    pub pairs: Vec<(String, String)>,
+    /// Unknown proteins (sorted)
+    pub proteins: Vec<String>,
+}
 
-pub fn score_interactions(
+impl MissingPairs {
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Prints the number of pairs left out and the unknown proteins
+    pub fn print_warnings(&self, source: &str) {
+        if self.is_empty() {
+            return;
+        }
+        println!(
+            "WARNING: {} - {} of {} training pairs left out, with {} proteins not in the sequences: {}",
+            source,
+            self.pairs.len(),
+            self.total,
+            self.proteins.len(),
+            self.proteins.join(", ")
+        );
+    }
+}
+
+/// Lists the training pairs with proteins that are not in the set
+pub fn missing_training_pairs(protein_set: &ProteinSet, training_pairs: &[(String, String)]) -> MissingPairs {
+    missing_pairs(|name| protein_set.contains(name), training_pairs)
+}
+
+/// Lists the training pairs with proteins that are not known
+pub fn missing_pairs(is_known: impl Fn(&String) -> bool, training_pairs: &[(String, String)]) -> MissingPairs {
+    let mut proteins: HashSet<&String> = HashSet::new();
+    let mut pairs = vec![];
+    for pair in training_pairs {
+        let unknown: Vec<&String> = [&pair.0, &pair.1].into_iter().filter(|name| !is_known(name)).collect();
+        if !unknown.is_empty() {
+            proteins.extend(unknown);
+            pairs.push(pair.clone());
+        }
+    }
+
+    let mut proteins: Vec<String> = proteins.into_iter().cloned().collect();
+    proteins.sort_unstable();
+    MissingPairs {
+        total: training_pairs.len(),
+        pairs,
+        proteins,
+    }
+}
+
+/// Maps the training pairs (of known proteins) to protein indices, and
+/// returns the pairs to use in this process along with the set of interactors
+///
+/// The pairs of unknown proteins are left out (see `missing_training_pairs`).
+pub(crate) fn prepare_training_pairs(
     protein_set: &ProteinSet,
-    hsps: &HashSet<HSP>,
-    training_pairs: &Vec<(String, String)>,
-    kmer_size: usize,
+    training_pairs: &[(String, String)],
     process_rank: usize,
     world_size: usize,
     verbose: bool,
-) -> Array2<f32> {
+) -> (Vec<(usize, usize)>, HashSet<usize>) {
     let mapped_training_pairs: Vec<(usize, usize)> = training_pairs
         .iter()
-        .filter(|pair| protein_set.contains(&pair.0) && protein_set.contains(&pair.1))
-        .map(|pair| {
-            (
-                protein_set.get_protein_by_name(&pair.0).index(),
-                protein_set.get_protein_by_name(&pair.1).index(),
-            )
+        .filter_map(|pair| {
+            Some((
+                protein_set.get_protein_by_name(&pair.0).ok()?.index(),
+                protein_set.get_protein_by_name(&pair.1).ok()?.index(),
+            ))
         })
         .collect();
 
@@ -62,54 +274,80 @@ pub fn score_interactions(
     let mut training_pairs_to_process: Vec<(usize, usize)> = vec![];
     for i in 0..mapped_training_pairs.len() {
         if (i + process_rank) % world_size == 0 {
-            training_pairs_to_process.push(mapped_training_pairs[i].clone());
+            training_pairs_to_process.push(mapped_training_pairs[i]);
         }
     }
 
-    if verbose {
-        println!("Process {} -: Building the HSP table", process_rank);
-    }
-    let hsp_table = build_hsp_table(hsps, &protein_set, &interactors, kmer_size);
 
-    unsafe {
-        if verbose {
-            println!("Process {} - Initializing the score matrix.", process_rank);
-        }
-        let matrix = PredictionMatrix::new(protein_set.len());
+    (training_pairs_to_process, interactors)
+}
 
-        if verbose {
-            println!("Process {} - Scoring the interactions...", process_rank);
-        }
-        let start = Instant::now();
+/// Maps pairs of protein names to pairs of protein indices
+pub fn map_queries(protein_set: &ProteinSet, queries: &[(String, String)]) -> Result<Vec<(usize, usize)>, SprintError> {
+    queries
+        .iter()
+        .map(|query| {
+            Ok((
+                protein_set.get_protein_by_name(&query.0)?.index(),
+                protein_set.get_protein_by_name(&query.1)?.index(),
+            ))
+        })
+        .collect()
+}
 
-        training_pairs_to_process
-            .par_iter()
-            .for_each(|pair| fill_matrix(pair, &hsp_table, kmer_size as f32, &matrix));
+/// Pairs of a protein with every protein of the set (itself included)
+pub fn one_vs_all_queries(protein_set: &ProteinSet, protein: usize) -> Vec<(usize, usize)> {
+    (0..protein_set.len()).map(|partner| (protein, partner)).collect()
+}
 
-        // Divide the diagonal by two because it is double scored
-        let matrix_ptr = matrix.scores.get();
-        for i in 0..(*matrix_ptr).len_of(Axis(0)) {
-            (*matrix_ptr)[[i, i]] /= 2.0;
-        }
+/// Scores only the given pairs of proteins (indices) instead of the full
+/// score matrix
+///
+/// The score of every pair is the one `score_interactions` gives it (the
+/// contributions are summed in the same order).
+pub fn score_pairs(
+    protein_set: &ProteinSet,
+    hsps: &dyn HspSource,
+    training_pairs: &[(String, String)],
+    queries: &[(usize, usize)],
+    settings: &ScoringSettings,
+    worker: Worker,
+) -> Vec<f32> {
+    let (kmer_size, matrix) = (settings.kmer_size, settings.matrix);
+    let (process_rank, world_size, verbose) = (worker.rank, worker.world_size, worker.verbose);
+    let (training_pairs_to_process, interactors) =
+        prepare_training_pairs(protein_set, training_pairs, process_rank, world_size, verbose);
 
-        if verbose {
-            println!(
-                "Process {} - Scored the interactions in {}...",
-                process_rank,
-                start.elapsed().as_secs()
-            );
-        }
+    if verbose {
+        println!("Process {} -: Building the HSP table", process_rank);
+    }
+    let hsp_table = build_hsp_table(hsps, protein_set, &interactors, kmer_size, matrix);
 
-        (*matrix.scores.get()).clone()
+    if verbose {
+        println!("Process {} - Scoring {} pairs...", process_rank, queries.len());
     }
-}
+    let start = Instant::now();
 
-pub unsafe fn initialize_score_matrix(matrix: &mut Vec<f32>, protein_set: &ProteinSet) {
-    let sequence_set_size = protein_set.len();
-    matrix.clear();
-    for _ in 0..sequence_set_size * (sequence_set_size + 1) / 2 {
-        matrix.push(0f32);
+    let pairs_by_protein = index_pairs(&training_pairs_to_process, protein_set.len());
+    let scores = queries
+        .par_iter()
+        .map(|query| {
+            let protein = std::cmp::max(query.0, query.1);
+            let partner = std::cmp::min(query.0, query.1);
+            let pairs = relevant_pairs(hsp_table[protein].iter().map(|hsp| hsp.0), &pairs_by_protein);
+            score_cell(protein, partner, &training_pairs_to_process, &pairs, &hsp_table, kmer_size as f32)
+        })
+        .collect();
+
+    if verbose {
+        println!(
+            "Process {} - Scored the pairs in {}...",
+            process_rank,
+            start.elapsed().as_secs()
+        );
     }
+
+    scores
 }
 
 /// Creates a table of HSPs used in scoring
@@ -117,6 +355,7 @@ pub unsafe fn initialize_score_matrix(matrix: &mut Vec<f32>, protein_set: &Prote
 /// It lists the HSPs in which a given protein is involved
 /// The format of the table is a Vec<Vec<ScoredHSP>> as follows:
 ///
+/// ```text
 /// <--------------- HSPs (vector) --------------->
 /// ^
 /// |
@@ -125,14 +364,17 @@ pub unsafe fn initialize_score_matrix(matrix: &mut Vec<f32>, protein_set: &Prote
 /// |
 /// |
 /// v
+/// ```
 ///
+/// The table is symmetric (every HSP is listed in the rows of both proteins).
 /// The HSPs are sorted in order of the partner protein's index (entry 0 of the scored HSP tuple)
 /// Tuples are (partner_index, partner protein length, length of HSP, HSP score)
 pub fn build_hsp_table(
-    hsps: &HashSet<HSP>,
+    hsps: &dyn HspSource,
     protein_set: &ProteinSet,
     interactors: &HashSet<usize>,
     kmer_size: usize,
+    matrix: &SubstitutionMatrix,
 ) -> Vec<Vec<(usize, f32, f32, f32)>> {
     let mut table: Vec<Vec<(usize, f32, f32, f32)>> = Vec::new();
 
@@ -141,7 +383,7 @@ pub fn build_hsp_table(
         table.push(vec![]);
     }
 
-    hsps.iter().for_each(|hsp| {
+    hsps.hsps().for_each(|hsp| {
         let protein1 = protein_set.get_protein_by_id(hsp.location(0).index());
         let protein2 = protein_set.get_protein_by_id(hsp.location(1).index());
         let hsp_score = score_hsp(
@@ -151,6 +393,7 @@ pub fn build_hsp_table(
             hsp.location(1).position(),
             hsp.len(),
             kmer_size,
+            matrix,
         );
 
         if interactors.contains(&hsp.location(0).index())
@@ -176,67 +419,276 @@ pub fn build_hsp_table(
         }
     });
 
+    // The HSPs are fully sorted so that the scores are summed in the same
+    // order whatever the order of the HSPs in the source
     for row in &mut table {
-        row.sort_by_key(|partner| partner.0);
+        row.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then(a.1.total_cmp(&b.1))
+                .then(a.2.total_cmp(&b.2))
+                .then(a.3.total_cmp(&b.3))
+        });
     }
 
     table
 }
 
+/// Indices of the training pairs involving every protein
+pub fn index_pairs(pairs: &[(usize, usize)], num_proteins: usize) -> Vec<Vec<usize>> {
+    let mut pairs_by_protein = vec![vec![]; num_proteins];
+    for (index, pair) in pairs.iter().enumerate() {
+        pairs_by_protein[pair.0].push(index);
+        if pair.1 != pair.0 {
+            pairs_by_protein[pair.1].push(index);
+        }
+    }
+    pairs_by_protein
+}
+
+/// Indices (sorted) of the training pairs involving one of the partners of
+/// a protein in the HSP table, i.e. the pairs that contribute to its scores
+pub fn relevant_pairs(partners: impl Iterator<Item = usize>, pairs_by_protein: &[Vec<usize>]) -> Vec<usize> {
+    let mut pairs: Vec<usize> = partners.flat_map(|partner| pairs_by_protein[partner].iter().cloned()).collect();
+    pairs.sort_unstable();
+    pairs.dedup();
+    pairs
+}
+
+/// Range of the HSPs of a row of the HSP table with a given partner
 #[inline(always)]
-pub fn get_1d_index(position1: usize, position2: usize) -> usize {
-    let smallest = std::cmp::min(position1, position2);
-    let largest = std::cmp::max(position1, position2);
-    let row_start = largest * (largest + 1) / 2;
-    return row_start + smallest;
+fn partner_range(hsps: &[(usize, f32, f32, f32)], partner: usize) -> Range<usize> {
+    hsps.partition_point(|hsp| hsp.0 < partner)..hsps.partition_point(|hsp| hsp.0 <= partner)
 }
 
-/// Fill the score matrix using the similarity-to-interacting-pair principle
+#[inline(always)]
+fn contribution(hsp1: &(usize, f32, f32, f32), hsp2: &(usize, f32, f32, f32), kmer_size: f32) -> f32 {
+    let term1 = hsp1.3 * (hsp2.2 - kmer_size + 1f32);
+    let term2 = hsp2.3 * (hsp1.2 - kmer_size + 1f32);
+    (term1 + term2) / (hsp1.1 * hsp2.1)
+}
+
+/// Fill a row of the score matrix using the similarity-to-interacting-pair principle
 ///
+/// ```text
 ///               Interactor 1 --------------- Interactor 2
 ///                    |                            |
 ///               HSP1 |                       HSP2 |
 ///                    |                            |
 ///                 Query 1                      Query 2
+/// ```
 ///
-pub unsafe fn fill_matrix(
-    interacting_pair: &(usize, usize),
-    hsps: &Vec<Vec<(usize, f32, f32, f32)>>,
+/// The row of `protein` holds the scores with the proteins of lower or equal
+/// index, so it receives the contributions of the pairs of HSPs in which
+/// `protein` is the query of highest index. The pairs are used in the given
+/// order.
+pub fn fill_row(
+    protein: usize,
+    row: &mut [f32],
+    interacting_pairs: &[(usize, usize)],
+    pair_indices: &[usize],
+    hsps: &[Vec<(usize, f32, f32, f32)>],
     kmer_size: f32,
-    prediction_matrix: &PredictionMatrix,
 ) {
-    let matrix_ptr = prediction_matrix.scores.get();
-
-    // If the training pair used for scoring involved the same protein (oligomerization),
-    // then, only use the HSP pairs once!
-    if interacting_pair.0 == interacting_pair.1 {
-        for i in 0..hsps[interacting_pair.0].len() {
-            for j in i..hsps[interacting_pair.1].len() {
-                let hsp1 = &hsps[interacting_pair.0][i];
-                let hsp2 = &hsps[interacting_pair.1][j];
-
-                let term1 = hsp1.3 * (hsp2.2 - kmer_size + 1f32);
-                let term2 = hsp2.3 * (hsp1.2 - kmer_size + 1f32);
-                let contribution = (term1 + term2) / (hsp1.1 * hsp2.1);
-                (*matrix_ptr)[[hsp1.0, hsp2.0]] += contribution;
-                (*matrix_ptr)[[hsp2.0, hsp1.0]] += contribution;
+    for &index in pair_indices {
+        let interacting_pair = interacting_pairs[index];
+        let hsps1 = &hsps[interacting_pair.0];
+        let hsps2 = &hsps[interacting_pair.1];
+
+        // If the training pair used for scoring involved the same protein (oligomerization),
+        // then, only use the HSP pairs once! (as the HSPs are sorted, the second HSP
+        // of a pair has the query of highest index)
+        if interacting_pair.0 == interacting_pair.1 {
+            for j in partner_range(hsps1, protein) {
+                for hsp1 in &hsps1[0..=j] {
+                    row[hsp1.0] += contribution(hsp1, &hsps1[j], kmer_size);
+                }
+            }
+
+        // We are not concerned with this if the interacting proteins are different.
+        } else {
+            let range1 = partner_range(hsps1, protein);
+            let range2 = partner_range(hsps2, protein);
+
+            for hsp1 in &hsps1[range1.clone()] {
+                for hsp2 in &hsps2[0..range2.end] {
+                    row[hsp2.0] += contribution(hsp1, hsp2, kmer_size);
+                }
+            }
+            for hsp2 in &hsps2[range2] {
+                for hsp1 in &hsps1[0..range1.start] {
+                    row[hsp1.0] += contribution(hsp1, hsp2, kmer_size);
+                }
             }
         }
+    }
+}
 
-    // We are not concerned with this if the interacting proteins are different.
-    } else {
-        let hsps1 = &hsps[interacting_pair.0];
+/// Fills the row of scores of a new protein (that is not in the HSP table)
+/// with the proteins of the table, like `fill_row` for a protein of higher
+/// index than all of them
+///
+/// `new_hsps` holds the entries of the new protein in the rows of the
+/// interactors it shares HSPs with (sorted like the rows of the table).
+pub fn fill_new_row(
+    row: &mut [f32],
+    interacting_pairs: &[(usize, usize)],
+    pair_indices: &[usize],
+    hsps: &[Vec<(usize, f32, f32, f32)>],
+    new_hsps: &HashMap<usize, Vec<(usize, f32, f32, f32)>>,
+    kmer_size: f32,
+) {
+    let none = vec![];
+    for &index in pair_indices {
+        let interacting_pair = interacting_pairs[index];
+        let new1 = new_hsps.get(&interacting_pair.0).unwrap_or(&none);
+
+        // As in `fill_row`, the entries of the new protein come after those
+        // of the proteins of the table
+        if interacting_pair.0 == interacting_pair.1 {
+            for new_hsp in new1 {
+                for hsp1 in &hsps[interacting_pair.0] {
+                    row[hsp1.0] += contribution(hsp1, new_hsp, kmer_size);
+                }
+            }
+        } else {
+            let new2 = new_hsps.get(&interacting_pair.1).unwrap_or(&none);
+            for hsp1 in new1 {
+                for hsp2 in &hsps[interacting_pair.1] {
+                    row[hsp2.0] += contribution(hsp1, hsp2, kmer_size);
+                }
+            }
+            for hsp2 in new2 {
+                for hsp1 in &hsps[interacting_pair.0] {
+                    row[hsp1.0] += contribution(hsp1, hsp2, kmer_size);
+                }
+            }
+        }
+    }
+}
 
-        for hsp1 in hsps1 {
-            let hsps2 = &hsps[interacting_pair.1];
+/// Score of `protein` with `partner` (of lower or equal index), i.e. the
+/// entry `partner` of the row filled by `fill_row`, summed in the same order
+pub fn score_cell(
+    protein: usize,
+    partner: usize,
+    interacting_pairs: &[(usize, usize)],
+    pair_indices: &[usize],
+    hsps: &[Vec<(usize, f32, f32, f32)>],
+    kmer_size: f32,
+) -> f32 {
+    let mut score = 0f32;
 
-            for hsp2 in hsps2 {
-                let term1 = hsp1.3 * (hsp2.2 - kmer_size + 1f32);
-                let term2 = hsp2.3 * (hsp1.2 - kmer_size + 1f32);
-                let contribution = (term1 + term2) / (hsp1.1 * hsp2.1);
-                (*matrix_ptr)[[hsp1.0, hsp2.0]] += contribution;
-                (*matrix_ptr)[[hsp2.0, hsp1.0]] += contribution;
+    for &index in pair_indices {
+        let interacting_pair = interacting_pairs[index];
+        let hsps1 = &hsps[interacting_pair.0];
+        let hsps2 = &hsps[interacting_pair.1];
+
+        if interacting_pair.0 == interacting_pair.1 {
+            let partner_hsps = partner_range(hsps1, partner);
+            for j in partner_range(hsps1, protein) {
+                for hsp1 in &hsps1[partner_hsps.start..std::cmp::min(partner_hsps.end, j + 1)] {
+                    score += contribution(hsp1, &hsps1[j], kmer_size);
+                }
+            }
+        } else {
+            for hsp1 in &hsps1[partner_range(hsps1, protein)] {
+                for hsp2 in &hsps2[partner_range(hsps2, partner)] {
+                    score += contribution(hsp1, hsp2, kmer_size);
+                }
+            }
+            if partner != protein {
+                for hsp2 in &hsps2[partner_range(hsps2, protein)] {
+                    for hsp1 in &hsps1[partner_range(hsps1, partner)] {
+                        score += contribution(hsp1, hsp2, kmer_size);
+                    }
+                }
             }
         }
     }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprint::hsp::HSP;
+    use crate::sprint::location::Location;
+    use crate::sprint::protein::Protein;
+
+    fn protein_set() -> ProteinSet {
+        let sequences = ["MKTAYIAKQRQISFVKSHFSRQ", "MKTAYIAKQRQLSFVKSHFSRA", "GAVLIPFMWSTCYNQDEKRH", "GAVLIPFMWSTCYNQDEKRW", "MSTNPKPQRKTKRNTNRRPQ"];
+        let proteins = sequences
+            .iter()
+            .enumerate()
+            .map(|(i, sequence)| Protein::new(i, format!("P{}", i), sequence.to_string(), false).unwrap())
+            .collect();
+        ProteinSet::new(proteins).unwrap()
+    }
+
+    fn hsps() -> HashSet<HSP> {
+        [(0, 1, 0, 0, 20), (2, 3, 0, 0, 18), (0, 4, 2, 3, 8), (1, 2, 5, 4, 10), (3, 4, 1, 6, 12)]
+            .iter()
+            .map(|&(protein1, protein2, position1, position2, length)| {
+                HSP::new(Location::new(protein1, position1), Location::new(protein2, position2), length)
+            })
+            .collect()
+    }
+
+    fn training_pairs() -> Vec<(String, String)> {
+        [("P0", "P2"), ("P1", "P3"), ("P4", "P2"), ("P0", "P3")]
+            .iter()
+            .map(|pair| (pair.0.to_string(), pair.1.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn training_pairs_of_unknown_proteins_are_reported() {
+        let pairs: Vec<(String, String)> = [("P0", "P1"), ("P0", "Q1"), ("Q2", "Q1"), ("P3", "P4")]
+            .iter()
+            .map(|(protein1, protein2)| (protein1.to_string(), protein2.to_string()))
+            .collect();
+        let missing = missing_training_pairs(&protein_set(), &pairs);
+        assert_eq!(missing.total, 4);
+        assert_eq!(missing.pairs, vec![pairs[1].clone(), pairs[2].clone()]);
+        assert_eq!(missing.proteins, vec!["Q1", "Q2"]);
+        assert!(missing_training_pairs(&protein_set(), &pairs[3..]).is_empty());
+    }
+
+    #[test]
+    fn checkpointed_scoring_resumes_with_the_same_scores() {
+        let protein_set = protein_set();
+        let (hsps, training_pairs) = (hsps(), training_pairs());
+        let matrix = SubstitutionMatrix::load("PAM120").unwrap();
+        let settings = ScoringSettings { kmer_size: 5, matrix: &matrix };
+        let expected = score_interactions(&protein_set, &hsps, &training_pairs, &settings, Worker::single(false));
+        assert!(expected.values().iter().any(|&score| score > 0.0));
+
+        let directory = std::env::temp_dir().join(format!("sprint_prediction_checkpoint_{}", std::process::id()));
+        let directory = directory.to_str().unwrap();
+        let manifest = scoring_manifest(&protein_set, &training_pairs, &settings, Worker::single(false), 1);
+        let score = |checkpoint: &mut Checkpoint| {
+            let scores = ScoreMatrix::new(protein_set.len());
+            score_interactions_into(&protein_set, &hsps, &training_pairs, &settings, Worker::single(false), scores, Some((checkpoint, 1)))
+                .unwrap()
+        };
+
+        // Every chunk saved, then the run interrupted after some chunks
+        let mut checkpoint = Checkpoint::open(directory, &manifest).unwrap();
+        assert_eq!(score(&mut checkpoint).values(), expected.values());
+        assert_eq!(checkpoint.keys().len(), training_pairs.len());
+        for completed in (0..training_pairs.len()).rev() {
+            std::fs::remove_file(format!("{}/{}", directory, scores_key(completed))).unwrap();
+            let mut checkpoint = Checkpoint::open(directory, &manifest).unwrap();
+            assert_eq!(score(&mut checkpoint).values(), expected.values(), "{} chunks completed", completed);
+        }
+
+        // A chunk that does not match the score matrix is rejected
+        let mut checkpoint = Checkpoint::open(directory, &manifest).unwrap();
+        checkpoint.save(&scores_key(0), &(protein_set.len() as u64).to_le_bytes()).unwrap();
+        let scores = ScoreMatrix::new(protein_set.len());
+        let result = score_interactions_into(&protein_set, &hsps, &training_pairs, &settings, Worker::single(false), scores, Some((&mut checkpoint, 1)));
+        assert!(result.is_err());
+        checkpoint.remove().unwrap();
+    }
 }
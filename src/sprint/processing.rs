@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 use crate::sprint::proteinset::ProteinSet;
 use crate::sprint::hsp::HSP;
+use crate::sprint::hspfile::HspSource;
 
 use super::location::Location;
 
@@ -16,29 +17,50 @@ pub fn process_hsps(
     if verbose {
         println!("Collecting the HSPs...");
     }
+    process_hsp_sources(protein_set, &[&hsps], kmer_size, t_count, verbose)
+}
+
+/// Processes the HSPs of several sources together (e.g. existing HSPs and
+/// newly extracted ones), visiting every source twice rather than loading it
+pub fn process_hsp_sources(
+    protein_set: &ProteinSet,
+    sources: &[&dyn HspSource],
+    kmer_size: usize,
+    t_count: u16,
+    verbose: bool
+) -> HashSet<HSP> {
 
     // Count the occurence of amino acids within HSPs
     if verbose {
         println!("Counting residue occurences within HSPs...");
     }
-    let counts = count_residue_occurences(&hsps, &protein_set, kmer_size);
+    let mut counts = empty_counts(protein_set);
+    for source in sources {
+        add_residue_occurences(&mut counts, *source, kmer_size);
+    }
 
     // Process the HSPs
     if verbose {
         println!("Processing HSPs...");
     }
     let mut processed_hsps: HashSet<HSP> = HashSet::new();
-    hsps.into_iter().for_each(|hsp| unsafe {
-        process_hsp(hsp, &protein_set, &counts, kmer_size, t_count, &mut processed_hsps);
-    });
+    for source in sources {
+        source.hsps().for_each(|hsp| unsafe {
+            process_hsp(hsp, &protein_set, &counts, kmer_size, t_count, &mut processed_hsps);
+        });
+    }
 
-    processed_hsps.into_iter().map(|hsp| {
-        hsp.clone()
-    }).collect::<HashSet<HSP>>()
+    processed_hsps
+}
 
+pub fn count_residue_occurences(hsps: &dyn HspSource, protein_set: &ProteinSet, kmer_size: usize) -> Vec<Vec<u16>>{
+    let mut counts = empty_counts(protein_set);
+    add_residue_occurences(&mut counts, hsps, kmer_size);
+    counts
 }
 
-pub fn count_residue_occurences(hsps: &HashSet<HSP>, protein_set: &ProteinSet, kmer_size: usize) -> Vec<Vec<u16>>{
+/// Counts of zero for every residue of every protein
+fn empty_counts(protein_set: &ProteinSet) -> Vec<Vec<u16>> {
     let protein_set_size= protein_set.len();
     let mut counts: Vec<Vec<u16>> = Vec::new();
 
@@ -51,8 +73,12 @@ pub fn count_residue_occurences(hsps: &HashSet<HSP>, protein_set: &ProteinSet, k
             counts[i].push(0);
         }
     }
+    counts
+}
 
-    for hsp in hsps {
+/// Adds the occurences of the residues within some HSPs to their counts
+fn add_residue_occurences(counts: &mut [Vec<u16>], hsps: &dyn HspSource, kmer_size: usize) {
+    for hsp in hsps.hsps() {
         if hsp.len() < kmer_size {
             continue;
         }
@@ -62,7 +88,6 @@ pub fn count_residue_occurences(hsps: &HashSet<HSP>, protein_set: &ProteinSet, k
             counts[hsp.location(1).index()][i + hsp.location(1).position()] += 1;
         }
     }
-    counts
 }
 
 pub unsafe fn process_hsp(
@@ -110,3 +135,158 @@ pub unsafe fn process_hsp(
         }
     }
 }
+
+/// Updates a set of processed HSPs after new raw HSPs were extracted (e.g.
+/// for new proteins), given the raw HSPs the processed HSPs were obtained from
+///
+/// Whether a residue is filtered out only depends on its count being above
+/// `t_count`, so only the old HSPs covering a residue whose count crossed the
+/// threshold need to be processed again, along with the new HSPs. The old
+/// HSPs are visited a few times (e.g. from a memory-mapped file) rather than
+/// loaded, only the affected ones being kept in memory.
+pub fn update_processed_hsps(
+    protein_set: &ProteinSet,
+    old_hsps: &dyn HspSource,
+    new_hsps: &HashSet<HSP>,
+    processed_hsps: HashSet<HSP>,
+    kmer_size: usize,
+    t_count: u16,
+    verbose: bool
+) -> HashSet<HSP> {
+
+    if verbose {
+        println!("Counting residue occurences within HSPs...");
+    }
+    let old_counts = count_residue_occurences(old_hsps, protein_set, kmer_size);
+    let added_counts = count_residue_occurences(new_hsps, protein_set, kmer_size);
+    let counts: Vec<Vec<u16>> = old_counts.iter()
+        .zip(added_counts.iter())
+        .map(|(old, added)| old.iter().zip(added.iter()).map(|(a, b)| a.saturating_add(*b)).collect())
+        .collect();
+
+    // Residues that are now filtered out
+    let affected: Vec<Vec<bool>> = old_counts.iter()
+        .zip(counts.iter())
+        .map(|(old, new)| old.iter().zip(new.iter()).map(|(a, b)| (*a > t_count) != (*b > t_count)).collect())
+        .collect();
+
+    let is_affected = |hsp: &HSP| {
+        hsp.len() >= kmer_size && (0..hsp.len() - (kmer_size - 1)).any(|i| {
+            affected[hsp.location(0).index()][hsp.location(0).position() + i]
+                || affected[hsp.location(1).index()][hsp.location(1).position() + i]
+        })
+    };
+
+    let affected_hsps: Vec<HSP> = old_hsps.hsps().filter(|hsp| is_affected(hsp)).collect();
+    let affected_diagonals: HashSet<(usize, usize, i64)> = affected_hsps.iter().map(diagonal).collect();
+
+    if verbose {
+        println!("Reprocessing {} of the previous HSPs and {} new HSPs...", affected_hsps.len(), new_hsps.len());
+    }
+
+    // The fragments previously obtained from the affected HSPs are removed,
+    // unless another (unaffected) HSP of the same diagonal also yields them
+    let mut removed = process_subset(affected_hsps.iter().cloned(), protein_set, &old_counts, kmer_size, t_count);
+    let kept = process_subset(
+        old_hsps.hsps().filter(|hsp| affected_diagonals.contains(&diagonal(hsp)) && !is_affected(hsp)),
+        protein_set, &old_counts, kmer_size, t_count);
+    removed.retain(|hsp| !kept.contains(hsp));
+
+    let mut updated: HashSet<HSP> = processed_hsps.into_iter().filter(|hsp| !removed.contains(hsp)).collect();
+    updated.extend(process_subset(affected_hsps.into_iter(), protein_set, &counts, kmer_size, t_count));
+    updated.extend(process_subset(new_hsps.iter().cloned(), protein_set, &counts, kmer_size, t_count));
+    updated
+}
+
+/// Processes some HSPs given the residue counts
+fn process_subset(
+    hsps: impl Iterator<Item = HSP>,
+    protein_set: &ProteinSet,
+    counts: &Vec<Vec<u16>>,
+    kmer_size: usize,
+    t_count: u16
+) -> HashSet<HSP> {
+    let mut processed_hsps: HashSet<HSP> = HashSet::new();
+    hsps.for_each(|hsp| unsafe {
+        process_hsp(hsp, protein_set, counts, kmer_size, t_count, &mut processed_hsps);
+    });
+    processed_hsps
+}
+
+/// Proteins and offset of the diagonal an HSP lies on (the fragments of a
+/// processed HSP lie on the same diagonal)
+fn diagonal(hsp: &HSP) -> (usize, usize, i64) {
+    (
+        hsp.location(0).index(),
+        hsp.location(1).index(),
+        hsp.location(1).position() as i64 - hsp.location(0).position() as i64
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprint::protein::Protein;
+
+    const LENGTHS: [usize; 4] = [40, 35, 30, 45];
+
+    fn protein_set() -> ProteinSet {
+        let proteins = LENGTHS
+            .iter()
+            .enumerate()
+            .map(|(i, &length)| Protein::new(i, format!("P{}", i), "ACDEFGHIKLMNPQRSTVWY".repeat(3)[..length].to_string(), false).unwrap())
+            .collect();
+        ProteinSet::new(proteins).unwrap()
+    }
+
+    /// Random HSPs between the proteins (from a linear congruential
+    /// generator, so that the test is deterministic)
+    fn random_hsps(seed: u64, count: usize) -> HashSet<HSP> {
+        let mut state = seed;
+        let mut next = |bound: usize| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) as usize % bound
+        };
+        (0..count)
+            .map(|_| {
+                let (protein1, protein2) = (next(LENGTHS.len()), next(LENGTHS.len()));
+                let length = 4 + next(12);
+                let position1 = next(LENGTHS[protein1] - length + 1);
+                let position2 = next(LENGTHS[protein2] - length + 1);
+                HSP::new(Location::new(protein1, position1), Location::new(protein2, position2), length)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn update_matches_processing_from_scratch() {
+        let protein_set = protein_set();
+        let (kmer_size, t_count) = (4, 3);
+
+        // Updates where some old HSPs must be processed again
+        let mut reprocessed = 0;
+        for seed in 0..20 {
+            let old_hsps = random_hsps(seed, 25);
+            let new_hsps: HashSet<HSP> = random_hsps(seed + 1000, 10).difference(&old_hsps).cloned().collect();
+            let processed = process_hsps(&protein_set, old_hsps.clone(), kmer_size, t_count, false);
+            let mut naive = processed.clone();
+            naive.extend(process_hsps(&protein_set, new_hsps.clone(), kmer_size, t_count, false));
+
+            let updated = update_processed_hsps(&protein_set, &old_hsps, &new_hsps, processed, kmer_size, t_count, false);
+            let all_hsps: HashSet<HSP> = old_hsps.union(&new_hsps).cloned().collect();
+            let expected = process_hsps(&protein_set, all_hsps, kmer_size, t_count, false);
+            assert!(updated == expected, "seed {}", seed);
+            reprocessed += (naive != expected) as usize;
+        }
+        assert!(reprocessed > 0);
+    }
+
+    #[test]
+    fn sources_are_processed_together() {
+        let protein_set = protein_set();
+        let (old_hsps, new_hsps) = (random_hsps(1, 25), random_hsps(2, 10));
+        let all_hsps: HashSet<HSP> = old_hsps.union(&new_hsps).cloned().collect();
+        let processed = process_hsp_sources(&protein_set, &[&old_hsps, &new_hsps], 4, 3, false);
+        assert!(processed == process_hsps(&protein_set, all_hsps, 4, 3, false));
+    }
+}
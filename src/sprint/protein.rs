@@ -1,4 +1,5 @@
 use crate::sprint::constants::RESIDUE_CODES;
+use crate::sprint::error::SprintError;
 
 #[derive(Clone)]
 pub struct Protein {
@@ -10,19 +11,47 @@ pub struct Protein {
 }
 
 impl Protein {
-    /// Creates a new protein
-    pub fn new(index: usize, name: String, sequence: String, new: bool) -> Self {
-        Protein { 
+    /// Creates a new protein (fails on characters that are not amino acids)
+    pub fn new(index: usize, name: String, sequence: String, new: bool) -> Result<Self, SprintError> {
+        let residues = sequence.chars()
+            .enumerate()
+            .map(|(i, amino_acid)| match RESIDUE_CODES.get(&amino_acid) {
+                Some(&code) => Ok(code),
+                None => Err(SprintError::InvalidResidue { protein: name.clone(), position: i + 1, residue: amino_acid }),
+            })
+            .collect::<Result<Vec<usize>, SprintError>>()?;
+
+        Ok(Protein {
+            index,
+            name,
+            sequence,
+            residues,
+            new
+        })
+    }
+
+    /// Creates a protein from the codes of its residues (e.g. given by the
+    /// policy of `sanitization`)
+    pub fn with_residues(index: usize, name: String, sequence: String, residues: Vec<usize>, new: bool) -> Self {
+        Protein {
             index,
-            name: name.clone(),
-            sequence: sequence.clone(),
-            residues: sequence.chars()
-                .map(|amino_acid| *RESIDUE_CODES.get(&amino_acid).unwrap())
-                .collect(),
+            name,
+            sequence,
+            residues,
             new
         }
     }
 
+    /// Copy of the protein with another index
+    pub fn reindexed(&self, index: usize, new: bool) -> Protein {
+        Protein { index, new, ..self.clone() }
+    }
+
+    /// Copy of the protein with another name
+    pub fn renamed(&self, name: String) -> Protein {
+        Protein { name, ..self.clone() }
+    }
+
     pub fn sub(&self, start: usize, end: usize) -> &str {
         &self.sequence[start as usize..end + 1 as usize]
     }
@@ -50,4 +79,25 @@ impl Protein {
     pub fn is_new(&self) -> bool {
         self.new
     }
+
+    /// Creates a new protein from this one with some residues substituted
+    /// (position, residue), the other residues keeping their codes
+    pub fn mutate(&self, index: usize, name: String, substitutions: &[(usize, char)]) -> Result<Protein, SprintError> {
+        let mut sequence: Vec<char> = self.sequence.chars().collect();
+        let mut residues = self.residues.clone();
+        for &(position, residue) in substitutions {
+            if position >= sequence.len() {
+                return Err(SprintError::invalid_input(format!(
+                    "Position {} is out of {} ({} residues)", position, self.name, sequence.len()
+                )));
+            }
+            residues[position] = *RESIDUE_CODES.get(&residue).ok_or_else(|| SprintError::InvalidResidue {
+                protein: name.clone(),
+                position: position + 1,
+                residue,
+            })?;
+            sequence[position] = residue;
+        }
+        Ok(Protein::with_residues(index, name, sequence.into_iter().collect(), residues, true))
+    }
 }
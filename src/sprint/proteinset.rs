@@ -1,88 +1,235 @@
-use std::collections::HashMap;
-use std::io::Error;
+use std::collections::{HashMap, HashSet};
+use crate::sprint::error::SprintError;
+use crate::sprint::headers::HeaderFormat;
 use crate::sprint::protein::Protein;
+use crate::sprint::sanitization::{SequencePolicy, ValidationReport};
 
 use crate::sprint::fileio::load_fasta;
+use crate::sprint::utils::fnv1a;
+
+/// What to do with records sharing an ID
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DuplicatePolicy {
+    /// Fail on the first duplicate ID
+    Error,
+    /// Rename the duplicates `<id>_2`, `<id>_3`, ... (in the order of the
+    /// records)
+    Suffix,
+}
+
+impl DuplicatePolicy {
+    pub fn from_name(name: &str) -> Result<Self, SprintError> {
+        match name.to_lowercase().as_str() {
+            "error" => Ok(DuplicatePolicy::Error),
+            "suffix" => Ok(DuplicatePolicy::Suffix),
+            _ => Err(SprintError::invalid_input(format!(
+                "Unknown duplicate policy: {} (expected error or suffix)",
+                name
+            ))),
+        }
+    }
+}
 
 /// Structure that holds the sequences used to extract HSPs
 /// and score protein interactions
+///
+/// When identical sequences are collapsed, only the first record of every
+/// sequence is kept (the representative), and the IDs of the others are
+/// aliases of it: looking up an alias gives the representative, and the
+/// scores of the representative are saved for all its aliases.
 #[derive(Clone)]
 pub struct ProteinSet {
     // Set of proteins sequences
     proteins: Vec<Protein>,
-    // Indices of the proteins
-    indices: HashMap<String, usize>
+    // Indices of the proteins (by name or alias)
+    indices: HashMap<String, usize>,
+    // Aliases of the representatives of collapsed sequences
+    aliases: HashMap<usize, Vec<String>>
+}
+
+/// Builds a name-index map from a vector of Protein (fails on duplicate
+/// names)
+fn get_indices(sequences: &[Protein]) -> Result<HashMap<String, usize>, SprintError> {
+    let mut indices: HashMap<String, usize> = HashMap::new();
+    for (i, protein) in sequences.iter().enumerate() {
+        if indices.insert(protein.name(), i).is_some() {
+            return Err(SprintError::DuplicateProtein(protein.name()));
+        }
+    }
+    Ok(indices)
 }
 
-/// Builds a name-index map from a vector of Protein
-fn get_indices(sequences: &Vec<Protein>) -> HashMap<String, usize> {
-    let indices: HashMap<String, usize> = sequences
-        .iter()
-        .enumerate()
-        .map(|(i, protein)| (protein.name(), i))
-        .collect();
-    indices
+/// Renames the proteins whose name was already seen `<name>_2`, `<name>_3`,
+/// ... (skipping the names taken by other proteins)
+fn suffix_duplicates(proteins: Vec<Protein>) -> Vec<Protein> {
+    let mut taken: HashSet<String> = proteins.iter().map(|protein| protein.name()).collect();
+    let mut seen: HashSet<String> = HashSet::new();
+
+    proteins
+        .into_iter()
+        .map(|protein| {
+            if seen.insert(protein.name()) {
+                return protein;
+            }
+            let name = (2..)
+                .map(|i| format!("{}_{}", protein.name(), i))
+                .find(|name| !taken.contains(name))
+                .unwrap();
+            taken.insert(name.clone());
+            seen.insert(name.clone());
+            protein.renamed(name)
+        })
+        .collect()
 }
 
 impl ProteinSet {
 
-    /// Create a protein set from protein sequences
-    pub fn new(proteins: Vec<Protein>) -> Self {
-        let indices = get_indices(&proteins);
+    /// Create a protein set from protein sequences (fails on duplicate names)
+    pub fn new(proteins: Vec<Protein>) -> Result<Self, SprintError> {
+        let indices = get_indices(&proteins)?;
 
-        ProteinSet {
+        Ok(ProteinSet {
             proteins,
-            indices
+            indices,
+            aliases: HashMap::new()
+        })
+    }
+
+    /// Create a protein set from protein sequences, handling the duplicate
+    /// names according to the policy and optionally collapsing the identical
+    /// sequences
+    pub fn with_options(proteins: Vec<Protein>, duplicates: DuplicatePolicy, collapse: bool) -> Result<Self, SprintError> {
+        let proteins = match duplicates {
+            DuplicatePolicy::Error => proteins,
+            DuplicatePolicy::Suffix => suffix_duplicates(proteins),
+        };
+        let mut protein_set = ProteinSet::new(proteins)?;
+        if collapse {
+            protein_set = protein_set.collapse_identical();
         }
+        Ok(protein_set)
+    }
+
+    /// Create a protein set from a FASTA file (see `load_fasta` for the
+    /// policy, the headers and the report, and `with_options` for the
+    /// duplicates)
+    pub fn from_file(
+        filepath: &str,
+        policy: &SequencePolicy,
+        headers: &HeaderFormat,
+        duplicates: DuplicatePolicy,
+        collapse: bool
+    ) -> Result<(Self, ValidationReport), SprintError> {
+        let (proteins, report) = load_fasta(filepath, false, policy, headers)?;
+        let protein_set = ProteinSet::with_options(proteins, duplicates, collapse)
+            .map_err(|error| error.at(filepath, None))?;
+        Ok((protein_set, report))
     }
 
-    /// Create a protein set from a FASTA file
-    pub fn from_file(filepath: &str) -> Result<Self, Error> {
-        let proteins = load_fasta(filepath, false)?;
-        let indices = get_indices(&proteins);
+    /// Keeps the first protein of every sequence, the names of the others
+    /// becoming aliases of it
+    pub fn collapse_identical(self) -> Self {
+        let mut representatives: HashMap<String, usize> = HashMap::new();
+        let mut proteins: Vec<Protein> = vec![];
+        let mut aliases: HashMap<usize, Vec<String>> = HashMap::new();
+        let mut indices: HashMap<String, usize> = HashMap::new();
 
-        Ok(ProteinSet {
+        for (index, protein) in self.proteins.into_iter().enumerate() {
+            let names = std::iter::once(protein.name()).chain(self.aliases.get(&index).cloned().unwrap_or_default());
+            match representatives.get(&protein.seq()) {
+                Some(&representative) => {
+                    for name in names {
+                        indices.insert(name.clone(), representative);
+                        aliases.entry(representative).or_default().push(name);
+                    }
+                }
+                None => {
+                    let representative = proteins.len();
+                    representatives.insert(protein.seq(), representative);
+                    for (i, name) in names.enumerate() {
+                        indices.insert(name.clone(), representative);
+                        if i > 0 {
+                            aliases.entry(representative).or_default().push(name);
+                        }
+                    }
+                    proteins.push(protein.reindexed(representative, protein.is_new()));
+                }
+            }
+        }
+
+        ProteinSet {
             proteins,
-            indices
-        })
+            indices,
+            aliases
+        }
     }
 
-    /// Add new proteins
-    pub fn add_new(&mut self, proteins: Vec<Protein>, new: bool) {
-        let mut new_set = vec![];
+    /// Protein set made of some of the proteins (with their aliases),
+    /// reindexed in the order of `indices`
+    pub fn subset(&self, indices: &[usize]) -> Self {
+        let proteins: Vec<Protein> = indices
+            .iter()
+            .enumerate()
+            .map(|(new, &old)| self.proteins[old].reindexed(new, self.proteins[old].is_new()))
+            .collect();
+        let aliases: HashMap<usize, Vec<String>> = indices
+            .iter()
+            .enumerate()
+            .filter_map(|(new, old)| Some((new, self.aliases.get(old)?.clone())))
+            .collect();
+        let mut subset_indices: HashMap<String, usize> =
+            proteins.iter().map(|protein| (protein.name(), protein.index())).collect();
+        for (&index, names) in aliases.iter() {
+            subset_indices.extend(names.iter().map(|name| (name.clone(), index)));
+        }
 
-        // Add the old proteins
-        for p in self.proteins.iter() {
-            new_set.push(p.clone());
+        ProteinSet {
+            proteins,
+            indices: subset_indices,
+            aliases
         }
+    }
 
-        // Add the new proteins
+    /// Add new proteins (fails on names already in the set)
+    pub fn add_new(&mut self, proteins: Vec<Protein>, new: bool) -> Result<(), SprintError> {
+        let mut indices = self.indices.clone();
+        let offset = self.proteins.len();
         for (i, p) in proteins.iter().enumerate() {
-            let new_prot = Protein::new(i + self.proteins.len(), p.name(), p.seq(), new);
-            new_set.push(new_prot);
+            if indices.insert(p.name(), i + offset).is_some() {
+                return Err(SprintError::DuplicateProtein(p.name()));
+            }
         }
 
-        // Reset the indices table
-        let indices = get_indices(&new_set);
-
-        // Update
-        self.proteins = new_set;
+        // Add the new proteins
+        for (i, p) in proteins.iter().enumerate() {
+            self.proteins.push(p.reindexed(i + offset, new));
+        }
         self.indices = indices;
+        Ok(())
     }
 
     // Add new proteins from a file
-    pub fn add_from_file(&mut self, filepath: &String, new: bool) {
-        let proteins = load_fasta(filepath, new)
-            .expect("Error while opening sequences file.");
-        self.add_new(proteins, new);
+    pub fn add_from_file(
+        &mut self,
+        filepath: &String,
+        new: bool,
+        policy: &SequencePolicy,
+        headers: &HeaderFormat
+    ) -> Result<ValidationReport, SprintError> {
+        let (proteins, report) = load_fasta(filepath, new, policy, headers)?;
+        self.add_new(proteins, new).map_err(|error| error.at(filepath, None))?;
+        Ok(report)
     }
 
     pub fn get_protein_by_id(&self, index: usize) -> &Protein {
         &self.proteins[index]
     }
 
-    pub fn get_protein_by_name(&self, name: &String) -> &Protein {
-        &self.proteins[*self.indices.get(name).unwrap()]
+    pub fn get_protein_by_name(&self, name: &str) -> Result<&Protein, SprintError> {
+        match self.indices.get(name) {
+            Some(&index) => Ok(&self.proteins[index]),
+            None => Err(SprintError::UnknownProtein(name.to_string())),
+        }
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &Protein> {
@@ -97,9 +244,120 @@ impl ProteinSet {
         self.proteins.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.proteins.is_empty()
+    }
+
     pub fn contains(&self, name: &String) -> bool {
         self.indices.contains_key(name)
     }
+
+    /// Names of the collapsed proteins identical to a protein
+    pub fn aliases(&self, index: usize) -> &[String] {
+        self.aliases.get(&index).map(|names| names.as_slice()).unwrap_or_default()
+    }
+
+    /// Name of a protein followed by its aliases
+    pub fn names(&self, index: usize) -> Vec<String> {
+        std::iter::once(self.proteins[index].name())
+            .chain(self.aliases(index).iter().cloned())
+            .collect()
+    }
+
+    /// Number of names (proteins and aliases)
+    pub fn num_names(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Fingerprint (64-bit FNV-1a hash) of the names and sequences of the
+    /// first `num_proteins` proteins, used to check that an HSP file was
+    /// extracted from the same sequences
+    pub fn fingerprint(&self, num_proteins: usize) -> u64 {
+        fnv1a(self.proteins.iter().take(num_proteins).flat_map(|protein| {
+            protein.name().into_bytes().into_iter()
+                .chain(std::iter::once(b'\n'))
+                .chain(protein.seq().into_bytes())
+                .chain(std::iter::once(b'\n'))
+        }))
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proteins(records: &[(&str, &str)]) -> Vec<Protein> {
+        records
+            .iter()
+            .enumerate()
+            .map(|(i, (name, sequence))| Protein::new(i, name.to_string(), sequence.to_string(), false).unwrap())
+            .collect()
+    }
+
+    fn names(protein_set: &ProteinSet) -> Vec<String> {
+        protein_set.iter().map(|protein| protein.name()).collect()
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected_by_default() {
+        let records = [("P1", "ACDE"), ("P2", "FGHI"), ("P1", "KLMN")];
+        match ProteinSet::with_options(proteins(&records), DuplicatePolicy::Error, false) {
+            Err(SprintError::DuplicateProtein(name)) => assert_eq!(name, "P1"),
+            _ => panic!("expected a duplicate protein"),
+        }
+    }
+
+    #[test]
+    fn duplicate_names_are_suffixed() {
+        // P1_2 is taken by another record, so the first duplicate is P1_3
+        let records = [("P1", "ACDE"), ("P1", "FGHI"), ("P1_2", "KLMN"), ("P1", "PQRS")];
+        let protein_set = ProteinSet::with_options(proteins(&records), DuplicatePolicy::Suffix, false).unwrap();
+        assert_eq!(names(&protein_set), vec!["P1", "P1_3", "P1_2", "P1_4"]);
+        assert_eq!(protein_set.get_protein_by_name("P1_3").unwrap().seq(), "FGHI");
+        assert_eq!(protein_set.get_protein_by_name("P1_4").unwrap().index(), 3);
+    }
+
+    #[test]
+    fn identical_sequences_are_collapsed_into_aliases() {
+        let records = [("A", "ACDE"), ("B", "FGHI"), ("C", "ACDE"), ("D", "FGHI"), ("E", "ACDE")];
+        let protein_set = ProteinSet::with_options(proteins(&records), DuplicatePolicy::Error, true).unwrap();
+
+        assert_eq!(names(&protein_set), vec!["A", "B"]);
+        assert_eq!(protein_set.len(), 2);
+        assert_eq!(protein_set.num_names(), 5);
+        assert_eq!(protein_set.names(0), vec!["A", "C", "E"]);
+        assert_eq!(protein_set.aliases(1), ["D".to_string()]);
+        assert_eq!(protein_set.get_protein_by_name("E").unwrap().index(), 0);
+        assert_eq!(protein_set.get_protein_by_name("D").unwrap().index(), 1);
+    }
+
+    #[test]
+    fn suffixed_duplicates_are_collapsed_too() {
+        let records = [("A", "ACDE"), ("A", "ACDE"), ("B", "FGHI")];
+        let protein_set = ProteinSet::with_options(proteins(&records), DuplicatePolicy::Suffix, true).unwrap();
+        assert_eq!(names(&protein_set), vec!["A", "B"]);
+        assert_eq!(protein_set.names(0), vec!["A", "A_2"]);
+        assert_eq!(protein_set.get_protein_by_name("B").unwrap().index(), 1);
+    }
+
+    #[test]
+    fn aliases_are_kept_by_subsets_and_reserved() {
+        let records = [("A", "ACDE"), ("B", "FGHI"), ("C", "FGHI")];
+        let mut protein_set = ProteinSet::with_options(proteins(&records), DuplicatePolicy::Error, true).unwrap();
 
+        let subset = protein_set.subset(&[1]);
+        assert_eq!(names(&subset), vec!["B"]);
+        assert_eq!(subset.names(0), vec!["B", "C"]);
+        assert_eq!(subset.get_protein_by_name("C").unwrap().index(), 0);
+        assert!(subset.get_protein_by_name("A").is_err());
+
+        // A new protein cannot take the name of an alias
+        match protein_set.add_new(proteins(&[("C", "KLMN")]), true) {
+            Err(SprintError::DuplicateProtein(name)) => assert_eq!(name, "C"),
+            _ => panic!("expected a duplicate protein"),
+        }
+        protein_set.add_new(proteins(&[("D", "KLMN")]), true).unwrap();
+        assert_eq!(protein_set.get_protein_by_name("D").unwrap().index(), 2);
+        assert!(protein_set.is_new(2));
+    }
+}
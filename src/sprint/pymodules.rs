@@ -1,16 +1,46 @@
 use std::collections::{HashSet, HashMap};
 
 use numpy::ToPyArray;
-use numpy::{PyArray2};
+use numpy::{PyArray2, PyReadonlyArray2};
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyDict};
 use pyo3::pymodule;
 
-use crate::sprint::hsp::HSP;
-use crate::sprint::prediction::score_interactions;
+use crate::sprint::checkpoint::Checkpoint;
+use crate::sprint::crossvalidation::{cross_validate, CrossValidationSettings};
+use crate::sprint::distributed::Worker;
+use crate::sprint::error::SprintError;
+use crate::sprint::evaluation::{evaluate, label_pairs, ordered_pair, Evaluation};
+use crate::sprint::evolution::{evolution_manifest, evolve_peptides, EvolutionSettings};
+use crate::sprint::explanation::{explain_pair, SupportingHsp};
+use crate::sprint::hsp::{NamedHsp, HSP};
+use crate::sprint::model::SprintModel;
+use crate::sprint::mutagenesis::PeptideScorer;
+use crate::sprint::negatives::sample_negatives;
+use crate::sprint::normalization::{postprocess_scores, Normalization};
+use crate::sprint::prediction::{map_queries, missing_pairs, one_vs_all_queries, score_interactions, score_interactions_into, score_pairs, ScoringSettings};
+use crate::sprint::scorematrix::ScoreMatrix;
 
-use super::{proteinset::ProteinSet, protein::Protein, extraction::extract_hsps};
+use super::{proteinset::ProteinSet, protein::Protein, extraction::{extract_hsps, extract_hsps_to_sink, ExtractionSettings}};
+use super::headers::HeaderFormat;
+use super::hspfile::HspParameters;
+use super::sink::{CallbackSink, ShardedHspWriter};
 use super::{processing::process_hsps};
-use super::{sites::compute_contributions};
+use super::sites::{
+    binding_segments, compute_contributions, interface_propensity, normalize_profile, pair_profiles, smooth_profile,
+    ProfileNormalization, ResidueWeighting,
+};
+use super::sanitization::{sanitize_proteins, SequencePolicy, ValidationReport};
+use super::substitution::SubstitutionMatrix;
+use super::seed::{Seed, default_seeds};
+
+/// Pair held out by a cross-validation: (fold, protein 1, protein 2, whether
+/// it is a training pair, score)
+type HeldOutTuple = (usize, String, String, bool, f32);
+
+/// Sanitized (name, sequence) pairs and the report of their changes
+type SanitizedTuples = (Vec<(String, String)>, Vec<PyObject>);
 
 #[pymodule]
 fn sprint(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
@@ -20,33 +50,30 @@ fn sprint(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
         world_size = "1",
         t_smer = "15",
         t_hsp = "35",
-        kmer_size = "20"
+        kmer_size = "20",
+        matrix = "\"PAM120\"",
+        seeds = "None",
+        residues = "None"
     )]
     #[pyo3(name = "extract_hsps")]
+    #[allow(clippy::too_many_arguments)]
     pub fn extract_hsps_py(
         proteins: Vec<(String, String)>,
         process_rank:usize,
         world_size: usize,
         t_smer: i16,
         t_hsp: i16,
-        kmer_size: usize
-    ) -> PyResult<HashSet<(String, String, usize, usize, usize)>> {
-        
-        let protein_set = ProteinSet::new(
-            convert_tuples_to_proteins(proteins, true)
-        );
+        kmer_size: usize,
+        matrix: &str,
+        seeds: Option<Vec<String>>,
+        residues: Option<&str>
+    ) -> PyResult<HashSet<NamedHsp>> {
+        let (protein_set, _, matrix) = load_inputs(proteins, HashSet::new(), residues, matrix, true)?;
+        let seeds = convert_strings_to_seeds(seeds)?;
 
-        let hsps = extract_hsps(
-            &protein_set,
-            kmer_size,
-            t_smer,
-            t_hsp,
-            process_rank,
-            world_size,
-            false,
-            true,
-            false
-        );
+        let settings = ExtractionSettings { kmer_size, t_sim: t_smer, t_hit: t_hsp, matrix: &matrix, seeds: &seeds };
+        let worker = Worker { rank: process_rank, world_size, verbose: false };
+        let hsps = extract_hsps(&protein_set, &settings, worker, false, true);
 
         Ok(hsps
             .iter()
@@ -59,9 +86,13 @@ fn sprint(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
         world_size = "1",
         t_smer = "15",
         t_hsp = "35",
-        kmer_size = "20"
+        kmer_size = "20",
+        matrix = "\"PAM120\"",
+        seeds = "None",
+        residues = "None"
     )]
     #[pyo3(name = "extract_peptide_hsps")]
+    #[allow(clippy::too_many_arguments)]
     pub fn extract_peptide_hsps_py(
         proteins: Vec<(String, String)>,
         peptides: Vec<(String, String)>,
@@ -69,29 +100,17 @@ fn sprint(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
         world_size: usize,
         t_smer: i16,
         t_hsp: i16,
-        kmer_size: usize
-    ) -> PyResult<HashSet<(String, String, usize, usize, usize)>> {
-        
-        let mut protein_set = ProteinSet::new(
-            convert_tuples_to_proteins(proteins, false)
-        );
+        kmer_size: usize,
+        matrix: &str,
+        seeds: Option<Vec<String>>,
+        residues: Option<&str>
+    ) -> PyResult<HashSet<NamedHsp>> {
+        let (protein_set, _, matrix) = load_peptide_inputs(proteins, peptides, HashSet::new(), residues, matrix)?;
+        let seeds = convert_strings_to_seeds(seeds)?;
 
-        protein_set.add_new(
-            convert_tuples_to_proteins(peptides, true),
-            true
-        );
-
-        let hsps = extract_hsps(
-            &protein_set,
-            kmer_size,
-            t_smer,
-            t_hsp,
-            process_rank,
-            world_size,
-            true,
-            true,
-            false
-        );
+        let settings = ExtractionSettings { kmer_size, t_sim: t_smer, t_hit: t_hsp, matrix: &matrix, seeds: &seeds };
+        let worker = Worker { rank: process_rank, world_size, verbose: false };
+        let hsps = extract_hsps(&protein_set, &settings, worker, true, true);
 
         Ok(hsps
             .iter()
@@ -99,28 +118,132 @@ fn sprint(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
             .collect())
     }
 
+    #[pyfunction(
+        process_rank = "0",
+        world_size = "1",
+        t_smer = "15",
+        t_hsp = "35",
+        kmer_size = "20",
+        matrix = "\"PAM120\"",
+        seeds = "None",
+        binary = "false",
+        batch_size = "10000",
+        shards = "64",
+        residues = "None"
+    )]
+    #[pyo3(name = "extract_hsps_to_file")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn extract_hsps_to_file_py(
+        proteins: Vec<(String, String)>,
+        output: &str,
+        process_rank:usize,
+        world_size: usize,
+        t_smer: i16,
+        t_hsp: i16,
+        kmer_size: usize,
+        matrix: &str,
+        seeds: Option<Vec<String>>,
+        binary: bool,
+        batch_size: usize,
+        shards: usize,
+        residues: Option<&str>
+    ) -> PyResult<()> {
+        let (protein_set, _, matrix) = load_inputs(proteins, HashSet::new(), residues, matrix, true)?;
+        let seeds = convert_strings_to_seeds(seeds)?;
+
+        let settings = ExtractionSettings { kmer_size, t_sim: t_smer, t_hit: t_hsp, matrix: &matrix, seeds: &seeds };
+        let worker = Worker { rank: process_rank, world_size, verbose: false };
+
+        let mut writer = ShardedHspWriter::new(output, protein_set.len(), shards)?;
+        extract_hsps_to_sink(&protein_set, &settings, worker, false, true, batch_size, &mut writer)?;
+
+        if binary {
+            let parameters = HspParameters {
+                kmer_size,
+                t_sim: Some(t_smer),
+                t_hsp: Some(t_hsp),
+                t_count: None,
+                matrix: Some(matrix.name()),
+                seeds: seeds.iter().map(|seed| seed.as_string()).collect(),
+            };
+            writer.finish_binary(&protein_set, &parameters)?;
+        } else {
+            writer.finish_text(&protein_set)?;
+        }
+        Ok(())
+    }
+
+    #[pyfunction(
+        process_rank = "0",
+        world_size = "1",
+        t_smer = "15",
+        t_hsp = "35",
+        kmer_size = "20",
+        matrix = "\"PAM120\"",
+        seeds = "None",
+        batch_size = "10000",
+        residues = "None"
+    )]
+    #[pyo3(name = "stream_hsps")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn stream_hsps_py(
+        py: Python<'_>,
+        proteins: Vec<(String, String)>,
+        callback: PyObject,
+        process_rank:usize,
+        world_size: usize,
+        t_smer: i16,
+        t_hsp: i16,
+        kmer_size: usize,
+        matrix: &str,
+        seeds: Option<Vec<String>>,
+        batch_size: usize,
+        residues: Option<&str>
+    ) -> PyResult<()> {
+        let (protein_set, _, matrix) = load_inputs(proteins, HashSet::new(), residues, matrix, true)?;
+        let seeds = convert_strings_to_seeds(seeds)?;
+
+        let settings = ExtractionSettings { kmer_size, t_sim: t_smer, t_hit: t_hsp, matrix: &matrix, seeds: &seeds };
+        let worker = Worker { rank: process_rank, world_size, verbose: false };
+
+        // The batches may contain HSPs already passed to the callback
+        let mut callback_error: Option<PyErr> = None;
+        let mut sink = CallbackSink(|hsps: Vec<HSP>| {
+            let batch: Vec<NamedHsp> = hsps
+                .iter()
+                .map(|hsp| hsp.to_named_tuple(&protein_set))
+                .collect();
+            callback.call1(py, (batch,)).map(|_| ()).map_err(|error| {
+                callback_error = Some(error);
+                std::io::Error::new(std::io::ErrorKind::Interrupted, "The HSP callback failed.")
+            })
+        });
+
+        let result = extract_hsps_to_sink(&protein_set, &settings, worker, false, true, batch_size, &mut sink);
+
+        match callback_error {
+            Some(error) => Err(error),
+            None => Ok(result?)
+        }
+    }
+
     #[pyfunction(
         kmer_size = "20",
         t_count = "40",
-        verbose = "false"
+        verbose = "false",
+        residues = "None"
     )]
     #[pyo3(name = "process_hsps")]
     pub fn process_hsps_py(
         proteins: Vec<(String, String)>,
-        hsps: HashSet<(String, String, usize, usize, usize)>,
+        hsps: HashSet<NamedHsp>,
         kmer_size: usize,
         t_count: u16,
-        verbose: bool
-    ) -> PyResult<HashSet<(String, String, usize, usize, usize)>> {
-
-        let protein_set = ProteinSet::new(
-            convert_tuples_to_proteins(proteins, true)
-        );
-
-        let parsed_hsps: HashSet<HSP> = hsps
-            .into_iter()
-            .map(|hsp| HSP::from_named_tuple(hsp, &protein_set))
-            .collect();
+        verbose: bool,
+        residues: Option<&str>
+    ) -> PyResult<HashSet<NamedHsp>> {
+        let protein_set = load_proteins(proteins, None, residues, true)?;
+        let parsed_hsps = convert_hsps(hsps, &protein_set)?;
         let hsps = process_hsps(&protein_set, parsed_hsps, kmer_size, t_count, verbose);
 
         Ok(hsps
@@ -129,149 +252,874 @@ fn sprint(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
             .collect())
 }
 
+    /// Scores all the protein pairs
+    ///
+    /// Returns the full (symmetric) score matrix, or only its lower triangle
+    /// (row by row) if `packed` is true. If `scores_file` is given, the
+    /// scores are kept in this file and the packed lower triangle is returned
+    /// as a read-only `numpy.memmap` of the file.
+    ///
+    /// `normalization` is one of "none", "zscore", "rank" or "degree", and
+    /// `calibration` ("platt" or "isotonic") maps the scores to interaction
+    /// probabilities fitted on the `positives` and `negatives` pairs.
     #[pyfunction(
         kmer_size = "20",
         process_rank = "0",
-        world_size = "1"
+        world_size = "1",
+        matrix = "\"PAM120\"",
+        packed = "false",
+        scores_file = "None",
+        normalization = "\"none\"",
+        calibration = "None",
+        positives = "Vec::new()",
+        negatives = "Vec::new()",
+        residues = "None"
     )]
     #[pyo3(name = "score_interactions")]
-    pub fn score_py<'py>(
-        py: Python<'py>,
+    #[allow(clippy::too_many_arguments)]
+    pub fn score_py(
+        py: Python<'_>,
+        proteins: Vec<(String, String)>,
+        hsps: HashSet<NamedHsp>,
+        training_pairs: Vec<(String, String)>,
+        kmer_size: usize,
+        process_rank:usize,
+        world_size: usize,
+        matrix: &str,
+        packed: bool,
+        scores_file: Option<&str>,
+        normalization: &str,
+        calibration: Option<&str>,
+        positives: Vec<(String, String)>,
+        negatives: Vec<(String, String)>,
+        residues: Option<&str>
+    ) -> PyResult<PyObject> {
+        let normalization = Normalization::from_name(normalization)?;
+
+        let (protein_set, parsed_hsps, matrix) = load_inputs(proteins, hsps, residues, matrix, true)?;
+
+        let mut scores = score_interactions_into(
+            &protein_set,
+            &parsed_hsps,
+            &training_pairs,
+            &ScoringSettings { kmer_size, matrix: &matrix },
+            Worker { rank: process_rank, world_size, verbose: false },
+            ScoreMatrix::allocate(protein_set.len(), scores_file)?,
+            None,
+        )?;
+
+        postprocess_scores(
+            &mut scores,
+            &protein_set,
+            &training_pairs,
+            normalization,
+            calibration,
+            &positives,
+            &negatives
+        )?;
+
+        if let Some(scores_file) = scores_file {
+            scores.flush()?;
+            drop(scores);
+            let kwargs = [("dtype", "float32"), ("mode", "r")].into_py_dict(py);
+            let memmap = py.import("numpy")?.getattr("memmap")?.call((scores_file,), Some(kwargs))?;
+            return Ok(memmap.to_object(py));
+        }
+
+        if packed {
+            Ok(scores.values().to_pyarray(py).to_object(py))
+        } else {
+            Ok(scores.to_array().to_pyarray(py).to_object(py))
+        }
+    }
+
+    /// Scores only some protein pairs: the pairs of `queries`, or the pairs
+    /// of `protein` with every protein
+    ///
+    /// Returns a list of (protein1, protein2, score) tuples.
+    #[pyfunction(
+        queries = "None",
+        protein = "None",
+        kmer_size = "20",
+        process_rank = "0",
+        world_size = "1",
+        matrix = "\"PAM120\"",
+        residues = "None"
+    )]
+    #[pyo3(name = "score_pairs")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn score_pairs_py(
         proteins: Vec<(String, String)>,
-        hsps: HashSet<(String, String, usize, usize, usize)>,
+        hsps: HashSet<NamedHsp>,
         training_pairs: Vec<(String, String)>,
+        queries: Option<Vec<(String, String)>>,
+        protein: Option<String>,
         kmer_size: usize,
         process_rank:usize,
-        world_size: usize
-    ) -> &'py PyArray2<f32> {
-        
-        let protein_set = ProteinSet::new(
-            convert_tuples_to_proteins(proteins, true)
+        world_size: usize,
+        matrix: &str,
+        residues: Option<&str>
+    ) -> PyResult<Vec<(String, String, f32)>> {
+        let (protein_set, parsed_hsps, matrix) = load_inputs(proteins, hsps, residues, matrix, true)?;
+
+        let queries = match (queries, protein) {
+            (Some(queries), None) => map_queries(&protein_set, &queries)?,
+            (None, Some(protein)) => one_vs_all_queries(&protein_set, protein_set.get_protein_by_name(&protein)?.index()),
+            _ => return Err(PyValueError::new_err("Either queries or protein is required (but not both).")),
+        };
+
+        let scores = score_pairs(
+            &protein_set,
+            &parsed_hsps,
+            &training_pairs,
+            &queries,
+            &ScoringSettings { kmer_size, matrix: &matrix },
+            Worker { rank: process_rank, world_size, verbose: false },
         );
 
-        let parsed_hsps: HashSet<HSP> = hsps
-            .into_iter()
-            .map(|h| HSP::from_named_tuple(h, &protein_set))
-            .collect();
+        Ok(queries
+            .iter()
+            .zip(scores)
+            .map(|(query, score)| (
+                protein_set.get_protein_by_id(query.0).name(),
+                protein_set.get_protein_by_id(query.1).name(),
+                score
+            ))
+            .collect())
+    }
 
-        let matrix = score_interactions(
+    /// Explains the score of two proteins: the training pairs and the pairs
+    /// of HSPs that contribute to it, from the highest contribution to the
+    /// lowest
+    ///
+    /// Returns a dictionary with the `score` of the pair and its `supports`
+    /// (dictionaries with the training pair, the contribution and the HSPs of
+    /// `protein1` and `protein2` with the proteins of the training pair).
+    #[pyfunction(
+        kmer_size = "20",
+        matrix = "\"PAM120\"",
+        residues = "None"
+    )]
+    #[pyo3(name = "explain")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn explain_py(
+        py: Python<'_>,
+        proteins: Vec<(String, String)>,
+        hsps: HashSet<NamedHsp>,
+        training_pairs: Vec<(String, String)>,
+        protein1: &str,
+        protein2: &str,
+        kmer_size: usize,
+        matrix: &str,
+        residues: Option<&str>
+    ) -> PyResult<PyObject> {
+        let (protein_set, parsed_hsps, matrix) = load_inputs(proteins, hsps, residues, matrix, true)?;
+
+        let explanation = explain_pair(
             &protein_set,
             &parsed_hsps,
             &training_pairs,
+            protein1,
+            protein2,
             kmer_size,
-            process_rank,
-            world_size,
+            &matrix
+        )?;
+
+        let name = |index: usize| protein_set.get_protein_by_id(index).name();
+        let hsp_to_dict = |hsp: &SupportingHsp| -> PyResult<PyObject> {
+            let dict = PyDict::new(py);
+            dict.set_item("protein", name(hsp.protein))?;
+            dict.set_item("partner", name(hsp.partner))?;
+            dict.set_item("position", hsp.position)?;
+            dict.set_item("partner_position", hsp.partner_position)?;
+            dict.set_item("length", hsp.length)?;
+            dict.set_item("score", hsp.score)?;
+            Ok(dict.to_object(py))
+        };
+
+        let supports = explanation
+            .supports
+            .iter()
+            .map(|support| {
+                let dict = PyDict::new(py);
+                dict.set_item("training_pair", (name(support.training_pair.0), name(support.training_pair.1)))?;
+                dict.set_item("contribution", support.contribution)?;
+                dict.set_item("hsp1", hsp_to_dict(&support.hsp1)?)?;
+                dict.set_item("hsp2", hsp_to_dict(&support.hsp2)?)?;
+                Ok(dict.to_object(py))
+            })
+            .collect::<PyResult<Vec<PyObject>>>()?;
+
+        let dict = PyDict::new(py);
+        dict.set_item("protein1", name(explanation.protein1))?;
+        dict.set_item("protein2", name(explanation.protein2))?;
+        dict.set_item("score", explanation.score)?;
+        dict.set_item("supports", supports)?;
+        Ok(dict.to_object(py))
+    }
+
+    /// Per-residue contributions to the score of two proteins, on both
+    /// proteins
+    ///
+    /// `weighting` ("uniform" or "substitution") distributes the
+    /// contributions over the similarity regions, `window` is the size of
+    /// the moving average smoothing the profiles and `normalization` is one
+    /// of "none", "sum", "max" or "zscore". Returns the profiles of
+    /// `protein1` and `protein2`.
+    #[pyfunction(
+        weighting = "\"uniform\"",
+        window = "1",
+        normalization = "\"none\"",
+        kmer_size = "20",
+        matrix = "\"PAM120\"",
+        residues = "None"
+    )]
+    #[pyo3(name = "pair_profiles")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn pair_profiles_py(
+        proteins: Vec<(String, String)>,
+        hsps: HashSet<NamedHsp>,
+        training_pairs: Vec<(String, String)>,
+        protein1: &str,
+        protein2: &str,
+        weighting: &str,
+        window: usize,
+        normalization: &str,
+        kmer_size: usize,
+        matrix: &str,
+        residues: Option<&str>
+    ) -> PyResult<(Vec<f32>, Vec<f32>)> {
+        let weighting = ResidueWeighting::from_name(weighting)?;
+        let normalization = ProfileNormalization::from_name(normalization)?;
+
+        let (protein_set, parsed_hsps, matrix) = load_inputs(proteins, hsps, residues, matrix, true)?;
+
+        let (mut profile1, mut profile2) = pair_profiles(
+            &protein_set, &parsed_hsps, &training_pairs, protein1, protein2, &ScoringSettings { kmer_size, matrix: &matrix }, weighting)?;
+        for profile in [&mut profile1, &mut profile2] {
+            smooth_profile(profile, window);
+            normalize_profile(profile, normalization);
+        }
+
+        Ok((profile1, profile2))
+    }
+
+    /// Interface propensity of a protein: the sum of its per-residue profiles
+    /// with every other protein, weighted, smoothed and normalized like in
+    /// `pair_profiles`
+    #[pyfunction(
+        weighting = "\"uniform\"",
+        window = "1",
+        normalization = "\"none\"",
+        kmer_size = "20",
+        matrix = "\"PAM120\"",
+        residues = "None"
+    )]
+    #[pyo3(name = "interface_propensity")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn interface_propensity_py(
+        proteins: Vec<(String, String)>,
+        hsps: HashSet<NamedHsp>,
+        training_pairs: Vec<(String, String)>,
+        protein: &str,
+        weighting: &str,
+        window: usize,
+        normalization: &str,
+        kmer_size: usize,
+        matrix: &str,
+        residues: Option<&str>
+    ) -> PyResult<Vec<f32>> {
+        let weighting = ResidueWeighting::from_name(weighting)?;
+        let normalization = ProfileNormalization::from_name(normalization)?;
+
+        let (protein_set, parsed_hsps, matrix) = load_inputs(proteins, hsps, residues, matrix, true)?;
+
+        let mut propensity = interface_propensity(
+            &protein_set, &parsed_hsps, &training_pairs, protein, &ScoringSettings { kmer_size, matrix: &matrix }, weighting, false)?;
+        smooth_profile(&mut propensity, window);
+        normalize_profile(&mut propensity, normalization);
+
+        Ok(propensity)
+    }
+
+    /// Predicted binding segments of a per-residue profile: the runs of
+    /// residues above the mean of the profile with the highest sums
+    ///
+    /// Returns at most `count` (start, end, score) tuples (the ends are
+    /// excluded).
+    #[pyfunction(count = "5")]
+    #[pyo3(name = "binding_segments")]
+    pub fn binding_segments_py(profile: Vec<f32>, count: usize) -> Vec<(usize, usize, f32)> {
+        binding_segments(&profile, count)
+            .into_iter()
+            .map(|segment| (segment.start, segment.end, segment.score))
+            .collect()
+    }
+
+    /// Cross-validation: scores every training pair using only the training
+    /// pairs of the other folds, along with random negative pairs
+    ///
+    /// `folds=None` holds out every pair on its own (leave-one-out). Returns a
+    /// list of (fold, protein1, protein2, is_training_pair, score) tuples.
+    #[pyfunction(
+        folds = "10",
+        negatives = "1",
+        degree_preserving = "false",
+        random_seed = "0",
+        kmer_size = "20",
+        matrix = "\"PAM120\"",
+        residues = "None"
+    )]
+    #[pyo3(name = "cross_validate")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn cross_validate_py(
+        proteins: Vec<(String, String)>,
+        hsps: HashSet<NamedHsp>,
+        training_pairs: Vec<(String, String)>,
+        folds: Option<usize>,
+        negatives: usize,
+        degree_preserving: bool,
+        random_seed: u64,
+        kmer_size: usize,
+        matrix: &str,
+        residues: Option<&str>
+    ) -> PyResult<Vec<HeldOutTuple>> {
+        let (protein_set, parsed_hsps, matrix) = load_inputs(proteins, hsps, residues, matrix, false)?;
+
+        let results = cross_validate(
+            &protein_set,
+            &parsed_hsps,
+            &training_pairs,
+            &CrossValidationSettings {
+                folds: folds.unwrap_or(training_pairs.len()),
+                negatives_per_positive: negatives,
+                degree_preserving,
+                random_seed,
+            },
+            &ScoringSettings { kmer_size, matrix: &matrix },
             false
         );
 
-        matrix.to_pyarray(py)
+        Ok(results
+            .into_iter()
+            .map(|pair| (
+                pair.fold,
+                protein_set.get_protein_by_id(pair.protein1).name(),
+                protein_set.get_protein_by_id(pair.protein2).name(),
+                pair.positive,
+                pair.score
+            ))
+            .collect())
+    }
+
+    /// Samples random negative pairs (pairs of distinct proteins that are not
+    /// positive pairs), `ratio` per positive pair
+    ///
+    /// With `interactors_only`, only the proteins of the positive pairs are
+    /// used, and with `degree_preserving`, the proteins are drawn in
+    /// proportion to their number of positive pairs.
+    #[pyfunction(
+        ratio = "1.0",
+        interactors_only = "false",
+        degree_preserving = "false",
+        random_seed = "0",
+        residues = "None"
+    )]
+    #[pyo3(name = "sample_negatives")]
+    pub fn sample_negatives_py(
+        proteins: Vec<(String, String)>,
+        positives: Vec<(String, String)>,
+        ratio: f32,
+        interactors_only: bool,
+        degree_preserving: bool,
+        random_seed: u64,
+        residues: Option<&str>
+    ) -> PyResult<Vec<(String, String)>> {
+        let protein_set = load_proteins(proteins, None, residues, false)?;
+
+        Ok(sample_negatives(&protein_set, &positives, ratio, interactors_only, degree_preserving, random_seed))
+    }
+
+    /// Evaluates scores, given as (protein1, protein2, score) tuples, against
+    /// positive and negative pairs
+    ///
+    /// Returns a dictionary with the AUROC, the AUPR, the precision at the
+    /// `top_k` best pairs and at the given `recalls`, and the points of the
+    /// ROC and precision-recall curves.
+    #[pyfunction(
+        top_k = "vec![10, 50, 100]",
+        recalls = "vec![0.1, 0.25, 0.5]"
+    )]
+    #[pyo3(name = "evaluate")]
+    pub fn evaluate_py(
+        py: Python<'_>,
+        scores: Vec<(String, String, f32)>,
+        positives: Vec<(String, String)>,
+        negatives: Vec<(String, String)>,
+        top_k: Vec<usize>,
+        recalls: Vec<f32>
+    ) -> PyResult<PyObject> {
+        let scores: HashMap<(String, String), f32> = scores
+            .into_iter()
+            .map(|(protein1, protein2, score)| (ordered_pair(&protein1, &protein2), score))
+            .collect();
+
+        let (labeled_scores, missing) = label_pairs(&positives, &negatives, |protein1, protein2| {
+            scores.get(&ordered_pair(protein1, protein2)).cloned()
+        });
+
+        evaluation_to_dict(py, &evaluate(&labeled_scores, &top_k, &recalls), missing)
+    }
+
+    /// Evaluates a full score matrix (e.g. returned by `score_interactions`)
+    /// whose rows and columns are the proteins `names`, like `evaluate`
+    #[pyfunction(
+        top_k = "vec![10, 50, 100]",
+        recalls = "vec![0.1, 0.25, 0.5]"
+    )]
+    #[pyo3(name = "evaluate_matrix")]
+    pub fn evaluate_matrix_py(
+        py: Python<'_>,
+        scores: PyReadonlyArray2<f32>,
+        names: Vec<String>,
+        positives: Vec<(String, String)>,
+        negatives: Vec<(String, String)>,
+        top_k: Vec<usize>,
+        recalls: Vec<f32>
+    ) -> PyResult<PyObject> {
+        let scores = scores.as_array();
+        if scores.nrows() != names.len() || scores.ncols() != names.len() {
+            return Err(PyValueError::new_err("The score matrix must have one row and one column per name."));
+        }
+        let indices: HashMap<&str, usize> = names.iter().enumerate().map(|(i, name)| (name.as_str(), i)).collect();
+
+        let (labeled_scores, missing) = label_pairs(&positives, &negatives, |protein1, protein2| {
+            match (indices.get(protein1), indices.get(protein2)) {
+                (Some(&i), Some(&j)) => Some(scores[[i, j]]),
+                _ => None,
+            }
+        });
+
+        evaluation_to_dict(py, &evaluate(&labeled_scores, &top_k, &recalls), missing)
     }
 
     #[pyfunction(
         kmer_size = "20",
         process_rank = "0",
-        world_size = "1"
+        world_size = "1",
+        matrix = "\"PAM120\"",
+        residues = "None"
     )]
     #[pyo3(name = "score_peptides")]
+    #[allow(clippy::too_many_arguments)]
     pub fn score_peptides_py<'py>(
         py: Python<'py>,
         proteins: Vec<(String, String)>,
         peptides: Vec<(String, String)>,
-        hsps: HashSet<(String, String, usize, usize, usize)>,
+        hsps: HashSet<NamedHsp>,
         training_pairs: Vec<(String, String)>,
         kmer_size: usize,
         process_rank:usize,
-        world_size: usize
-    ) -> &'py PyArray2<f32> {
-        
-        let mut protein_set = ProteinSet::new(
-            convert_tuples_to_proteins(proteins, false)
-        );
-
-        protein_set.add_new(
-            convert_tuples_to_proteins(peptides, true),
-            true
-        );
-
-        let parsed_hsps: HashSet<HSP> = hsps
-            .into_iter()
-            .map(|h| HSP::from_named_tuple(h, &protein_set))
-            .collect();
+        world_size: usize,
+        matrix: &str,
+        residues: Option<&str>
+    ) -> PyResult<&'py PyArray2<f32>> {
+        let (protein_set, parsed_hsps, matrix) = load_peptide_inputs(proteins, peptides, hsps, residues, matrix)?;
 
-        let matrix = score_interactions(
+        let scores = score_interactions(
             &protein_set,
             &parsed_hsps,
             &training_pairs,
-            kmer_size,
-            process_rank,
-            world_size,
-            false
+            &ScoringSettings { kmer_size, matrix: &matrix },
+            Worker { rank: process_rank, world_size, verbose: false },
         );
 
-        matrix.to_pyarray(py)
+        Ok(scores.to_array().to_pyarray(py))
 
     }
 
+    /// Evolves a population of peptides toward a high score with the target
+    /// and low scores with the decoys (see `evolution::evolve_peptides`),
+    /// with the given `EvolutionSettings` (or the default ones)
+    ///
+    /// Returns the last population as (sequence, fitness, score, off-target
+    /// score) tuples, from the fittest peptide to the least fit. With a
+    /// checkpoint directory, the population of every generation is saved and
+    /// an interrupted run resumes from it.
+    #[pyfunction(
+        decoys = "Vec::new()",
+        initial = "Vec::new()",
+        length = "15",
+        settings = "None",
+        checkpoint = "None",
+        t_smer = "15",
+        t_hsp = "35",
+        kmer_size = "20",
+        matrix = "\"PAM120\"",
+        seeds = "None",
+        residues = "None"
+    )]
+    #[pyo3(name = "optimize_peptides")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn optimize_peptides_py(
+        proteins: Vec<(String, String)>,
+        hsps: HashSet<NamedHsp>,
+        training_pairs: Vec<(String, String)>,
+        target: String,
+        decoys: Vec<String>,
+        initial: Vec<String>,
+        length: usize,
+        settings: Option<EvolutionSettings>,
+        checkpoint: Option<String>,
+        t_smer: i16,
+        t_hsp: i16,
+        kmer_size: usize,
+        matrix: &str,
+        seeds: Option<Vec<String>>,
+        residues: Option<&str>
+    ) -> PyResult<Vec<(String, f32, f32, f32)>> {
+        let (protein_set, parsed_hsps, matrix) = load_inputs(proteins, hsps, residues, matrix, false)?;
+        let seeds = convert_strings_to_seeds(seeds)?;
+
+        // The target first, then the decoys
+        let mut targets = vec![target];
+        targets.extend(decoys);
+
+        let extraction = ExtractionSettings { kmer_size, t_sim: t_smer, t_hit: t_hsp, matrix: &matrix, seeds: &seeds };
+        let scorer = PeptideScorer::new(&protein_set, &parsed_hsps, &training_pairs, &targets, extraction)?;
+
+        let settings = settings.unwrap_or_default();
+
+        let mut checkpoint = match checkpoint {
+            Some(directory) => Some(Checkpoint::open(&directory, &evolution_manifest(&scorer, &initial, length, &settings))?),
+            None => None,
+        };
+
+        let population = evolve_peptides(&scorer, &initial, length, &settings, checkpoint.as_mut(), false)?;
+
+        Ok(population
+            .into_iter()
+            .map(|candidate| (candidate.sequence, candidate.fitness, candidate.score, candidate.off_target))
+            .collect())
+    }
+
     #[pyfunction(
         kmer_size = "20",
         process_rank = "0",
-        world_size = "1"
+        world_size = "1",
+        matrix = "\"PAM120\"",
+        weighting = "\"uniform\"",
+        window = "1",
+        residues = "None"
     )]
     #[pyo3(name = "compute_contributions")]
+    #[allow(clippy::too_many_arguments)]
     pub fn compute_contributions_py(
         proteins: Vec<(String, String)>,
         peptides: Vec<(String, String)>,
-        hsps: HashSet<(String, String, usize, usize, usize)>,
+        hsps: HashSet<NamedHsp>,
         training_pairs: Vec<(String, String)>,
         target: String,
         kmer_size: usize,
         process_rank: usize,
-        world_size: usize
+        world_size: usize,
+        matrix: &str,
+        weighting: &str,
+        window: usize,
+        residues: Option<&str>
     ) -> PyResult<HashMap<String, Vec<f32>>> {
+        let weighting = ResidueWeighting::from_name(weighting)?;
 
-        let mut protein_set = ProteinSet::new(
-            convert_tuples_to_proteins(proteins, false)
-        );
-
-        protein_set.add_new(
-            convert_tuples_to_proteins(peptides, true),
-            true
-        );
-
-        let parsed_hsps: HashSet<HSP> = hsps
-            .into_iter()
-            .map(|h| HSP::from_named_tuple(h, &protein_set))
-            .collect();
+        let (protein_set, parsed_hsps, matrix) = load_peptide_inputs(proteins, peptides, hsps, residues, matrix)?;
 
         // Compute the contributions of residues within the target to the interaction score
         // for the peptides of interest (new)
         let contributions = compute_contributions(
-            &target, &protein_set, &parsed_hsps, &training_pairs, kmer_size, process_rank, world_size, false);
+            &target, &protein_set, &parsed_hsps, &training_pairs, &ScoringSettings { kmer_size, matrix: &matrix },
+            weighting, Worker { rank: process_rank, world_size, verbose: false })?;
 
         let named_contributions: HashMap<String, Vec<f32>> = contributions
             .into_iter()
-            .map(|(k, v)| (protein_set.get_protein_by_id(k).name(), v))
+            .map(|(k, mut v)| {
+                smooth_profile(&mut v, window);
+                (protein_set.get_protein_by_id(k).name(), v)
+            })
             .collect();
 
         Ok(named_contributions)
     }
 
+    #[pyfunction(residues = "None")]
+    #[pyo3(name = "sanitize_sequences")]
+    pub fn sanitize_sequences_py(
+        py: Python<'_>,
+        proteins: Vec<(String, String)>,
+        residues: Option<&str>
+    ) -> PyResult<SanitizedTuples> {
+        let policy = SequencePolicy::parse(residues.unwrap_or_default())?;
+        let (proteins, report) = sanitize_proteins(proteins, false, &policy)?;
+
+        Ok((
+            proteins.iter().map(|protein| (protein.name(), protein.seq())).collect(),
+            report_to_dicts(py, &report)?,
+        ))
+    }
+
+    /// ID of a protein given its FASTA header (without the '>'), in the
+    /// format of the `headers` option of the binaries (None if the header
+    /// does not have the expected format)
+    #[pyfunction(headers = "\"id\"")]
+    #[pyo3(name = "parse_header")]
+    pub fn parse_header_py(header: &str, headers: &str) -> PyResult<Option<String>> {
+        let headers = HeaderFormat::from_name(headers)?;
+        let header = header.trim_start_matches('>').trim();
+        let (id, description) = match header.split_once(char::is_whitespace) {
+            Some((id, description)) => (id, Some(description.trim_start())),
+            None => (header, None),
+        };
+        Ok(headers.extract(id, description))
+    }
+
+    /// Training pairs with proteins that are not among the proteins, and
+    /// these proteins
+    #[pyfunction]
+    #[pyo3(name = "missing_training_pairs")]
+    pub fn missing_training_pairs_py(
+        proteins: Vec<String>,
+        training_pairs: Vec<(String, String)>
+    ) -> (Vec<(String, String)>, Vec<String>) {
+        let names: HashSet<String> = proteins.into_iter().collect();
+        let missing = missing_pairs(|name| names.contains(name), &training_pairs);
+        (missing.pairs, missing.proteins)
+    }
+
     m.add_function(wrap_pyfunction!(extract_hsps_py, m)?)?;
     m.add_function(wrap_pyfunction!(extract_peptide_hsps_py, m)?)?;
+    m.add_function(wrap_pyfunction!(extract_hsps_to_file_py, m)?)?;
+    m.add_function(wrap_pyfunction!(stream_hsps_py, m)?)?;
     m.add_function(wrap_pyfunction!(process_hsps_py, m)?)?;
     m.add_function(wrap_pyfunction!(score_peptides_py, m)?)?;
+    m.add_function(wrap_pyfunction!(optimize_peptides_py, m)?)?;
     m.add_function(wrap_pyfunction!(score_py, m)?)?;
+    m.add_function(wrap_pyfunction!(score_pairs_py, m)?)?;
+    m.add_function(wrap_pyfunction!(explain_py, m)?)?;
+    m.add_function(wrap_pyfunction!(cross_validate_py, m)?)?;
+    m.add_function(wrap_pyfunction!(sample_negatives_py, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate_py, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate_matrix_py, m)?)?;
     m.add_function(wrap_pyfunction!(compute_contributions_py, m)?)?;
+    m.add_function(wrap_pyfunction!(pair_profiles_py, m)?)?;
+    m.add_function(wrap_pyfunction!(interface_propensity_py, m)?)?;
+    m.add_function(wrap_pyfunction!(binding_segments_py, m)?)?;
+    m.add_function(wrap_pyfunction!(sanitize_sequences_py, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_header_py, m)?)?;
+    m.add_function(wrap_pyfunction!(missing_training_pairs_py, m)?)?;
+    m.add_class::<PySprintModel>()?;
+    m.add_class::<EvolutionSettings>()?;
     Ok(())
 }
 
 
-fn convert_tuples_to_proteins(tuples: Vec<(String, String)>, new: bool) -> Vec<Protein> {
-    tuples
-        .into_iter() // TODO is it necessary to yield ownership?
-        .enumerate()
-        .map(|(i, p)| 
-            Protein::new(i, p.0, p.1, new)
-        )
-        .collect::<Vec<Protein>>()
+/// Proteins, processed HSPs and training pairs kept in memory to score new
+/// sequences (e.g. peptides) repeatedly, without rebuilding the protein set
+/// and the HSP table for every call
+#[pyclass(name = "SprintModel")]
+pub struct PySprintModel {
+    model: SprintModel,
+    /// Policy applied to the new sequences
+    policy: SequencePolicy,
+}
+
+#[pymethods]
+impl PySprintModel {
+    #[new]
+    #[args(
+        kmer_size = "20",
+        t_smer = "15",
+        t_hsp = "35",
+        matrix = "\"PAM120\"",
+        seeds = "None",
+        residues = "None"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        proteins: Vec<(String, String)>,
+        hsps: HashSet<NamedHsp>,
+        training_pairs: Vec<(String, String)>,
+        kmer_size: usize,
+        t_smer: i16,
+        t_hsp: i16,
+        matrix: &str,
+        seeds: Option<Vec<String>>,
+        residues: Option<&str>
+    ) -> PyResult<Self> {
+        let policy = SequencePolicy::parse(residues.unwrap_or_default())?;
+        let (protein_set, parsed_hsps, matrix) = load_inputs(proteins, hsps, residues, matrix, false)?;
+        let seeds = convert_strings_to_seeds(seeds)?;
+
+        let settings = ExtractionSettings { kmer_size, t_sim: t_smer, t_hit: t_hsp, matrix: &matrix, seeds: &seeds };
+
+        Ok(PySprintModel {
+            model: SprintModel::new(protein_set, &parsed_hsps, training_pairs, &settings, false),
+            policy,
+        })
+    }
+
+    /// Names of the proteins (the columns of the scores)
+    #[getter]
+    pub fn protein_names(&self) -> Vec<String> {
+        self.model.protein_set().iter().map(|protein| protein.name()).collect()
+    }
+
+    /// Scores of the new sequences (rows) with the proteins (columns)
+    pub fn score<'py>(&self, py: Python<'py>, sequences: Vec<(String, String)>) -> PyResult<&'py PyArray2<f32>> {
+        let scores = self.model
+            .score(&convert_tuples_to_proteins(sequences, true, &self.policy)?)?;
+
+        PyArray2::from_vec2(py, &scores)
+            .map_err(|error| PyValueError::new_err(error.to_string()))
+    }
+
+    /// Contributions of the residues of the target to its scores with the
+    /// new sequences, by sequence name
+    #[args(weighting = "\"uniform\"", window = "1")]
+    pub fn contributions(
+        &self,
+        sequences: Vec<(String, String)>,
+        target: &str,
+        weighting: &str,
+        window: usize
+    ) -> PyResult<HashMap<String, Vec<f32>>> {
+        let weighting = ResidueWeighting::from_name(weighting)?;
+
+        let sequences = convert_tuples_to_proteins(sequences, true, &self.policy)?;
+        let contributions = self.model
+            .contributions(&sequences, target, weighting)?;
+
+        Ok(sequences
+            .iter()
+            .zip(contributions)
+            .map(|(sequence, mut values)| {
+                smooth_profile(&mut values, window);
+                (sequence.name(), values)
+            })
+            .collect())
+    }
+
+    /// Adds training pairs (the pairs of unknown proteins are ignored)
+    pub fn add_training_pairs(&mut self, training_pairs: Vec<(String, String)>) {
+        self.model.add_training_pairs(&training_pairs);
+    }
+}
+
+fn evaluation_to_dict(py: Python<'_>, evaluation: &Evaluation, missing: usize) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("positives", evaluation.positives)?;
+    dict.set_item("negatives", evaluation.negatives)?;
+    dict.set_item("missing", missing)?;
+    dict.set_item("auroc", evaluation.auroc)?;
+    dict.set_item("aupr", evaluation.aupr)?;
+    dict.set_item("precision_at_k", evaluation.precision_at_k.iter().cloned().collect::<HashMap<usize, f32>>())?;
+    dict.set_item("precision_at_recall", evaluation.precision_at_recall.clone())?;
+    dict.set_item("thresholds", evaluation.curve.iter().map(|point| point.threshold).collect::<Vec<f32>>())?;
+    dict.set_item("precision", evaluation.curve.iter().map(|point| point.precision).collect::<Vec<f32>>())?;
+    dict.set_item("recall", evaluation.curve.iter().map(|point| point.recall).collect::<Vec<f32>>())?;
+    dict.set_item("false_positive_rate", evaluation.curve.iter().map(|point| point.false_positive_rate).collect::<Vec<f32>>())?;
+    Ok(dict.to_object(py))
+}
+
+/// Sanitizes the proteins into a protein set, the peptides (if any) being
+/// added to it as new proteins
+fn load_proteins(
+    proteins: Vec<(String, String)>,
+    peptides: Option<Vec<(String, String)>>,
+    residues: Option<&str>,
+    new: bool
+) -> PyResult<ProteinSet> {
+    let policy = SequencePolicy::parse(residues.unwrap_or_default())?;
+    let mut protein_set = ProteinSet::new(convert_tuples_to_proteins(proteins, new, &policy)?)?;
+    if let Some(peptides) = peptides {
+        protein_set.add_new(convert_tuples_to_proteins(peptides, true, &policy)?, true)?;
+    }
+    Ok(protein_set)
+}
+
+/// Converts (protein1, protein2, position1, position2, length) tuples to HSPs
+fn convert_hsps(
+    hsps: HashSet<NamedHsp>,
+    protein_set: &ProteinSet
+) -> PyResult<HashSet<HSP>> {
+    Ok(hsps
+        .into_iter()
+        .map(|hsp| HSP::from_named_tuple(hsp, protein_set))
+        .collect::<Result<_, SprintError>>()?)
+}
+
+/// Loads the protein set, its HSPs and the substitution matrix given to a
+/// function
+fn load_inputs(
+    proteins: Vec<(String, String)>,
+    hsps: HashSet<NamedHsp>,
+    residues: Option<&str>,
+    matrix: &str,
+    new: bool
+) -> PyResult<(ProteinSet, HashSet<HSP>, SubstitutionMatrix)> {
+    let protein_set = load_proteins(proteins, None, residues, new)?;
+    let hsps = convert_hsps(hsps, &protein_set)?;
+    Ok((protein_set, hsps, SubstitutionMatrix::load(matrix)?))
+}
+
+/// Loads the inputs like `load_inputs`, with peptides added to the proteins
+/// (the HSPs may involve them)
+fn load_peptide_inputs(
+    proteins: Vec<(String, String)>,
+    peptides: Vec<(String, String)>,
+    hsps: HashSet<NamedHsp>,
+    residues: Option<&str>,
+    matrix: &str
+) -> PyResult<(ProteinSet, HashSet<HSP>, SubstitutionMatrix)> {
+    let protein_set = load_proteins(proteins, Some(peptides), residues, false)?;
+    let hsps = convert_hsps(hsps, &protein_set)?;
+    Ok((protein_set, hsps, SubstitutionMatrix::load(matrix)?))
+}
+
+/// Sanitizes the sequences (the records dropped by the policy are left out)
+fn convert_tuples_to_proteins(
+    tuples: Vec<(String, String)>,
+    new: bool,
+    policy: &SequencePolicy
+) -> Result<Vec<Protein>, SprintError> {
+    let (proteins, _) = sanitize_proteins(tuples, new, policy)?;
+    Ok(proteins)
+}
+
+fn report_to_dicts(py: Python<'_>, report: &ValidationReport) -> PyResult<Vec<PyObject>> {
+    report
+        .entries
+        .iter()
+        .map(|entry| {
+            let dict = PyDict::new(py);
+            dict.set_item("name", &entry.name)?;
+            dict.set_item("dropped", entry.dropped)?;
+            dict.set_item(
+                "residues",
+                entry.residues
+                    .iter()
+                    .map(|change| (change.position, change.residue, change.policy.name()))
+                    .collect::<Vec<(usize, char, &str)>>(),
+            )?;
+            Ok(dict.to_object(py))
+        })
+        .collect()
+}
+
+fn convert_strings_to_seeds(seeds: Option<Vec<String>>) -> PyResult<Vec<Seed>> {
+    match seeds {
+        Some(seeds) if seeds.is_empty() => Err(PyValueError::new_err("At least one seed is required.")),
+        Some(seeds) => Ok(seeds
+            .iter()
+            .map(|seed| Seed::new(seed))
+            .collect::<Result<Vec<Seed>, _>>()?),
+        None => Ok(default_seeds())
+    }
 }
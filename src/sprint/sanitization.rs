@@ -0,0 +1,453 @@
+use std::fmt;
+
+use crate::sprint::constants::{B_CODE, MASKED_CODE, RESIDUE_CODES, SUBSTITUTED_CODE, Z_CODE};
+use crate::sprint::error::SprintError;
+use crate::sprint::protein::Protein;
+
+/// Classes of the characters of a sequence that are not standard amino acids
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResidueClass {
+    /// B (D or N) and Z (E or Q)
+    Ambiguous,
+    /// X
+    Unknown,
+    /// U (selenocysteine) and O (pyrrolysine)
+    Rare,
+    /// Any other character (e.g. '*', '-', digits or whitespace)
+    Invalid,
+}
+
+impl ResidueClass {
+    pub const ALL: [ResidueClass; 4] =
+        [ResidueClass::Ambiguous, ResidueClass::Unknown, ResidueClass::Rare, ResidueClass::Invalid];
+
+    /// Class of a character (None for the standard amino acids, in either case)
+    pub fn of(residue: char) -> Option<Self> {
+        match residue.to_ascii_uppercase() {
+            'A' | 'R' | 'N' | 'D' | 'C' | 'Q' | 'E' | 'G' | 'H' | 'I' | 'L' | 'K' | 'M' | 'F' | 'P' | 'S' | 'T'
+            | 'W' | 'Y' | 'V' => None,
+            'B' | 'Z' => Some(ResidueClass::Ambiguous),
+            'X' => Some(ResidueClass::Unknown),
+            'U' | 'O' => Some(ResidueClass::Rare),
+            _ => Some(ResidueClass::Invalid),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, SprintError> {
+        match name.to_lowercase().as_str() {
+            "ambiguous" => Ok(ResidueClass::Ambiguous),
+            "unknown" => Ok(ResidueClass::Unknown),
+            "rare" => Ok(ResidueClass::Rare),
+            "invalid" => Ok(ResidueClass::Invalid),
+            _ => Err(SprintError::invalid_input(format!(
+                "Unknown residue class: {} (expected ambiguous, unknown, rare, invalid or all)",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResidueClass::Ambiguous => "ambiguous",
+            ResidueClass::Unknown => "unknown",
+            ResidueClass::Rare => "rare",
+            ResidueClass::Invalid => "invalid",
+        }
+    }
+}
+
+/// What to do with the residues of a class
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResiduePolicy {
+    /// Keep the residues but score them as V, as the original SPRINT does
+    Substitute,
+    /// Fail on the first such residue
+    Reject,
+    /// Leave out the records with such residues
+    Skip,
+    /// Replace the residues by X, which scores 0 with every residue
+    Mask,
+    /// Keep B and Z, which score the average of D/N and E/Q (ambiguous
+    /// residues only)
+    Average,
+}
+
+impl ResiduePolicy {
+    pub fn from_name(name: &str) -> Result<Self, SprintError> {
+        match name.to_lowercase().as_str() {
+            "substitute" => Ok(ResiduePolicy::Substitute),
+            "reject" => Ok(ResiduePolicy::Reject),
+            "skip" => Ok(ResiduePolicy::Skip),
+            "mask" => Ok(ResiduePolicy::Mask),
+            "average" => Ok(ResiduePolicy::Average),
+            _ => Err(SprintError::invalid_input(format!(
+                "Unknown residue policy: {} (expected substitute, reject, skip, mask or average)",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResiduePolicy::Substitute => "substitute",
+            ResiduePolicy::Reject => "reject",
+            ResiduePolicy::Skip => "skip",
+            ResiduePolicy::Mask => "mask",
+            ResiduePolicy::Average => "average",
+        }
+    }
+}
+
+/// Policies of the classes of non-standard residues
+///
+/// By default, B, Z, X, U and O are scored as V (as in the original SPRINT)
+/// and any other character is rejected.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SequencePolicy {
+    pub ambiguous: ResiduePolicy,
+    pub unknown: ResiduePolicy,
+    pub rare: ResiduePolicy,
+    pub invalid: ResiduePolicy,
+}
+
+impl Default for SequencePolicy {
+    fn default() -> Self {
+        SequencePolicy {
+            ambiguous: ResiduePolicy::Substitute,
+            unknown: ResiduePolicy::Substitute,
+            rare: ResiduePolicy::Substitute,
+            invalid: ResiduePolicy::Reject,
+        }
+    }
+}
+
+impl SequencePolicy {
+    /// Parses comma-separated `class=policy` entries (e.g.
+    /// "rare=skip,invalid=mask", "all" setting every class), the classes
+    /// left out keeping their default policy
+    pub fn parse(spec: &str) -> Result<Self, SprintError> {
+        let mut policy = SequencePolicy::default();
+
+        for entry in spec.split(',').map(|entry| entry.trim()).filter(|entry| !entry.is_empty()) {
+            let (class, residue_policy) = entry.split_once('=').ok_or_else(|| {
+                SprintError::invalid_input(format!("Invalid residue policy: {} (expected class=policy)", entry))
+            })?;
+            let residue_policy = ResiduePolicy::from_name(residue_policy.trim())?;
+            let classes = match class.trim().to_lowercase().as_str() {
+                "all" => ResidueClass::ALL.to_vec(),
+                class => vec![ResidueClass::from_name(class)?],
+            };
+            for class in classes {
+                policy.set(class, residue_policy)?;
+            }
+        }
+
+        Ok(policy)
+    }
+
+    pub fn policy(&self, class: ResidueClass) -> ResiduePolicy {
+        match class {
+            ResidueClass::Ambiguous => self.ambiguous,
+            ResidueClass::Unknown => self.unknown,
+            ResidueClass::Rare => self.rare,
+            ResidueClass::Invalid => self.invalid,
+        }
+    }
+
+    /// Sets the policy of a class (only ambiguous residues can be averaged,
+    /// and invalid characters cannot be substituted)
+    pub fn set(&mut self, class: ResidueClass, policy: ResiduePolicy) -> Result<(), SprintError> {
+        if policy == ResiduePolicy::Average && class != ResidueClass::Ambiguous {
+            return Err(SprintError::invalid_input(format!(
+                "Only ambiguous residues can be averaged, not {} residues",
+                class.name()
+            )));
+        }
+        if policy == ResiduePolicy::Substitute && class == ResidueClass::Invalid {
+            return Err(SprintError::invalid_input("Invalid characters cannot be substituted"));
+        }
+        match class {
+            ResidueClass::Ambiguous => self.ambiguous = policy,
+            ResidueClass::Unknown => self.unknown = policy,
+            ResidueClass::Rare => self.rare = policy,
+            ResidueClass::Invalid => self.invalid = policy,
+        }
+        Ok(())
+    }
+}
+
+/// Non-standard residue of a record (positions start at 1)
+#[derive(Clone, Debug)]
+pub struct ResidueChange {
+    pub position: usize,
+    pub residue: char,
+    pub policy: ResiduePolicy,
+}
+
+/// Non-standard residues of a record, and whether it was dropped
+#[derive(Clone, Debug)]
+pub struct RecordReport {
+    pub name: String,
+    pub dropped: bool,
+    pub residues: Vec<ResidueChange>,
+}
+
+impl fmt::Display for RecordReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let residues: Vec<String> = self
+            .residues
+            .iter()
+            .map(|change| format!("'{}' at {} ({})", change.residue.escape_debug(), change.position, change.policy.name()))
+            .collect();
+        let action = if self.dropped { "dropped" } else { "modified" };
+        write!(f, "{} {}: {}", action, self.name, residues.join(", "))
+    }
+}
+
+/// Records with non-standard residues among the records of a file (or of a
+/// list of sequences)
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+    /// Number of records read
+    pub records: usize,
+    /// Records with non-standard residues, in the order of the input
+    pub entries: Vec<RecordReport>,
+    /// Records whose header does not have the expected format (named after
+    /// the first word of their header)
+    pub unmatched_headers: Vec<String>,
+}
+
+impl ValidationReport {
+    /// Adds the entries of another report
+    pub fn add(&mut self, other: ValidationReport) {
+        self.records += other.records;
+        self.entries.extend(other.entries);
+        self.unmatched_headers.extend(other.unmatched_headers);
+    }
+
+    /// Records kept with some of their residues substituted, masked or averaged
+    pub fn modified(&self) -> impl Iterator<Item = &RecordReport> {
+        self.entries.iter().filter(|entry| !entry.dropped)
+    }
+
+    /// Records left out
+    pub fn dropped(&self) -> impl Iterator<Item = &RecordReport> {
+        self.entries.iter().filter(|entry| entry.dropped)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.unmatched_headers.is_empty()
+    }
+
+    /// Prints a summary of the report, and a warning for every record
+    /// modified or dropped
+    pub fn print_warnings(&self, source: &str) {
+        if !self.unmatched_headers.is_empty() {
+            println!(
+                "WARNING: {} - {} of {} headers do not have the expected format (named after their first word): {}",
+                source,
+                self.unmatched_headers.len(),
+                self.records,
+                self.unmatched_headers.join(", ")
+            );
+        }
+        if self.entries.is_empty() {
+            return;
+        }
+        println!(
+            "WARNING: {} - {} of {} records modified, {} dropped (non-standard residues)",
+            source,
+            self.modified().count(),
+            self.records,
+            self.dropped().count()
+        );
+        for entry in self.entries.iter() {
+            println!("WARNING: {} - {}", source, entry);
+        }
+    }
+}
+
+/// Whether a residue code is the one of a standard amino acid
+pub fn is_standard(code: usize) -> bool {
+    (1..21).contains(&code)
+}
+
+/// A sanitized sequence and the codes of its residues
+pub type SanitizedSequence = (String, Vec<usize>);
+
+/// Applies a policy to a sequence
+///
+/// Returns the sanitized sequence and the codes of its residues (None if the
+/// record is dropped), and the non-standard residues of the record (None if
+/// there are none).
+pub fn sanitize_sequence(
+    name: &str,
+    sequence: &str,
+    policy: &SequencePolicy,
+) -> Result<(Option<SanitizedSequence>, Option<RecordReport>), SprintError> {
+    let mut sanitized = String::with_capacity(sequence.len());
+    let mut codes = Vec::with_capacity(sequence.len());
+    let mut residues = vec![];
+    let mut dropped = false;
+
+    for (i, residue) in sequence.chars().enumerate() {
+        let class = match ResidueClass::of(residue) {
+            Some(class) => class,
+            None => {
+                sanitized.push(residue);
+                codes.push(RESIDUE_CODES[&residue]);
+                continue;
+            }
+        };
+
+        let residue_policy = policy.policy(class);
+        match residue_policy {
+            ResiduePolicy::Reject => {
+                return Err(SprintError::InvalidResidue { protein: name.to_string(), position: i + 1, residue });
+            }
+            ResiduePolicy::Skip => dropped = true,
+            ResiduePolicy::Substitute => {
+                sanitized.push(residue);
+                codes.push(SUBSTITUTED_CODE);
+            }
+            ResiduePolicy::Mask => {
+                sanitized.push('X');
+                codes.push(MASKED_CODE);
+            }
+            ResiduePolicy::Average => {
+                let residue = residue.to_ascii_uppercase();
+                sanitized.push(residue);
+                codes.push(if residue == 'B' { B_CODE } else { Z_CODE });
+            }
+        }
+        residues.push(ResidueChange { position: i + 1, residue, policy: residue_policy });
+    }
+
+    let report = match residues.is_empty() {
+        true => None,
+        false => Some(RecordReport { name: name.to_string(), dropped, residues }),
+    };
+    Ok((if dropped { None } else { Some((sanitized, codes)) }, report))
+}
+
+/// Sanitizes (name, sequence) records into proteins, indexed in the order of
+/// the records kept
+pub fn sanitize_proteins(
+    records: Vec<(String, String)>,
+    new: bool,
+    policy: &SequencePolicy,
+) -> Result<(Vec<Protein>, ValidationReport), SprintError> {
+    let mut proteins = vec![];
+    let mut report = ValidationReport { records: records.len(), ..Default::default() };
+
+    for (name, sequence) in records {
+        let (sanitized, entry) = sanitize_sequence(&name, &sequence, policy)?;
+        report.entries.extend(entry);
+        if let Some((sanitized, codes)) = sanitized {
+            proteins.push(Protein::with_residues(proteins.len(), name, sanitized, codes, new));
+        }
+    }
+
+    Ok((proteins, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprint::constants::CODE_RESIDUE_MAP;
+
+    fn records() -> Vec<(String, String)> {
+        vec![
+            ("P1".to_string(), "ACDEFGHIK".to_string()),
+            ("P2".to_string(), "ACBZXUO".to_string()),
+            ("P3".to_string(), "ACxu".to_string()),
+        ]
+    }
+
+    fn codes(protein: &Protein) -> Vec<usize> {
+        (0..protein.len()).map(|i| protein.residue(i)).collect()
+    }
+
+    #[test]
+    fn non_standard_residues_are_scored_as_v_by_default() {
+        let (proteins, report) = sanitize_proteins(records(), false, &SequencePolicy::default()).unwrap();
+
+        // The sequences are kept, with the codes the original SPRINT gave them
+        assert_eq!(proteins.len(), 3);
+        assert_eq!(proteins[1].seq(), "ACBZXUO");
+        assert_eq!(codes(&proteins[1]), codes(&Protein::new(0, "V".to_string(), "ACVVVVV".to_string(), false).unwrap()));
+        assert_eq!(codes(&proteins[2])[2..], [SUBSTITUTED_CODE, SUBSTITUTED_CODE]);
+
+        assert_eq!(report.records, 3);
+        assert_eq!(report.modified().count(), 2);
+        assert_eq!(report.dropped().count(), 0);
+        assert!(report.entries[0].residues.iter().all(|change| change.policy == ResiduePolicy::Substitute));
+    }
+
+    #[test]
+    fn invalid_characters_are_rejected_by_default() {
+        let records = vec![("P1".to_string(), "AC*D".to_string())];
+        match sanitize_proteins(records, false, &SequencePolicy::default()) {
+            Err(SprintError::InvalidResidue { protein, position, residue }) => {
+                assert_eq!((protein.as_str(), position, residue), ("P1", 3, '*'));
+            }
+            _ => panic!("expected an invalid residue"),
+        }
+    }
+
+    #[test]
+    fn residues_are_masked_averaged_or_skipped() {
+        let policy = SequencePolicy::parse("ambiguous=average,unknown=mask,rare=skip").unwrap();
+        let records = vec![
+            ("P1".to_string(), "ABZX".to_string()),
+            ("P2".to_string(), "AU".to_string()),
+            ("P3".to_string(), "AC".to_string()),
+        ];
+        let (proteins, report) = sanitize_proteins(records, false, &policy).unwrap();
+
+        assert_eq!(proteins.len(), 2);
+        assert_eq!(proteins[0].seq(), "ABZX");
+        assert_eq!(codes(&proteins[0]), vec![1, B_CODE, Z_CODE, MASKED_CODE]);
+        assert_eq!(proteins[1].name(), "P3");
+        assert_eq!(proteins[1].index(), 1);
+
+        let dropped: Vec<&str> = report.dropped().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(dropped, vec!["P2"]);
+        assert_eq!(report.modified().count(), 1);
+    }
+
+    #[test]
+    fn masked_residues_are_written_as_x() {
+        let policy = SequencePolicy::parse("all=mask").unwrap();
+        let (sanitized, _) = sanitize_sequence("P1", "AU*B", &policy).unwrap();
+        let (sequence, codes) = sanitized.unwrap();
+        assert_eq!(sequence, "AXXX");
+        assert_eq!(codes, vec![1, MASKED_CODE, MASKED_CODE, MASKED_CODE]);
+        assert_eq!(CODE_RESIDUE_MAP[&(MASKED_CODE as u16)], 'X');
+    }
+
+    #[test]
+    fn policies_are_parsed() {
+        let policy = SequencePolicy::parse(" rare=skip , invalid=mask ").unwrap();
+        assert_eq!(policy.policy(ResidueClass::Ambiguous), ResiduePolicy::Substitute);
+        assert_eq!(policy.policy(ResidueClass::Rare), ResiduePolicy::Skip);
+        assert_eq!(policy.policy(ResidueClass::Invalid), ResiduePolicy::Mask);
+        assert_eq!(SequencePolicy::parse("").unwrap(), SequencePolicy::default());
+
+        assert!(SequencePolicy::parse("rare").is_err());
+        assert!(SequencePolicy::parse("rare=drop").is_err());
+        assert!(SequencePolicy::parse("other=skip").is_err());
+        assert!(SequencePolicy::parse("unknown=average").is_err());
+        assert!(SequencePolicy::parse("invalid=substitute").is_err());
+    }
+
+    #[test]
+    fn mutations_keep_the_codes_of_the_other_residues() {
+        let (proteins, _) = sanitize_proteins(records(), false, &SequencePolicy::default()).unwrap();
+        let mutant = proteins[1].mutate(3, "M".to_string(), &[(0, 'W')]).unwrap();
+        assert_eq!(mutant.seq(), "WCBZXUO");
+        assert_eq!(codes(&mutant)[0], 18);
+        assert_eq!(codes(&mutant)[1..], codes(&proteins[1])[1..]);
+        assert!(proteins[1].mutate(3, "M".to_string(), &[(0, '*')]).is_err());
+    }
+}
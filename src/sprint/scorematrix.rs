@@ -0,0 +1,149 @@
+use std::fs::OpenOptions;
+use std::io::Error;
+
+use memmap2::MmapMut;
+use ndarray::Array2;
+
+/// Symmetric matrix of interaction scores
+///
+/// Only the lower triangle (diagonal included) is stored, row by row: the
+/// score of the proteins `i` and `j` (`j <= i`) is at `i * (i + 1) / 2 + j`.
+/// The scores are either held in memory or in a memory-mapped file, which
+/// lets the operating system page them out for very large protein sets.
+pub struct ScoreMatrix {
+    size: usize,
+    storage: Storage,
+}
+
+enum Storage {
+    Memory(Vec<f32>),
+    /// Memory-mapped file (and its name)
+    Mapped(MmapMut, String),
+}
+
+impl ScoreMatrix {
+    /// Creates a matrix of zeros for `size` proteins
+    pub fn new(size: usize) -> Self {
+        ScoreMatrix {
+            size,
+            storage: Storage::Memory(vec![0f32; num_entries(size)]),
+        }
+    }
+
+    /// Creates a matrix of zeros for `size` proteins, stored in a
+    /// memory-mapped file (created or overwritten)
+    pub fn mapped(size: usize, filename: &str) -> Result<Self, Error> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(filename)?;
+        file.set_len((num_entries(size) * 4) as u64)?;
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+
+        Ok(ScoreMatrix {
+            size,
+            storage: Storage::Mapped(mmap, filename.to_string()),
+        })
+    }
+
+    /// Creates a matrix in memory, or in a memory-mapped file if one is given
+    pub fn allocate(size: usize, filename: Option<&str>) -> Result<Self, Error> {
+        match filename {
+            Some(filename) => ScoreMatrix::mapped(size, filename),
+            None => Ok(ScoreMatrix::new(size)),
+        }
+    }
+
+    /// Number of proteins
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// File the scores are memory-mapped from, if any
+    pub fn filename(&self) -> Option<&str> {
+        match &self.storage {
+            Storage::Memory(_) => None,
+            Storage::Mapped(_, filename) => Some(filename),
+        }
+    }
+
+    #[inline(always)]
+    pub fn get(&self, protein1: usize, protein2: usize) -> f32 {
+        self.values()[index(protein1, protein2)]
+    }
+
+    #[inline(always)]
+    pub fn add(&mut self, protein1: usize, protein2: usize, score: f32) {
+        self.values_mut()[index(protein1, protein2)] += score;
+    }
+
+    /// Scores of a protein with the proteins of lower or equal index
+    pub fn row(&self, protein: usize) -> &[f32] {
+        let start = index(protein, 0);
+        &self.values()[start..start + protein + 1]
+    }
+
+    /// Packed scores (lower triangle, row by row)
+    pub fn values(&self) -> &[f32] {
+        match &self.storage {
+            Storage::Memory(values) => values,
+            Storage::Mapped(mmap, _) => unsafe {
+                std::slice::from_raw_parts(mmap.as_ptr() as *const f32, num_entries(self.size))
+            },
+        }
+    }
+
+    pub fn values_mut(&mut self) -> &mut [f32] {
+        let length = num_entries(self.size);
+        match &mut self.storage {
+            Storage::Memory(values) => values,
+            Storage::Mapped(mmap, _) => unsafe {
+                std::slice::from_raw_parts_mut(mmap.as_mut_ptr() as *mut f32, length)
+            },
+        }
+    }
+
+    /// Rows of the matrix (row `i` holds the scores of the protein `i` with
+    /// the proteins of lower or equal index), which can be filled in parallel
+    pub fn rows_mut(&mut self) -> Vec<&mut [f32]> {
+        let size = self.size;
+        let mut rows = Vec::with_capacity(size);
+        let mut rest = self.values_mut();
+        for protein in 0..size {
+            let (row, next) = rest.split_at_mut(protein + 1);
+            rows.push(row);
+            rest = next;
+        }
+        rows
+    }
+
+    /// Adds the scores of another matrix of the same size
+    pub fn add_scores(&mut self, scores: &[f32]) {
+        self.values_mut()
+            .iter_mut()
+            .zip(scores.iter())
+            .for_each(|(total, score)| *total += score);
+    }
+
+    /// Full (symmetric) matrix of scores
+    pub fn to_array(&self) -> Array2<f32> {
+        Array2::from_shape_fn((self.size, self.size), |(i, j)| self.get(i, j))
+    }
+
+    /// Writes the scores to the memory-mapped file (if any)
+    pub fn flush(&self) -> Result<(), Error> {
+        match &self.storage {
+            Storage::Memory(_) => Ok(()),
+            Storage::Mapped(mmap, _) => mmap.flush(),
+        }
+    }
+}
+
+/// Number of scores stored for `size` proteins
+pub fn num_entries(size: usize) -> usize {
+    size * (size + 1) / 2
+}
+
+#[inline(always)]
+fn index(protein1: usize, protein2: usize) -> usize {
+    let smallest = std::cmp::min(protein1, protein2);
+    let largest = std::cmp::max(protein1, protein2);
+    largest * (largest + 1) / 2 + smallest
+}
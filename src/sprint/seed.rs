@@ -1,3 +1,17 @@
+use crate::sprint::constants::SEEDS;
+use crate::sprint::error::SprintError;
+use crate::sprint::fileio::load_seeds;
+
+/// Maximum number of matching positions in a seed (an s-mer stores 5 bits
+/// per matching position in a u64)
+pub const MAX_SEED_WEIGHT: usize = 12;
+
+/// Spaced seed (e.g. "11****11***1")
+///
+/// Only the residues at the matching positions ('1') of the seed are stored
+/// in an s-mer, so the value of the seed is a mask of `weight()` groups of
+/// 5 bits, the first matching position being the most significant.
+#[derive(Clone)]
 pub struct Seed {
     seed: String,
     value: u64,
@@ -6,47 +20,130 @@ pub struct Seed {
 }
 
 impl Seed {
-    pub fn new(seed: &str) -> Seed {
+    pub fn new(seed: &str) -> Result<Seed, SprintError> {
+        let invalid = SprintError::InvalidInput;
+
         let mut int_seed: u64 = 0;
         let mut non_zero_positions = vec![];
         for (i, character) in seed.chars().enumerate() {
-            if character == '*' {
-                int_seed = int_seed << 5; // just shift
-            } else if character == '1' {
+            if character == '1' {
                 int_seed = int_seed << 5;
                 int_seed = int_seed + 31; // 31 is decimal for 11111
                 non_zero_positions.push(i);
-            } else {
-                panic!("Encountered invalid character {} while converting the seed.", character);
+            } else if character != '*' {
+                return Err(invalid(format!(
+                    "Encountered invalid character '{}' in seed {} (only '1' and '*' are allowed).",
+                    character, seed
+                )));
             }
         }
 
-        Seed {
+        if !seed.starts_with('1') || !seed.ends_with('1') {
+            return Err(invalid(format!("The seed {} must start and end with a '1'.", seed)));
+        }
+
+        if non_zero_positions.len() > MAX_SEED_WEIGHT {
+            return Err(invalid(format!(
+                "The seed {} has {} matching positions (at most {} are supported).",
+                seed,
+                non_zero_positions.len(),
+                MAX_SEED_WEIGHT
+            )));
+        }
+
+        Ok(Seed {
             seed: seed.to_string(),
             value: int_seed,
             length: seed.len(),
             non_zero_positions
-        }
+        })
     }
 
+    /// Length of the seed (number of residues spanned by an s-mer)
     pub fn len(&self) -> usize {
-        self.seed.len()
+        self.length
+    }
+
+    /// Number of matching positions of the seed
+    pub fn weight(&self) -> usize {
+        self.non_zero_positions.len()
+    }
+
+    /// Matching positions of the seed
+    pub fn positions(&self) -> &Vec<usize> {
+        &self.non_zero_positions
+    }
+
+    /// Number of bits an s-mer must be shifted by to retrieve the residue at
+    /// the given position of the seed (None if the position does not matter)
+    pub fn shift(&self, position: usize) -> Option<usize> {
+        self.non_zero_positions
+            .iter()
+            .position(|&p| p == position)
+            .map(|rank| (self.weight() - rank - 1) * 5)
     }
 
     pub fn value(&self) -> u64 {
         self.value
     }
-    
-    pub fn clone(&self) -> Seed {
-        Seed {
-            seed: self.seed.clone(),
-            value: self.value,
-            length: self.length,
-            non_zero_positions: self.non_zero_positions.clone()
-        }
-    }
 
     pub fn as_string(&self) -> String {
         self.seed.clone()
     }
 }
+
+/// The seeds that were determined to be optimal by the authors of SPRINT
+pub fn default_seeds() -> Vec<Seed> {
+    SEEDS.iter()
+        .map(|seed| Seed::new(seed).expect("the default seeds are valid"))
+        .collect()
+}
+
+/// Parses a comma-separated list of seeds (e.g. "11**1*1,1*1**11")
+pub fn parse_seeds(seeds: &str) -> Result<Vec<Seed>, SprintError> {
+    let seeds = seeds
+        .split(',')
+        .map(|seed| seed.trim())
+        .filter(|seed| !seed.is_empty())
+        .map(Seed::new)
+        .collect::<Result<Vec<Seed>, SprintError>>()?;
+
+    if seeds.is_empty() {
+        return Err(SprintError::invalid_input("At least one seed is required."));
+    }
+
+    Ok(seeds)
+}
+
+/// Selects the seeds given as a comma-separated list, or else the seeds in a
+/// seed file, or else the default seeds
+pub fn select_seeds(seeds: &Option<String>, seed_file: &Option<String>) -> Result<Vec<Seed>, SprintError> {
+    match (seeds, seed_file) {
+        (Some(seeds), _) => parse_seeds(seeds),
+        (None, Some(filename)) => load_seeds(filename),
+        (None, None) => Ok(default_seeds()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_seeds_are_valid() {
+        let seeds = default_seeds();
+        assert_eq!(seeds.len(), SEEDS.len());
+        for (seed, expected) in seeds.iter().zip(SEEDS.iter()) {
+            assert_eq!(seed.as_string(), *expected);
+            assert!(seed.weight() <= MAX_SEED_WEIGHT);
+        }
+    }
+
+    #[test]
+    fn invalid_seeds_are_rejected() {
+        assert!(Seed::new("11*x1").is_err());
+        assert!(Seed::new("*11").is_err());
+        assert!(Seed::new("1111111111111").is_err());
+        assert!(parse_seeds(" , ").is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+use std::fs::{create_dir_all, remove_dir_all, remove_file, File};
+use std::io::{copy, BufReader, BufWriter, Error, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{Sender, SyncSender};
+
+use crate::sprint::compression::CompressedWriter;
+use crate::sprint::hsp::HSP;
+use crate::sprint::hspfile::{decode_records, encode_records, from_record, to_record, write_header, write_record, HspParameters, HspRecord};
+use crate::sprint::proteinset::ProteinSet;
+
+/// Destination of the HSPs produced by a streaming extraction
+///
+/// The HSPs are sent in batches (one per chunk of s-mers). A batch never
+/// contains duplicates, but the same HSP may be found again in a later
+/// batch (e.g. with another seed).
+pub trait HspSink {
+    fn send(&mut self, hsps: Vec<HSP>) -> Result<(), Error>;
+
+    /// Whether the chunk `chunk` of the s-mers of the seed `seed` was already
+    /// processed (e.g. before an interruption), in which case it is skipped
+    fn is_done(&self, _seed: usize, _chunk: usize) -> bool {
+        false
+    }
+
+    /// Receives the HSPs of a chunk of s-mers (the trivial HSPs are the
+    /// chunk 0 of the seed `seeds.len()`)
+    fn send_chunk(&mut self, _seed: usize, _chunk: usize, hsps: Vec<HSP>) -> Result<(), Error> {
+        self.send(hsps)
+    }
+}
+
+/// Collects the HSPs in memory
+impl HspSink for HashSet<HSP> {
+    fn send(&mut self, hsps: Vec<HSP>) -> Result<(), Error> {
+        self.extend(hsps);
+        Ok(())
+    }
+}
+
+impl HspSink for Sender<Vec<HSP>> {
+    fn send(&mut self, hsps: Vec<HSP>) -> Result<(), Error> {
+        Sender::send(self, hsps).map_err(|_| Error::new(ErrorKind::BrokenPipe, "The HSP receiver was dropped."))
+    }
+}
+
+/// Bounded channel (the extraction waits for the receiver when it is full)
+impl HspSink for SyncSender<Vec<HSP>> {
+    fn send(&mut self, hsps: Vec<HSP>) -> Result<(), Error> {
+        SyncSender::send(self, hsps).map_err(|_| Error::new(ErrorKind::BrokenPipe, "The HSP receiver was dropped."))
+    }
+}
+
+/// Passes every batch of HSPs to a function
+pub struct CallbackSink<F: FnMut(Vec<HSP>) -> Result<(), Error>>(pub F);
+
+impl<F: FnMut(Vec<HSP>) -> Result<(), Error>> HspSink for CallbackSink<F> {
+    fn send(&mut self, hsps: Vec<HSP>) -> Result<(), Error> {
+        (self.0)(hsps)
+    }
+}
+
+/// Writes HSPs to a file without holding them all in memory
+///
+/// The HSPs are spilled to shard files (in `<output>.shards/`) partitioned by
+/// the lowest protein index of the HSP. Once the extraction is done, the
+/// shards are deduplicated one at a time and concatenated into the output, so
+/// that at most one shard is held in memory.
+pub struct ShardedHspWriter {
+    output: String,
+    directory: PathBuf,
+    num_proteins: usize,
+    shards: Vec<BufWriter<File>>,
+}
+
+impl ShardedHspWriter {
+    pub fn new(output: &str, num_proteins: usize, num_shards: usize) -> Result<Self, Error> {
+        if num_shards == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "At least one shard is required."));
+        }
+
+        let directory = PathBuf::from(format!("{}.shards", output));
+        create_dir_all(&directory)?;
+
+        let shards = (0..num_shards)
+            .map(|shard| File::create(directory.join(format!("shard_{}", shard))).map(BufWriter::new))
+            .collect::<Result<Vec<BufWriter<File>>, Error>>()?;
+
+        Ok(ShardedHspWriter {
+            output: output.to_string(),
+            directory,
+            num_proteins: std::cmp::max(num_proteins, 1),
+            shards,
+        })
+    }
+
+    /// Writes the deduplicated HSPs in the text format (compressed if the
+    /// output ends with .gz or .zst)
+    pub fn finish_text(mut self, protein_set: &ProteinSet) -> Result<(), Error> {
+        let mut writer = CompressedWriter::create(&self.output)?;
+        let mut first = true;
+
+        for shard in 0..self.shards.len() {
+            for record in self.read_shard(shard)? {
+                if !first {
+                    writer.write_all(b"\n")?;
+                }
+                writer.write_all(from_record(&record).as_string(protein_set).as_bytes())?;
+                first = false;
+            }
+        }
+
+        writer.finish()?;
+        self.cleanup()
+    }
+
+    /// Writes the deduplicated HSPs in the binary format
+    pub fn finish_binary(mut self, protein_set: &ProteinSet, parameters: &HspParameters) -> Result<(), Error> {
+        // The index precedes the records, so the records are staged in a
+        // temporary file while the rows are counted
+        let records_path = self.directory.join("records");
+        let mut records = BufWriter::new(File::create(&records_path)?);
+        let mut index = vec![0u64; protein_set.len() + 1];
+
+        for shard in 0..self.shards.len() {
+            for record in self.read_shard(shard)? {
+                index[record.0 as usize + 1] += 1;
+                write_record(&mut records, &record)?;
+            }
+        }
+        records.flush()?;
+        drop(records);
+
+        for i in 0..protein_set.len() {
+            index[i + 1] += index[i];
+        }
+
+        let mut writer = BufWriter::new(File::create(&self.output)?);
+        write_header(&mut writer, protein_set, parameters, &index)?;
+        copy(&mut BufReader::new(File::open(&records_path)?), &mut writer)?;
+        writer.flush()?;
+
+        self.cleanup()
+    }
+
+    /// Loads, sorts and deduplicates the records of a shard, then deletes it
+    fn read_shard(&mut self, shard: usize) -> Result<Vec<HspRecord>, Error> {
+        self.shards[shard].flush()?;
+
+        let path = self.directory.join(format!("shard_{}", shard));
+        let mut bytes = vec![];
+        File::open(&path)?.read_to_end(&mut bytes)?;
+        remove_file(&path)?;
+
+        let mut records = decode_records(&bytes);
+        records.sort_unstable();
+        records.dedup();
+
+        Ok(records)
+    }
+
+    fn cleanup(self) -> Result<(), Error> {
+        drop(self.shards);
+        remove_dir_all(&self.directory)
+    }
+}
+
+impl HspSink for ShardedHspWriter {
+    fn send(&mut self, hsps: Vec<HSP>) -> Result<(), Error> {
+        let num_shards = self.shards.len();
+        for hsp in hsps.iter() {
+            let record = to_record(hsp);
+
+            // Contiguous ranges of proteins per shard keep the output sorted
+            let shard = std::cmp::min(record.0 as usize * num_shards / self.num_proteins, num_shards - 1);
+            self.shards[shard].write_all(&encode_records(&[record]))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprint::fileio::load_hsps;
+    use crate::sprint::hspfile::HspFile;
+    use crate::sprint::location::Location;
+    use crate::sprint::protein::Protein;
+
+    fn protein_set() -> ProteinSet {
+        let sequences = ["MKTAYIAKQRQISFVKSHFSRQ", "MKTAYIAKQRQISFVKSHFSRQLEER", "GSHMLEDPVAG", "PEPTIDEPEPTIDE"];
+        let proteins = sequences
+            .iter()
+            .enumerate()
+            .map(|(i, sequence)| Protein::new(i, format!("P{}", i), sequence.to_string(), false).unwrap())
+            .collect();
+        ProteinSet::new(proteins).unwrap()
+    }
+
+    fn hsp(protein1: usize, position1: usize, protein2: usize, position2: usize, length: usize) -> HSP {
+        HSP::new(Location::new(protein1, position1), Location::new(protein2, position2), length)
+    }
+
+    /// Batches sharing HSPs, some of them with their locations swapped
+    fn batches() -> Vec<Vec<HSP>> {
+        vec![
+            vec![hsp(0, 0, 1, 0, 20), hsp(2, 1, 3, 2, 8), hsp(3, 0, 3, 7, 7)],
+            vec![hsp(1, 0, 0, 0, 20), hsp(0, 3, 2, 0, 6)],
+            vec![hsp(2, 1, 3, 2, 8), hsp(3, 7, 3, 0, 7), hsp(0, 0, 1, 0, 20)],
+        ]
+    }
+
+    /// HSPs in the orientation in which they are stored (HSPs loaded from
+    /// text files are ordered by protein name rather than index)
+    fn records<I: IntoIterator<Item = HSP>>(hsps: I) -> HashSet<HspRecord> {
+        hsps.into_iter().map(|hsp| to_record(&hsp)).collect()
+    }
+
+    fn expected() -> HashSet<HspRecord> {
+        records(batches().into_iter().flatten())
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("sprint_sink_{}_{}", name, std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn write(path: &str, num_shards: usize) -> ShardedHspWriter {
+        let mut writer = ShardedHspWriter::new(path, protein_set().len(), num_shards).unwrap();
+        for batch in batches() {
+            writer.send(batch).unwrap();
+        }
+        writer
+    }
+
+    #[test]
+    fn text_output_is_deduplicated() {
+        for num_shards in [1, 3, 8] {
+            let path = temp_path(&format!("text_{}", num_shards));
+            write(&path, num_shards).finish_text(&protein_set()).unwrap();
+
+            let lines = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(lines.lines().count(), 4);
+            assert_eq!(records(load_hsps(&path, &protein_set()).unwrap()), expected());
+            assert!(!PathBuf::from(format!("{}.shards", path)).exists());
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn binary_output_is_deduplicated() {
+        let path = temp_path("binary");
+        write(&path, 2).finish_binary(&protein_set(), &HspParameters::default()).unwrap();
+
+        let hsp_file = HspFile::open(&path, &protein_set()).unwrap();
+        assert_eq!(hsp_file.len(), 4);
+        assert_eq!(records(hsp_file.iter()), expected());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,113 +1,367 @@
-use crate::sprint::hsp::HSP;
+use crate::sprint::distributed::Worker;
+use crate::sprint::error::SprintError;
+use crate::sprint::explanation::{explain_pair, find_supports, SupportingHsp};
+use crate::sprint::hspfile::HspSource;
+use crate::sprint::prediction::{index_pairs, prepare_training_pairs, relevant_pairs, ScoringSettings};
 use crate::sprint::proteinset::ProteinSet;
-use crate::sprint::scoring::score_hsp;
+use crate::sprint::scoring::{score_hsp, score_position};
+use crate::sprint::substitution::SubstitutionMatrix;
 use rayon::prelude::*;
-use std::cell::UnsafeCell;
 use std::collections::{HashSet, HashMap};
+use std::ops::Range;
 use std::time::Instant;
 
-pub struct ContributionsDict {
-    pub dict: UnsafeCell<HashMap<usize, Vec<f32>>>
+/// Normalization of a per-residue profile
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProfileNormalization {
+    None,
+    /// The contributions sum to 1
+    Sum,
+    /// The highest contribution is 1
+    Max,
+    /// Z-scores of the contributions along the protein
+    ZScore,
 }
 
-impl ContributionsDict {
-    pub fn new(target_index: usize, protein_set: &ProteinSet) -> ContributionsDict {
-        let target_length = protein_set.get_protein_by_id(target_index).len();
-        let mut contributions_dict = HashMap::new();
-
-        for p in protein_set.iter() {
-            if p.is_new() {
-                contributions_dict.insert(p.index(), vec![0f32; target_length]);
-            }
+impl ProfileNormalization {
+    pub fn from_name(name: &str) -> Result<Self, SprintError> {
+        match name.to_lowercase().as_str() {
+            "none" => Ok(ProfileNormalization::None),
+            "sum" => Ok(ProfileNormalization::Sum),
+            "max" => Ok(ProfileNormalization::Max),
+            "zscore" => Ok(ProfileNormalization::ZScore),
+            _ => Err(SprintError::invalid_input(format!(
+                "Unknown profile normalization {} (none, sum, max or zscore).",
+                name
+            ))),
         }
-
-        ContributionsDict { dict: UnsafeCell::new(contributions_dict) }
     }
 }
 
-unsafe impl Sync for ContributionsDict {}
-unsafe impl Send for ContributionsDict {}
+/// How the contribution of a pair of HSPs is distributed over the residues
+/// of a similarity region
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResidueWeighting {
+    /// Every residue receives `contribution / length`
+    Uniform,
+    /// Every residue receives a share proportional to the (positive)
+    /// substitution score of the residues it is aligned with
+    /// (`scoring::score_position`)
+    Substitution,
+}
+
+impl ResidueWeighting {
+    pub fn from_name(name: &str) -> Result<Self, SprintError> {
+        match name.to_lowercase().as_str() {
+            "uniform" => Ok(ResidueWeighting::Uniform),
+            "substitution" => Ok(ResidueWeighting::Substitution),
+            _ => Err(SprintError::invalid_input(format!(
+                "Unknown residue weighting {} (uniform or substitution).",
+                name
+            ))),
+        }
+    }
+}
 
+/// HSP of a row of the HSP table: (partner index, partner length, HSP
+/// length, HSP score, position, partner position)
+pub type PositionedHsp = (usize, f32, f32, f32, usize, usize);
+
+/// Range of consecutive residues predicted to bind
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub start: usize,
+    /// End of the segment (excluded)
+    pub end: usize,
+    /// Sum of the profile over the segment
+    pub score: f32,
+}
 
+/// Contributions of the residues of the target to its scores with every new
+/// protein (e.g. peptides)
+///
+/// The contributions of the target for a new protein are its profile in
+/// `pair_profiles` with that protein (using only the training pairs of this
+/// process).
 pub fn compute_contributions(
     target_name: &String,
     protein_set: &ProteinSet,
-    hsps: &HashSet<HSP>,
-    training_pairs: &Vec<(String, String)>,
-    kmer_size: usize,
-    process_rank: usize,
-    world_size: usize,
-    verbose: bool,
-) -> HashMap<usize, Vec<f32>> {
-    let mapped_training_pairs: Vec<(usize, usize)> = training_pairs
-        .iter()
-        .filter(|pair| protein_set.contains(&pair.0) && protein_set.contains(&pair.1))
-        .map(|pair| {
-            (
-                protein_set.get_protein_by_name(&pair.0).index(),
-                protein_set.get_protein_by_name(&pair.1).index(),
-            )
-        })
-        .collect();
+    hsps: &dyn HspSource,
+    training_pairs: &[(String, String)],
+    settings: &ScoringSettings,
+    weighting: ResidueWeighting,
+    worker: Worker,
+) -> Result<HashMap<usize, Vec<f32>>, SprintError> {
+    let (kmer_size, matrix) = (settings.kmer_size, settings.matrix);
+    let Worker { rank: process_rank, world_size, verbose } = worker;
+    let target = protein_set.get_protein_by_name(target_name)?;
+    let target_index = target.index();
+    let target_length = target.len();
+
+    let (training_pairs_to_process, interactors) =
+        prepare_training_pairs(protein_set, training_pairs, process_rank, world_size, verbose);
 
     if verbose {
-        println!("Process {} - Identifying interactors ", process_rank);
+        println!("Process {} -: Building the HSP table", process_rank);
     }
+    let hsp_table = build_hsp_table(hsps, protein_set, &interactors, kmer_size, matrix);
 
-    let mut interactors: HashSet<usize> = HashSet::new();
-    mapped_training_pairs.iter().for_each(|pair| {
-        interactors.insert(pair.0);
-        interactors.insert(pair.1);
-    });
+    let new_proteins: Vec<usize> = protein_set.iter().filter(|p| p.is_new()).map(|p| p.index()).collect();
+
+    if verbose {
+        println!("Process {} - Scoring the interactions...", process_rank);
+    }
+    let start = Instant::now();
+
+    // The contributions of every new protein are computed by a single task,
+    // so they do not depend on the number of threads
+    let pairs_by_protein = index_pairs(&training_pairs_to_process, protein_set.len());
+    let contributions: HashMap<usize, Vec<f32>> = new_proteins
+        .par_iter()
+        .map(|&protein| {
+            let mut contributions = vec![0f32; target_length];
+            let pairs = relevant_pairs(hsp_table[protein].iter().map(|hsp| hsp.0), &pairs_by_protein);
+            let supports = find_supports(
+                target_index,
+                protein,
+                &training_pairs_to_process,
+                &pairs,
+                &hsp_table,
+                kmer_size as f32,
+            );
+            for support in supports.iter() {
+                distribute(&mut contributions, protein_set, &support.hsp1, support.contribution, weighting, matrix);
+            }
+            (protein, contributions)
+        })
+        .collect();
 
     if verbose {
         println!(
-            "Process {} - Identification of relevant pairs.",
-            process_rank
+            "Process {} - Scored the interactions in {}...",
+            process_rank,
+            start.elapsed().as_secs()
         );
     }
-    // Prepare the batch of training pairs to use in this process
-    let mut training_pairs_to_process: Vec<(usize, usize)> = vec![];
-    for i in 0..mapped_training_pairs.len() {
-        if (i + process_rank) % world_size == 0 {
-            training_pairs_to_process.push(mapped_training_pairs[i].clone());
-        }
+
+    Ok(contributions)
+}
+
+/// Per-residue contributions to the score of two proteins, on both proteins
+///
+/// The contribution of every pair of HSPs supporting the score (see
+/// `explanation::explain_pair`) is distributed over the similarity region of
+/// each protein (according to `weighting`), so each profile sums to the score
+/// of the pair.
+pub fn pair_profiles(
+    protein_set: &ProteinSet,
+    hsps: &dyn HspSource,
+    training_pairs: &[(String, String)],
+    protein1: &str,
+    protein2: &str,
+    settings: &ScoringSettings,
+    weighting: ResidueWeighting,
+) -> Result<(Vec<f32>, Vec<f32>), SprintError> {
+    let matrix = settings.matrix;
+    let explanation = explain_pair(protein_set, hsps, training_pairs, protein1, protein2, settings.kmer_size, matrix)?;
+
+    let mut profile1 = vec![0f32; protein_set.get_protein_by_id(explanation.protein1).len()];
+    let mut profile2 = vec![0f32; protein_set.get_protein_by_id(explanation.protein2).len()];
+    for support in explanation.supports.iter() {
+        distribute(&mut profile1, protein_set, &support.hsp1, support.contribution, weighting, matrix);
+        distribute(&mut profile2, protein_set, &support.hsp2, support.contribution, weighting, matrix);
     }
 
+    Ok((profile1, profile2))
+}
+
+/// Interface propensity of a protein: the sum of its per-residue profiles
+/// (as in `pair_profiles`) with every other protein of the set
+pub fn interface_propensity(
+    protein_set: &ProteinSet,
+    hsps: &dyn HspSource,
+    training_pairs: &[(String, String)],
+    protein: &str,
+    settings: &ScoringSettings,
+    weighting: ResidueWeighting,
+    verbose: bool,
+) -> Result<Vec<f32>, SprintError> {
+    let (kmer_size, matrix) = (settings.kmer_size, settings.matrix);
+    let protein = protein_set.get_protein_by_name(protein)?.index();
+
+    let (pairs, interactors) = prepare_training_pairs(protein_set, training_pairs, 0, 1, verbose);
     if verbose {
-        println!("Process {} -: Building the HSP table", process_rank);
+        println!("Process 0 - Building the HSP table");
     }
-    let hsp_table = build_hsp_table(hsps, &protein_set, &interactors, kmer_size);
+    let hsp_table = build_hsp_table(hsps, protein_set, &interactors, kmer_size, matrix);
+    let kmer_size = kmer_size as f32;
+
+    let pairs_by_protein = index_pairs(&pairs, protein_set.len());
+    let mut propensity = vec![0f32; protein_set.get_protein_by_id(protein).len()];
+    for index in relevant_pairs(hsp_table[protein].iter().map(|hsp| hsp.0), &pairs_by_protein) {
+        let (interactor1, interactor2) = pairs[index];
+
+        // Every HSP of the protein with an interactor is supported by the
+        // HSPs of the other interactor with the other proteins (for a
+        // homodimer, the other HSPs of the same interactor)
+        let mut orientations = vec![(interactor1, interactor2)];
+        if interactor1 != interactor2 {
+            orientations.push((interactor2, interactor1));
+        }
+        for (interactor, other) in orientations {
+            let hsps = &hsp_table[interactor];
+            for hsp1 in &hsps[partner_range(hsps, protein)] {
+                let contribution: f32 = hsp_table[other]
+                    .iter()
+                    .filter(|hsp2| hsp2.0 != protein)
+                    .map(|hsp2| pair_contribution(hsp1, hsp2, kmer_size))
+                    .sum();
+                let hsp = SupportingHsp {
+                    protein,
+                    partner: interactor,
+                    position: hsp1.5,
+                    partner_position: hsp1.4,
+                    length: hsp1.2 as usize,
+                    score: hsp1.3,
+                };
+                distribute(&mut propensity, protein_set, &hsp, contribution, weighting, matrix);
+            }
+        }
+    }
+
+    Ok(propensity)
+}
 
-    unsafe {
-        if verbose {
-            println!("Process {} - Initializing the score matrix.", process_rank);
+/// Normalizes a per-residue profile in place
+pub fn normalize_profile(profile: &mut [f32], normalization: ProfileNormalization) {
+    if profile.is_empty() {
+        return;
+    }
+    match normalization {
+        ProfileNormalization::None => {}
+        ProfileNormalization::Sum => {
+            let sum: f32 = profile.iter().sum();
+            if sum != 0f32 {
+                profile.iter_mut().for_each(|value| *value /= sum);
+            }
+        }
+        ProfileNormalization::Max => {
+            let max = profile.iter().cloned().fold(0f32, f32::max);
+            if max > 0f32 {
+                profile.iter_mut().for_each(|value| *value /= max);
+            }
+        }
+        ProfileNormalization::ZScore => {
+            let mean = profile.iter().sum::<f32>() / profile.len() as f32;
+            let variance = profile.iter().map(|value| (value - mean) * (value - mean)).sum::<f32>() / profile.len() as f32;
+            let deviation = variance.sqrt();
+            profile.iter_mut().for_each(|value| {
+                *value = match deviation > 0f32 {
+                    true => (*value - mean) / deviation,
+                    false => 0f32,
+                }
+            });
         }
+    }
+}
 
-        let target_index = protein_set.get_protein_by_name(target_name).index();
-        let dict = ContributionsDict::new(target_index, protein_set);
+/// Smooths a per-residue profile in place with a moving average over a
+/// window of residues centered on every residue (truncated at the ends of the
+/// protein)
+pub fn smooth_profile(profile: &mut [f32], window: usize) {
+    if window <= 1 {
+        return;
+    }
+    let original = profile.to_vec();
+    for (i, value) in profile.iter_mut().enumerate() {
+        let start = i.saturating_sub((window - 1) / 2);
+        let end = std::cmp::min(original.len(), i + window / 2 + 1);
+        *value = original[start..end].iter().sum::<f32>() / (end - start) as f32;
+    }
+}
 
-        if verbose {
-            println!("Process {} - Scoring the interactions...", process_rank);
+/// Predicted binding segments of a profile: the runs of consecutive
+/// residues above the mean of the profile, from the highest sum of the profile
+/// to the lowest (at most `count` segments)
+pub fn binding_segments(profile: &[f32], count: usize) -> Vec<Segment> {
+    if profile.is_empty() {
+        return vec![];
+    }
+    let mean = profile.iter().sum::<f32>() / profile.len() as f32;
+
+    let mut segments: Vec<Segment> = vec![];
+    let mut start = None;
+    for position in 0..=profile.len() {
+        let above = position < profile.len() && profile[position] > mean;
+        match (above, start) {
+            (true, None) => start = Some(position),
+            (false, Some(segment_start)) => {
+                segments.push(Segment {
+                    start: segment_start,
+                    end: position,
+                    score: profile[segment_start..position].iter().sum(),
+                });
+                start = None;
+            }
+            _ => {}
         }
-        let start = Instant::now();
+    }
 
-        training_pairs_to_process
-            .par_iter()
-            .for_each(|pair| fill_dict(target_index, pair, &hsp_table, kmer_size as f32, protein_set, &dict));
+    segments.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.start.cmp(&b.start)));
+    segments.truncate(count);
+    segments
+}
 
-        if verbose {
-            println!(
-                "Process {} - Scored the interactions in {}...",
-                process_rank,
-                start.elapsed().as_secs()
-            );
+/// Distributes a contribution over the residues of the protein of an HSP
+/// (its similarity region with the partner)
+fn distribute(
+    profile: &mut [f32],
+    protein_set: &ProteinSet,
+    hsp: &SupportingHsp,
+    contribution: f32,
+    weighting: ResidueWeighting,
+    matrix: &SubstitutionMatrix,
+) {
+    let region = &mut profile[hsp.position..hsp.position + hsp.length];
+
+    if weighting == ResidueWeighting::Substitution {
+        let protein = protein_set.get_protein_by_id(hsp.protein);
+        let partner = protein_set.get_protein_by_id(hsp.partner);
+        let weights: Vec<f32> = (0..hsp.length)
+            .map(|i| std::cmp::max(score_position(protein, partner, hsp.position + i, hsp.partner_position + i, matrix), 0) as f32)
+            .collect();
+        let total: f32 = weights.iter().sum();
+
+        // Without any positive score, the contribution is distributed uniformly
+        if total > 0f32 {
+            for (value, weight) in region.iter_mut().zip(weights.iter()) {
+                *value += contribution * weight / total;
+            }
+            return;
         }
+    }
 
-        return (*dict.dict.get()).clone();
+    for value in region.iter_mut() {
+        *value += contribution / hsp.length as f32;
     }
 }
 
+/// Range of the HSPs of a row of the HSP table with a given partner
+pub(crate) fn partner_range(hsps: &[PositionedHsp], partner: usize) -> Range<usize> {
+    hsps.partition_point(|hsp| hsp.0 < partner)..hsps.partition_point(|hsp| hsp.0 <= partner)
+}
+
+#[inline(always)]
+fn pair_contribution(
+    hsp1: &PositionedHsp,
+    hsp2: &PositionedHsp,
+    kmer_size: f32,
+) -> f32 {
+    let term1 = hsp1.3 * (hsp2.2 - kmer_size + 1f32);
+    let term2 = hsp2.3 * (hsp1.2 - kmer_size + 1f32);
+    (term1 + term2) / (hsp1.1 * hsp2.1)
+}
+
 pub unsafe fn initialize_score_matrix(matrix: &mut Vec<f32>, protein_set: &ProteinSet) {
     let sequence_set_size = protein_set.len();
     matrix.clear();
@@ -117,19 +371,20 @@ pub unsafe fn initialize_score_matrix(matrix: &mut Vec<f32>, protein_set: &Prote
 }
 
 pub fn build_hsp_table(
-    hsps: &HashSet<HSP>,
+    hsps: &dyn HspSource,
     protein_set: &ProteinSet,
     interactors: &HashSet<usize>,
     kmer_size: usize,
-) -> Vec<Vec<(usize, f32, f32, f32, usize, usize)>> {
-    let mut table: Vec<Vec<(usize, f32, f32, f32, usize, usize)>> = Vec::new();
+    matrix: &SubstitutionMatrix,
+) -> Vec<Vec<PositionedHsp>> {
+    let mut table: Vec<Vec<PositionedHsp>> = Vec::new();
 
     // Initialize the table
     for _ in 0..protein_set.len() {
         table.push(vec![]);
     }
 
-    hsps.iter().for_each(|hsp| {
+    hsps.hsps().for_each(|hsp| {
         let protein1 = protein_set.get_protein_by_id(hsp.location(0).index());
         let protein2 = protein_set.get_protein_by_id(hsp.location(1).index());
         let hsp_score = score_hsp(
@@ -139,6 +394,7 @@ pub fn build_hsp_table(
             hsp.location(1).position(),
             hsp.len(),
             kmer_size,
+            matrix,
         );
 
         if interactors.contains(&hsp.location(0).index())
@@ -167,8 +423,15 @@ pub fn build_hsp_table(
         }
     });
 
+    // The HSPs are fully sorted so that the contributions are summed in the
+    // same order whatever the order of the HSPs in the source
     for row in &mut table {
-        row.sort_by_key(|partner| partner.0);
+        row.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then(a.4.cmp(&b.4))
+                .then(a.5.cmp(&b.5))
+                .then(a.2.total_cmp(&b.2))
+        });
     }
 
     table
@@ -181,77 +444,3 @@ pub fn get_1d_index(position1: usize, position2: usize) -> usize {
     let row_start = largest * (largest + 1) / 2;
     return row_start + smallest;
 }
-
-pub unsafe fn fill_dict(
-    target_index: usize,
-    interacting_pair: &(usize, usize),
-    hsps: &Vec<Vec<(usize, f32, f32, f32, usize, usize)>>,
-    kmer_size: f32,
-    protein_set: &ProteinSet,
-    contributions_dict: &ContributionsDict,
-) {
-    let dict_ptr = contributions_dict.dict.get();
-
-    if interacting_pair.0 == interacting_pair.1 {
-        for i in 0..hsps[interacting_pair.0].len() {
-            for j in i..hsps[interacting_pair.1].len() {
-                let hsp1 = &hsps[interacting_pair.0][i];
-                let hsp2 = &hsps[interacting_pair.1][j];
-
-                let term1 = hsp1.3 * (hsp2.2 - kmer_size + 1f32);
-                let term2 = hsp2.3 * (hsp1.2 - kmer_size + 1f32);
-                let contribution = (term1 + term2) / (hsp1.1 * hsp2.1); // TODO divide at the end
-
-                let p1 = protein_set.get_protein_by_id(hsp1.0);
-                let p2 = protein_set.get_protein_by_id(hsp2.0);
-                let p1_is_target = p1.index() == target_index;
-
-                if (p1.is_new() && p2.index() == target_index) || (p2.is_new() && p1.index() == target_index) {
-                    if p1_is_target {
-                        let vec = (*dict_ptr).get_mut(&p2.index()).unwrap();
-                        for i in 0..hsp1.2 as usize {
-                            vec[hsp1.5 + i] += contribution / hsp1.2; // Contribution is distributed over similarity region
-                        }
-                    } else {
-                        let vec = (*dict_ptr).get_mut(&p1.index()).unwrap();
-                        for i in 0..hsp2.2 as usize {
-                            vec[hsp2.5 + i] += contribution / hsp2.2; 
-                        }
-                    }
-                }
-            }
-        }
-    } else {
-        let hsps1 = &hsps[interacting_pair.0];
-
-        for hsp1 in hsps1 {
-            let hsps2 = &hsps[interacting_pair.1];
-
-            for hsp2 in hsps2 {
-                let term1 = hsp1.3 * (hsp2.2 - kmer_size + 1f32);
-                let term2 = hsp2.3 * (hsp1.2 - kmer_size + 1f32);
-                let contribution = (term1 + term2) / (hsp1.1 * hsp2.1); // TODO divide at the end
-
-
-                let p1 = protein_set.get_protein_by_id(hsp1.0);
-                let p2 = protein_set.get_protein_by_id(hsp2.0);
-                let p1_is_target = p1.index() == target_index;
-
-                if (p1.is_new() && p2.index() == target_index) || (p2.is_new() && p1.index() == target_index) {
-                    if p1_is_target {
-                        let vec = (*dict_ptr).get_mut(&p2.index()).unwrap();
-                        for i in 0..hsp1.2 as usize {
-                            vec[hsp1.5 + i] += contribution / hsp1.2;
-                        }
-                    } else {
-                        let vec = (*dict_ptr).get_mut(&p1.index()).unwrap();
-                        for i in 0..hsp2.2 as usize {
-                            vec[hsp2.5 + i] += contribution / hsp2.2;
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
-
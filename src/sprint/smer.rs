@@ -24,33 +24,42 @@ impl Smer {
     }
 
     pub fn get_aa_index_at(value: u64, position: usize, seed: &Seed) -> usize {
-        ((value >> ((seed.len() - position - 1) * 5)) & 31) as usize
+        match seed.shift(position) {
+            Some(shift) => ((value >> shift) & 31) as usize,
+            None => 0
+        }
     }
 
     pub fn as_string(value: u64, seed: &Seed) -> String {
         let mut sequence = "".to_string();
         for i in 0..seed.len() {
-            let value = value >> (5u64 * (seed.len() as u64 - i as u64 - 1)) & 31u64;
-            let amino_acid = *CODE_RESIDUE_MAP.get(&(value as u16)).unwrap();
+            // The positions that do not matter are shown as '-', and codes
+            // that are not residue codes (e.g. of a mutation by an arbitrary
+            // index) as '?'
+            let amino_acid = match seed.shift(i) {
+                Some(_) => {
+                    let value = Smer::get_aa_index_at(value, i, seed);
+                    CODE_RESIDUE_MAP.get(&(value as u16)).copied().unwrap_or('?')
+                }
+                None => '-',
+            };
             sequence.push(amino_acid);
         }
         sequence
     }
 
     pub fn to_sequence(&self, seed: &Seed) -> String {
-        let mut sequence = "".to_string();
-        for i in 0..seed.len() {
-            let value = self.value >> (5u64 * (seed.len() as u64 - i as u64 - 1)) & 31u64;
-            let amino_acid = *CODE_RESIDUE_MAP.get(&(value as u16)).unwrap();
-            sequence.push(amino_acid);
-        }
-        sequence
+        Smer::as_string(self.value, seed)
     }
 
     pub fn mutate(value: u64, position: usize, aa_index: u64, seed: &Seed) -> u64 {
-        let original = value & (31 << ((seed.len() - position - 1) * 5));
-        let new_value = value - original + (aa_index << ((seed.len() - position - 1) * 5));
-        new_value
+        match seed.shift(position) {
+            Some(shift) => {
+                let original = value & (31 << shift);
+                value - original + (aa_index << shift)
+            },
+            None => value
+        }
     }
 }
 
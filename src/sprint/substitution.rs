@@ -0,0 +1,314 @@
+use std::fs::read_to_string;
+
+use crate::sprint::constants::{BLOSUM45, BLOSUM62, B_CODE, MATRIX_RESIDUES, NUM_CODES, PAM120, PAM250, Z_CODE};
+use crate::sprint::error::SprintError;
+use crate::sprint::utils::fnv1a;
+
+/// Number of residues considered when enumerating similar s-mers
+/// (the 20 standard amino acids plus B, in SPRINT format)
+const NUM_ORDERED: usize = 21;
+
+/// Substitution matrix used to score s-mers, hits and HSPs
+///
+/// The scores are stored with a leading ``don't care`` row/column so that
+/// they can be indexed directly with the codes of `RESIDUE_CODES` (a code
+/// of 0 always yields a score of 0). The table is extended with the codes
+/// of B and Z, which score the (rounded) average of D/N and E/Q. The scores
+/// in SPRINT format and the table of residues ordered by decreasing
+/// similarity, needed to enumerate similar s-mers, are derived from the
+/// final scores (B being the last residue in SPRINT format).
+#[derive(Clone)]
+pub struct SubstitutionMatrix {
+    name: String,
+    scores: [[i16; NUM_CODES]; NUM_CODES],
+    sprint_scores: [[i16; NUM_ORDERED]; NUM_ORDERED],
+    ordered: [[usize; NUM_ORDERED]; NUM_ORDERED],
+}
+
+impl SubstitutionMatrix {
+    /// Creates a substitution matrix from a score table laid out like `constants::PAM120`
+    pub fn new(name: &str, scores: [[i16; 24]; 24]) -> Self {
+        let mut table = [[0i16; NUM_CODES]; NUM_CODES];
+        for (row, scores) in table.iter_mut().zip(scores.iter()) {
+            row[..24].copy_from_slice(scores);
+        }
+
+        // Non-important positions never contribute to a score
+        table[0] = [0; NUM_CODES];
+        for row in table.iter_mut() {
+            row[0] = 0;
+        }
+
+        // The ambiguous residues (computed from the standard residues only)
+        let codes: Vec<usize> = (1..21).chain([B_CODE, Z_CODE]).collect();
+        let mut averages = table;
+        for &i in codes.iter() {
+            for &j in codes.iter() {
+                if i >= B_CODE || j >= B_CODE {
+                    averages[i][j] = average_score(&table, i, j);
+                }
+            }
+        }
+
+        let mut sprint_scores = [[0i16; NUM_ORDERED]; NUM_ORDERED];
+        for (residue1, row) in sprint_scores.iter_mut().enumerate() {
+            for (residue2, score) in row.iter_mut().enumerate() {
+                *score = averages[sprint_code(residue1)][sprint_code(residue2)];
+            }
+        }
+
+        SubstitutionMatrix {
+            name: name.to_string(),
+            scores: averages,
+            sprint_scores,
+            ordered: order_residues(&sprint_scores),
+        }
+    }
+
+    pub fn pam120() -> Self {
+        SubstitutionMatrix::new("PAM120", PAM120)
+    }
+
+    pub fn pam250() -> Self {
+        SubstitutionMatrix::new("PAM250", PAM250)
+    }
+
+    pub fn blosum45() -> Self {
+        SubstitutionMatrix::new("BLOSUM45", BLOSUM45)
+    }
+
+    pub fn blosum62() -> Self {
+        SubstitutionMatrix::new("BLOSUM62", BLOSUM62)
+    }
+
+    /// Retrieves one of the built-in matrices by name (case insensitive)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "PAM120" => Some(SubstitutionMatrix::pam120()),
+            "PAM250" => Some(SubstitutionMatrix::pam250()),
+            "BLOSUM45" => Some(SubstitutionMatrix::blosum45()),
+            "BLOSUM62" => Some(SubstitutionMatrix::blosum62()),
+            _ => None,
+        }
+    }
+
+    /// Loads a matrix in the NCBI format (as distributed with BLAST)
+    ///
+    /// Lines starting with '#' are ignored. The first remaining line lists the
+    /// residues of the columns and every other line starts with the residue of
+    /// the row. All 20 standard amino acids are required; B, Z and X default to
+    /// a score of 0 when absent and any other symbol (e.g. '*') is ignored.
+    pub fn from_file(filepath: &str) -> Result<Self, SprintError> {
+        let contents = read_to_string(filepath).map_err(|error| SprintError::io(filepath, error))?;
+        let invalid = |line: Option<usize>, message: String| SprintError::parse(filepath, line, message);
+
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let columns: Vec<Option<usize>> = match lines.next() {
+            Some((_, header)) => header.split_whitespace().map(matrix_index).collect(),
+            None => return Err(invalid(None, "The substitution matrix is empty".to_string())),
+        };
+
+        let mut scores = [[0i16; 24]; 24];
+        let mut seen = [[false; 24]; 24];
+
+        for (number, line) in lines {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let row = match matrix_index(tokens[0]) {
+                Some(row) => row,
+                None => continue,
+            };
+
+            if tokens.len() - 1 != columns.len() {
+                return Err(invalid(Some(number), format!(
+                    "Row {} has {} scores but the header lists {} residues",
+                    tokens[0],
+                    tokens.len() - 1,
+                    columns.len()
+                )));
+            }
+
+            for (column, token) in columns.iter().zip(tokens[1..].iter()) {
+                if let Some(column) = column {
+                    scores[row][*column] = token
+                        .parse::<i16>()
+                        .map_err(|_| invalid(Some(number), format!("Invalid score {} in row {}", token, tokens[0])))?;
+                    seen[row][*column] = true;
+                }
+            }
+        }
+
+        // Every standard residue pair must be present
+        for i in 1..21 {
+            for j in 1..21 {
+                if !seen[i][j] {
+                    let residues: Vec<char> = MATRIX_RESIDUES.chars().collect();
+                    return Err(invalid(None, format!(
+                        "Missing score for the pair {}/{}",
+                        residues[i - 1],
+                        residues[j - 1]
+                    )));
+                }
+            }
+        }
+
+        let name = std::path::Path::new(filepath)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| filepath.to_string());
+
+        Ok(SubstitutionMatrix::new(&name, scores))
+    }
+
+    /// Loads a built-in matrix if `spec` is the name of one, otherwise
+    /// treats `spec` as the path of a matrix file
+    pub fn load(spec: &str) -> Result<Self, SprintError> {
+        match SubstitutionMatrix::from_name(spec) {
+            Some(matrix) => Ok(matrix),
+            None => SubstitutionMatrix::from_file(spec),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Hash of the scores, to tell apart matrices with the same name (the
+    /// averages of B and Z are left out, being derived from the others)
+    pub fn fingerprint(&self) -> u64 {
+        fnv1a(self.scores[..24].iter().flat_map(|row| row[..24].iter()).flat_map(|score| score.to_le_bytes()))
+    }
+
+    /// Score of two residues given their codes in `RESIDUE_CODES`
+    #[inline(always)]
+    pub fn score(&self, residue1: usize, residue2: usize) -> i16 {
+        self.scores[residue1][residue2]
+    }
+
+    /// Score of two residues given their indices in SPRINT format (code - 1
+    /// for the standard residues, 20 for B)
+    #[inline(always)]
+    pub fn sprint_score(&self, residue1: usize, residue2: usize) -> i16 {
+        self.sprint_scores[residue1][residue2]
+    }
+
+    /// Residues (SPRINT format) sorted by decreasing score against `residue`
+    #[inline(always)]
+    pub fn ordered(&self, residue: usize) -> &[usize; NUM_ORDERED] {
+        &self.ordered[residue]
+    }
+}
+
+impl Default for SubstitutionMatrix {
+    fn default() -> Self {
+        SubstitutionMatrix::pam120()
+    }
+}
+
+/// Index of a residue in the rows/columns of the score table
+fn matrix_index(residue: &str) -> Option<usize> {
+    if residue.len() != 1 {
+        return None;
+    }
+    MATRIX_RESIDUES
+        .find(residue.to_uppercase().as_str())
+        .map(|position| position + 1)
+}
+
+/// Residues an ambiguous residue stands for (a standard residue stands for
+/// itself)
+fn members(code: usize) -> Vec<usize> {
+    match code {
+        B_CODE => vec![3, 4],
+        Z_CODE => vec![6, 7],
+        _ => vec![code],
+    }
+}
+
+/// Average score of the residues two codes stand for
+fn average_score(scores: &[[i16; NUM_CODES]; NUM_CODES], code1: usize, code2: usize) -> i16 {
+    let (members1, members2) = (members(code1), members(code2));
+    let total: i16 = members1.iter().flat_map(|&i| members2.iter().map(move |&j| scores[i][j])).sum();
+    (total as f32 / (members1.len() * members2.len()) as f32).round() as i16
+}
+
+/// Code in `RESIDUE_CODES` of a residue in SPRINT format
+pub fn sprint_code(residue: usize) -> usize {
+    match residue {
+        20 => B_CODE,
+        _ => residue + 1,
+    }
+}
+
+/// For each residue (SPRINT format), sorts the other residues by decreasing
+/// score (ties are broken by residue index)
+fn order_residues(scores: &[[i16; NUM_ORDERED]; NUM_ORDERED]) -> [[usize; NUM_ORDERED]; NUM_ORDERED] {
+    let mut ordered = [[0usize; NUM_ORDERED]; NUM_ORDERED];
+
+    for (residue, row) in ordered.iter_mut().enumerate() {
+        let mut others: Vec<usize> = (0..NUM_ORDERED).collect();
+        others.sort_by_key(|&other| -scores[residue][other]);
+        row.copy_from_slice(&others);
+    }
+
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Residues ordered by decreasing PAM120 score, as hard-coded before the
+    /// order was derived from the matrix (B being scored with its own column)
+    const PAM120_ORDERED_SPRINT: [[usize; 21]; 21] = [
+        [0, 7, 14, 15, 16, 3, 6, 19, 20, 2, 5, 9, 11, 12, 1, 4, 8, 10, 13, 18, 17],
+        [1, 11, 5, 8, 17, 2, 12, 14, 15, 9, 16, 20, 0, 3, 6, 19, 4, 7, 10, 13, 18],
+        [2, 20, 3, 8, 6, 11, 15, 5, 7, 16, 0, 1, 9, 14, 18, 12, 19, 10, 13, 17, 4],
+        [3, 20, 6, 2, 5, 0, 7, 8, 15, 11, 16, 1, 9, 14, 19, 12, 10, 18, 4, 13, 17],
+        [4, 15, 18, 0, 9, 16, 19, 1, 7, 8, 14, 2, 12, 13, 20, 3, 5, 6, 10, 11, 17],
+        [5, 8, 6, 1, 3, 2, 11, 14, 20, 0, 12, 10, 15, 16, 7, 9, 19, 18, 13, 17, 4],
+        [6, 3, 20, 5, 2, 0, 7, 8, 11, 15, 14, 16, 1, 9, 12, 19, 10, 18, 4, 13, 17],
+        [7, 0, 15, 2, 3, 20, 6, 16, 14, 19, 5, 11, 1, 4, 8, 9, 12, 10, 13, 18, 17],
+        [8, 5, 2, 1, 20, 3, 6, 14, 18, 11, 15, 0, 10, 13, 16, 17, 19, 4, 7, 9, 12],
+        [9, 19, 10, 12, 13, 16, 0, 1, 2, 15, 18, 3, 4, 5, 6, 11, 14, 20, 7, 8, 17],
+        [10, 12, 9, 19, 13, 5, 18, 0, 8, 14, 16, 17, 1, 2, 6, 11, 15, 20, 3, 7, 4],
+        [11, 1, 2, 5, 12, 20, 3, 6, 15, 16, 0, 8, 14, 7, 9, 10, 19, 17, 18, 4, 13],
+        [12, 10, 9, 19, 11, 1, 5, 13, 16, 0, 15, 2, 6, 14, 3, 7, 8, 18, 20, 4, 17],
+        [13, 18, 9, 10, 12, 17, 8, 15, 19, 0, 2, 16, 1, 7, 14, 20, 4, 5, 3, 6, 11],
+        [14, 0, 15, 5, 1, 8, 16, 2, 6, 7, 11, 19, 20, 3, 9, 10, 12, 4, 13, 18, 17],
+        [15, 16, 0, 2, 7, 14, 3, 4, 20, 1, 6, 11, 5, 8, 9, 12, 17, 19, 13, 18, 10],
+        [16, 15, 0, 2, 9, 19, 20, 3, 7, 11, 12, 14, 1, 5, 6, 4, 8, 10, 18, 13, 17],
+        [17, 1, 13, 15, 18, 8, 10, 2, 11, 5, 9, 12, 16, 20, 0, 14, 3, 4, 6, 7, 19],
+        [18, 13, 4, 8, 2, 9, 10, 17, 15, 16, 19, 20, 0, 12, 1, 3, 5, 6, 11, 7, 14],
+        [19, 9, 10, 12, 0, 16, 7, 14, 15, 1, 2, 3, 4, 5, 6, 8, 13, 18, 20, 11, 17],
+        [3, 20, 2, 6, 8, 0, 5, 7, 11, 15, 16, 1, 14, 9, 18, 19, 10, 12, 13, 4, 17],
+    ];
+
+    fn standard(row: &[usize]) -> Vec<usize> {
+        row.iter().cloned().filter(|&residue| residue < 20).collect()
+    }
+
+    #[test]
+    fn pam120_order_matches_the_hard_coded_table() {
+        let matrix = SubstitutionMatrix::pam120();
+        for residue in 0..20 {
+            // B now scores the average of D/N, so only its rank may differ
+            assert_eq!(standard(matrix.ordered(residue)), standard(&PAM120_ORDERED_SPRINT[residue]), "row {}", residue);
+        }
+    }
+
+    #[test]
+    fn b_is_ordered_by_its_averaged_scores() {
+        let matrix = SubstitutionMatrix::pam120();
+        assert_eq!(matrix.sprint_score(20, 0), matrix.score(B_CODE, 1));
+        assert_eq!(matrix.sprint_score(3, 20), matrix.score(4, B_CODE));
+        for residue in 0..21 {
+            let scores: Vec<i16> = matrix.ordered(residue).iter().map(|&other| matrix.sprint_score(residue, other)).collect();
+            assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]), "row {}", residue);
+        }
+    }
+}
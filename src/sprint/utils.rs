@@ -1,4 +1,5 @@
 use crate::sprint::seed::Seed;
+use crate::sprint::substitution::sprint_code;
 
 pub fn matrix_max(matrix: &Vec<Vec<f32>>) -> f32 {
     let mut max = 0f32;
@@ -14,9 +15,9 @@ pub fn matrix_max(matrix: &Vec<Vec<f32>>) -> f32 {
 
 /// Converts an s-mer from rSPRINT format to SPRINT format, i.e. with
 /// -1 for every "matter" position
-pub fn convert_smer_to_sprint(smer: u64) -> u64 {
+pub fn convert_smer_to_sprint(smer: u64, seed: &Seed) -> u64 {
     let mut sprint_smer: u64 = 0;
-    for i in 0..12 {
+    for i in 0..seed.weight() {
         let val = (smer >> (i * 5)) & 31;
         let amino_acid_index = if val == 0 { 0 } else { val - 1 };
         sprint_smer += amino_acid_index << (i * 5);
@@ -25,15 +26,31 @@ pub fn convert_smer_to_sprint(smer: u64) -> u64 {
 }
 
 /// Converts an s-mer in SPRINT format to rSPRINT format, i.e. with
-/// +1 for every "matter" position
+/// +1 for every "matter" position (B being 20 in SPRINT format)
 pub fn convert_smer_to_pysprint(smer: u64, seed: &Seed) -> u64 {
     let mut pysprint_smer: u64 = 0;
-    for i in 0..12 {
+    for i in 0..seed.weight() {
         if ((seed.value() >> (i * 5)) & 31) == 0 {
             continue;
         }
-        let amino_acid_index = ((smer >> (i * 5)) & 31) + 1;
+        let amino_acid_index = sprint_code(((smer >> (i * 5)) & 31) as usize) as u64;
         pysprint_smer += amino_acid_index << (i * 5);
     }
     pysprint_smer
 }
+
+/// 64-bit FNV-1a hash, used to fingerprint inputs
+pub fn fnv1a<I: IntoIterator<Item = u8>>(bytes: I) -> u64 {
+    fnv1a_extend(0xcbf29ce484222325, bytes)
+}
+
+/// Continues a 64-bit FNV-1a hash with more bytes (e.g. to hash a file
+/// one buffer at a time)
+pub fn fnv1a_extend<I: IntoIterator<Item = u8>>(hash: u64, bytes: I) -> u64 {
+    let mut hash = hash;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
@@ -0,0 +1,161 @@
+use clap::{CommandFactory, FromArgMatches, Parser};
+
+use sprint::sprint::error::SprintError;
+use sprint::sprint::options::{ExtractionOptions, SequenceOptions};
+use sprint::sprint::compression::staged_path;
+use sprint::sprint::distributed::Worker;
+use sprint::sprint::extraction::extract_hsps;
+use sprint::sprint::fileio::{load_hsps, open_hsps, save_fasta, save_hsps};
+use sprint::sprint::hsp::HSP;
+use sprint::sprint::hspfile::{is_binary_hsp_file, save_binary_hsps, HspFile, HspParameters};
+use sprint::sprint::processing::{process_hsp_sources, update_processed_hsps};
+use sprint::sprint::sink::{HspSink, ShardedHspWriter};
+
+/// Number of existing HSPs copied to the output at a time
+const COPY_BATCH_SIZE: usize = 100000;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct UpdateArgs {
+    #[clap(value_parser, short='i', long="sequences")]
+    pub sequences_path: String,
+
+    /// HSPs to update (the parameters of binary HSP files are checked
+    /// against the requested ones, the ones of text HSP files cannot be)
+    #[clap(value_parser, short='s', long="hsps")]
+    pub hsps_path: String,
+
+    #[clap(value_parser, short='n', long="new")]
+    pub new_path: String,
+
+    #[clap(value_parser, short='o', long="output")]
+    pub output_path: String,
+
+    #[clap(value_parser, short='u', long="updated_sequences")]
+    pub updated_sequences_path: Option<String>,
+
+    #[clap(value_parser, long="processed_output")]
+    pub processed_output_path: Option<String>,
+
+    #[clap(value_parser, long="processed_hsps", requires="processed-output-path")]
+    pub processed_hsps_path: Option<String>,
+
+    #[clap(value_parser, short='c', long="count_threshold", default_value="40")]
+    pub count_threshold: u16,
+
+    #[clap(action, short='b', long="binary")]
+    pub binary: bool,
+
+    #[clap(value_parser, long="shards", default_value="64")]
+    pub shards: usize,
+
+    #[clap(flatten)]
+    pub extraction: ExtractionOptions,
+
+    #[clap(flatten)]
+    pub sequences: SequenceOptions,
+}
+
+fn main() -> Result<(), SprintError> {
+    let args = parse_args();
+
+    // Load the sequences the HSPs were extracted from and append the new ones
+    let (mut protein_set, report) = args.sequences.load(&args.sequences_path)?;
+    report.print_warnings(&args.sequences_path);
+    let (new_proteins, report) = args.sequences.load_fasta(&args.new_path, true)?;
+    report.print_warnings(&args.new_path);
+    println!("Adding {} proteins to the {} existing proteins...", new_proteins.len(), protein_set.len());
+    protein_set.add_new(new_proteins, true)?;
+
+    let matrix = args.extraction.matrix()?;
+    let seeds = args.extraction.seeds()?;
+
+    let mut parameters = HspParameters {
+        kmer_size: args.extraction.scoring.kmer_size,
+        t_sim: Some(args.extraction.t_sim),
+        t_hsp: Some(args.extraction.t_hsp),
+        t_count: None,
+        matrix: Some(matrix.name()),
+        seeds: seeds.iter().map(|seed| seed.as_string()).collect(),
+    };
+
+    // The new HSPs must be extracted like the existing ones (only binary
+    // HSP files store their parameters)
+    if is_binary_hsp_file(&args.hsps_path)? {
+        HspFile::open(&args.hsps_path, &protein_set)?
+            .header()
+            .parameters
+            .check_extraction(&parameters)
+            .map_err(|error| SprintError::invalid_input(format!("{}: {}", args.hsps_path, error)))?;
+    }
+
+    // Extract the HSPs involving at least one new protein
+    let settings = args.extraction.settings(&matrix, &seeds);
+    let new_hsps = extract_hsps(&protein_set, &settings, Worker::single(true), true, true);
+    println!("Found {} new HSPs.", new_hsps.len());
+
+    // Merge the new HSPs with the existing ones (the output is staged so
+    // that the existing HSP file can be updated in place, and its staged
+    // path keeps the extension that selects its compression)
+    let staged_output = staged_path(&args.output_path);
+    let old_hsps = open_hsps(&args.hsps_path, &protein_set)?;
+    let mut writer = ShardedHspWriter::new(&staged_output, protein_set.len(), args.shards)?;
+
+    let mut batch: Vec<HSP> = Vec::with_capacity(COPY_BATCH_SIZE);
+    for hsp in old_hsps.hsps() {
+        batch.push(hsp);
+        if batch.len() == COPY_BATCH_SIZE {
+            writer.send(std::mem::take(&mut batch))?;
+        }
+    }
+    writer.send(batch)?;
+    writer.send(new_hsps.iter().cloned().collect())?;
+
+    if args.binary {
+        writer.finish_binary(&protein_set, &parameters)?;
+    } else {
+        writer.finish_text(&protein_set)?;
+    }
+
+    // Process the merged HSPs
+    if let Some(processed_output_path) = &args.processed_output_path {
+        // The old raw HSPs are visited again rather than loaded
+        let processed = match &args.processed_hsps_path {
+            Some(processed_hsps_path) => update_processed_hsps(
+                &protein_set,
+                &*old_hsps,
+                &new_hsps,
+                load_hsps(processed_hsps_path, &protein_set)?,
+                args.extraction.scoring.kmer_size,
+                args.count_threshold,
+                true
+            ),
+            None => process_hsp_sources(&protein_set, &[&*old_hsps, &new_hsps], args.extraction.scoring.kmer_size, args.count_threshold, true),
+        };
+
+        let staged_processed = staged_path(processed_output_path);
+        if args.binary {
+            parameters.t_count = Some(args.count_threshold);
+            save_binary_hsps(&processed, &protein_set, &parameters, &staged_processed)?;
+        } else {
+            save_hsps(processed, &protein_set, &staged_processed)?;
+        }
+        std::fs::rename(&staged_processed, processed_output_path)?;
+    }
+
+    drop(old_hsps);
+    std::fs::rename(&staged_output, &args.output_path)?;
+
+    // Save the sequences the updated HSPs correspond to
+    if let Some(updated_sequences_path) = &args.updated_sequences_path {
+        save_fasta(&protein_set, updated_sequences_path)?;
+    }
+
+    Ok(())
+}
+
+/// Parses the arguments, the HSP threshold keeping its short flag (-t)
+fn parse_args() -> UpdateArgs {
+    let command = UpdateArgs::command().mut_arg("t-hsp", |arg| arg.short('t'));
+    UpdateArgs::from_arg_matches(&command.get_matches()).unwrap_or_else(|error| error.exit())
+}
@@ -0,0 +1,175 @@
+use std::collections::{HashMap, HashSet};
+
+use sprint::sprint::distributed::Worker;
+use sprint::sprint::extraction::{extract_hsps, ExtractionSettings};
+use sprint::sprint::hsp::HSP;
+use sprint::sprint::location::Location;
+use sprint::sprint::model::SprintModel;
+use sprint::sprint::prediction::{score_interactions, ScoringSettings};
+use sprint::sprint::protein::Protein;
+use sprint::sprint::proteinset::ProteinSet;
+use sprint::sprint::seed::default_seeds;
+use sprint::sprint::sites::{compute_contributions, ResidueWeighting};
+use sprint::sprint::substitution::SubstitutionMatrix;
+
+const AMINO_ACIDS: &[u8] = b"ACDEFGHIKLMNPQRSTVWY";
+const DOMAIN_LENGTH: usize = 40;
+
+/// Linear congruential generator, so that the test data is always the same
+struct Generator(u64);
+
+impl Generator {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % bound as u64) as usize
+    }
+
+    fn sequence(&mut self, length: usize) -> String {
+        (0..length).map(|_| AMINO_ACIDS[self.next(AMINO_ACIDS.len())] as char).collect()
+    }
+
+    /// Copy of a sequence with a few substitutions
+    fn mutate(&mut self, sequence: &str) -> String {
+        sequence
+            .chars()
+            .map(|residue| match self.next(10) {
+                0 => AMINO_ACIDS[self.next(AMINO_ACIDS.len())] as char,
+                _ => residue,
+            })
+            .collect()
+    }
+}
+
+/// Proteins sharing domains, peptides similar to some of the domains, the
+/// HSPs between the copies of every domain and training pairs (including a
+/// homodimer)
+fn dataset() -> (ProteinSet, HashSet<HSP>, Vec<(String, String)>) {
+    let mut generator = Generator(42);
+    let domains: Vec<String> = (0..6).map(|_| generator.sequence(DOMAIN_LENGTH)).collect();
+
+    // Locations of the copies of every domain
+    let mut copies: Vec<Vec<Location>> = vec![vec![]; domains.len()];
+
+    let mut proteins = vec![];
+    for index in 0..30 {
+        let mut sequence = generator.sequence(20);
+        for _ in 0..2 {
+            let domain = generator.next(domains.len());
+            copies[domain].push(Location::new(index, sequence.len()));
+            sequence.push_str(&generator.mutate(&domains[domain]));
+            sequence.push_str(&generator.sequence(15));
+        }
+        proteins.push(Protein::new(index, format!("P{}", index), sequence, false).unwrap());
+    }
+    let mut protein_set = ProteinSet::new(proteins).unwrap();
+
+    let peptides = (0..4)
+        .map(|domain| {
+            copies[domain].push(Location::new(30 + domain, 0));
+            Protein::new(30 + domain, format!("pep{}", domain), generator.mutate(&domains[domain]), true).unwrap()
+        })
+        .collect();
+    protein_set.add_new(peptides, true).unwrap();
+
+    let mut hsps = HashSet::new();
+    for locations in copies.iter() {
+        for (i, location1) in locations.iter().enumerate() {
+            for location2 in locations[i + 1..].iter() {
+                hsps.insert(HSP::new(location1.clone(), location2.clone(), DOMAIN_LENGTH));
+            }
+        }
+    }
+
+    let mut training_pairs = vec![("P0".to_string(), "P0".to_string())];
+    for _ in 0..40 {
+        let protein1 = generator.next(30);
+        let protein2 = generator.next(30);
+        training_pairs.push((format!("P{}", protein1), format!("P{}", protein2)));
+    }
+
+    (protein_set, hsps, training_pairs)
+}
+
+fn run_with_threads<T: Send>(threads: usize, task: impl FnOnce() -> T + Send) -> T {
+    rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap().install(task)
+}
+
+#[test]
+fn scores_do_not_depend_on_the_number_of_threads() {
+    let (protein_set, hsps, training_pairs) = dataset();
+    let matrix = SubstitutionMatrix::load("PAM120").unwrap();
+
+    let score = |threads| {
+        run_with_threads(threads, || {
+            score_interactions(&protein_set, &hsps, &training_pairs, &ScoringSettings { kmer_size: 20, matrix: &matrix }, Worker::single(false))
+                .values()
+                .iter()
+                .map(|score| score.to_bits())
+                .collect::<Vec<u32>>()
+        })
+    };
+
+    let single_threaded = score(1);
+    assert!(single_threaded.iter().any(|&score| score != 0));
+    for threads in [2, 8] {
+        assert_eq!(single_threaded, score(threads));
+    }
+}
+
+#[test]
+fn contributions_do_not_depend_on_the_number_of_threads() {
+    let (protein_set, hsps, training_pairs) = dataset();
+    let matrix = SubstitutionMatrix::load("PAM120").unwrap();
+
+    let contribute = |threads| {
+        run_with_threads(threads, || {
+            let weighting = ResidueWeighting::Uniform;
+            compute_contributions(&"P3".to_string(), &protein_set, &hsps, &training_pairs, &ScoringSettings { kmer_size: 20, matrix: &matrix }, weighting, Worker::single(false))
+                .unwrap()
+                .into_iter()
+                .map(|(peptide, values)| (peptide, values.iter().map(|value| value.to_bits()).collect()))
+                .collect::<HashMap<usize, Vec<u32>>>()
+        })
+    };
+
+    let single_threaded = contribute(1);
+    assert_eq!(single_threaded.len(), 4);
+    assert!(single_threaded.values().flatten().any(|&value| value != 0));
+    for threads in [2, 8] {
+        assert_eq!(single_threaded, contribute(threads));
+    }
+}
+
+#[test]
+fn model_scores_are_the_scores_of_new_proteins() {
+    let (protein_set, _, training_pairs) = dataset();
+    let matrix = SubstitutionMatrix::load("PAM120").unwrap();
+    let seeds = default_seeds().into_iter().take(1).collect::<Vec<_>>();
+
+    // Scores and contributions of the peptides added to the proteins, as in `peptide-scorer`
+    let proteins: Vec<Protein> = protein_set.iter().filter(|protein| !protein.is_new()).cloned().collect();
+    let peptides: Vec<Protein> = protein_set.iter().filter(|protein| protein.is_new()).cloned().collect();
+    let settings = ExtractionSettings { kmer_size: 20, t_sim: 25, t_hit: 35, matrix: &matrix, seeds: &seeds };
+    let hsps = extract_hsps(&ProteinSet::new(proteins.clone()).unwrap(), &settings, Worker::single(false), false, true);
+    let mut all_hsps = extract_hsps(&protein_set, &settings, Worker::single(false), true, true);
+    all_hsps.extend(hsps.iter().cloned());
+    let expected = score_interactions(&protein_set, &all_hsps, &training_pairs, &settings.scoring(), Worker::single(false));
+    let weighting = ResidueWeighting::Uniform;
+    let expected_contributions =
+        compute_contributions(&"P3".to_string(), &protein_set, &all_hsps, &training_pairs, &settings.scoring(), weighting, Worker::single(false))
+            .unwrap();
+
+    let model = SprintModel::new(ProteinSet::new(proteins.clone()).unwrap(), &hsps, training_pairs, &settings, false);
+    let scores = model.score(&peptides).unwrap();
+    let contributions = model.contributions(&peptides, "P3", weighting).unwrap();
+
+    assert!(scores.iter().flatten().any(|&score| score != 0f32));
+    assert!(contributions.iter().flatten().any(|&value| value != 0f32));
+    for (peptide, row) in peptides.iter().zip(scores.iter()) {
+        let expected_row: Vec<u32> = (0..proteins.len()).map(|protein| expected.get(peptide.index(), protein).to_bits()).collect();
+        assert_eq!(row.iter().map(|score| score.to_bits()).collect::<Vec<u32>>(), expected_row);
+    }
+    for (peptide, values) in peptides.iter().zip(contributions.iter()) {
+        assert_eq!(values, &expected_contributions[&peptide.index()]);
+    }
+}